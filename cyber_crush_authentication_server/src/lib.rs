@@ -0,0 +1,608 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_logging, server_health::{self, HealthCheck}, server_metrics::{self, TimedQuery}, server_openapi, common::{self, LegacyFailureBody, ErrorResponse, ResponseStatus, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, authorization::UserPermissions, input_validation::InputLimits, login_lockout::LoginLockout, password_hashing::{HashedPassword, PasswordCheck, PasswordHashing}, server_rate_limit::{RateLimitLayer, RateLimitGroup, ClientIp, ClientIpState}};
+
+use axum::{
+    extract::{Json, State},
+    routing::{get, post},
+    Router,
+};
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use serde_json::json;
+use sqlx::{PgPool, types::chrono::{DateTime, Utc}};
+use tracing::error;
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::authentication::{LoginRequest, LoginResponse, ValidateTokenResponse, SessionInfo, GetSessionsResponse, RevokeSessionRequest, RevokeSessionResponse, LogoutResponse, LogoutAllResponse, ChangePasswordRequest, ChangePasswordResponse, LinkCharacterRequest, LinkCharacterResponse, SwitchCharacterRequest, SwitchCharacterResponse, LinkedCharacter, GetLinkedCharactersResponse};
+
+mod user_management;
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    hello, login, validate_token, get_sessions, revoke_session, logout, logout_all, change_password, link_character, switch_character, get_linked_characters,
+    user_management::create_user, user_management::import_users, user_management::update_user, user_management::disable_user,
+    user_management::enable_user, user_management::delete_user, user_management::reset_password, user_management::get_roles, user_management::set_role, user_management::impersonate,
+))]
+struct AuthenticationApi;
+
+#[derive(Debug)]
+struct ServerState {
+    password_hashing: Arc<PasswordHashing>,
+    db_pool: PgPool, //This is thread safe
+    session_tokens: SessionTokens,
+    login_lockout: LoginLockout,
+    input_limits: InputLimits,
+    trust_forwarded_for: bool,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+impl ClientIpState for ServerState {
+    fn trust_forwarded_for(&self) -> bool {
+        self.trust_forwarded_for
+    }
+}
+
+/// OpenAPI document of the authentication server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("authentication", env!("CARGO_PKG_VERSION"), AuthenticationApi::openapi())
+}
+
+pub fn create_router(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{
+        session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()),
+        login_lockout: LoginLockout::new(server_configuration.login_lockout, db_pool.clone()),
+        password_hashing: Arc::new(PasswordHashing::new(&server_configuration.password_hashing)),
+        db_pool,
+        input_limits: server_configuration.input_limits.clone(),
+        trust_forwarded_for: server_configuration.rate_limit_trust_forwarded_for,
+    });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/login", post(login).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Login)))
+        .route("/validate_token", post(validate_token))
+        .route("/get_sessions", get(get_sessions))
+        .route("/revoke_session", post(revoke_session))
+        .route("/logout", post(logout))
+        .route("/logout_all", post(logout_all))
+        .route("/change_password", post(change_password).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Login)))
+        .route("/link_character", post(link_character).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Login)))
+        .route("/switch_character", post(switch_character))
+        .route("/get_linked_characters", get(get_linked_characters))
+        .route("/admin/create_user", post(user_management::create_user))
+        .route("/admin/import_users", post(user_management::import_users))
+        .route("/admin/update_user", post(user_management::update_user))
+        .route("/admin/disable_user", post(user_management::disable_user))
+        .route("/admin/enable_user", post(user_management::enable_user))
+        .route("/admin/delete_user", post(user_management::delete_user))
+        .route("/admin/get_roles", get(user_management::get_roles))
+        .route("/admin/set_role", post(user_management::set_role))
+        .route("/admin/impersonate", post(user_management::impersonate))
+        .route("/admin/reset_password", post(user_management::reset_password).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Login)))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("authentication", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "authentication", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/login", LegacyFailureBody::Fields(json!({ "token": "" }))),
+        ("/validate_token", LegacyFailureBody::Status),
+    ]
+}
+
+#[utoipa::path(get, path = "/hello", tag = "authentication", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush authentication server!"
+}
+
+/// Logs the user in with a new session token, valid until `token_expires_at`. Unknown usernames and wrong
+/// passwords get the same error. Too many failed logins of a username or from an IP lock them out for a while.
+#[utoipa::path(post, path = "/login", tag = "authentication", request_body = LoginRequest, responses(
+    (status = 200, body = LoginResponse),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "WRONG_CREDENTIALS", body = ErrorResponse),
+    (status = 403, description = "USER_DISABLED", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED, LOGIN_LOCKED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn login(State(state): State<Arc<ServerState>>, ClientIp(client_ip): ClientIp, Json(payload): Json<LoginRequest>) -> Result<Json<LoginResponse>, ServerError> {
+    state.input_limits.validate_login(&payload.username, &payload.password)?;
+    if let Some(device_label) = &payload.device_label {
+        state.input_limits.validate_device_label(device_label)?;
+    }
+
+    let password_query = check_credentials(&state, &payload.username, &payload.password, client_ip).await?;
+    server_logging::record_user_id(password_query.id);
+
+    let issued_token = state.session_tokens.start_session(password_query.id, password_query.permissions, payload.device_label.as_deref()).await?;
+
+    server_metrics::record_login(true);
+    Ok(Json(LoginResponse{ response_status: ResponseStatus::success(), token: issued_token.token, token_expires_at: issued_token.expires_at, session_id: issued_token.token_id }))
+}
+
+/// Succeeds while the token is valid: signed by the backend, not expired and its session not revoked.
+/// Checks the session in the database, so a revocation is seen at once, and updates its last seen time.
+#[utoipa::path(post, path = "/validate_token", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn validate_token(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<ValidateTokenResponse>, ServerError> {
+    let session_id = match &user.session_id {
+        Some(session_id) => session_id,
+        None => {
+            return Ok(Json(ResponseStatus::success()));
+        }
+    };
+
+    let session_query = sqlx::query_scalar::<_, i32>(
+        "UPDATE sessions SET last_seen_at = NOW() WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW() RETURNING user_id"
+        )
+        .bind(session_id)
+        .fetch_optional(&state.db_pool)
+        .timed("validate_session")
+        .await;
+
+    match session_query {
+        Ok(Some(_)) => Ok(Json(ResponseStatus::success())),
+        Ok(None) => Err(ServerError::unauthorized("INVALID_TOKEN", "Session not found or revoked")),
+        Err(error) => {
+            error!("Failed to validate the session of user_id {}: {}", user.user_id, error);
+            Err(ServerError::internal("Token validation server internal error."))
+        }
+    }
+}
+
+/// Active sessions of the user, one per login on a device.
+#[utoipa::path(get, path = "/get_sessions", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = GetSessionsResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_sessions(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetSessionsResponse>, ServerError> {
+    let sessions_query = sqlx::query_as::<_, SessionInfo>(
+        r#"
+        SELECT id AS session_id, device_label, created_at, last_seen_at, expires_at, id = $2 AS is_current
+        FROM sessions
+        WHERE (user_id = $1 OR id IN (SELECT session_id FROM session_characters WHERE user_id = $1))
+            AND NOT is_hack AND impersonated_by IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_seen_at DESC
+        "#
+        )
+        .bind(user.user_id)
+        .bind(user.session_id.as_deref().unwrap_or_default())
+        .fetch_all(&state.db_pool)
+        .timed("get_sessions")
+        .await;
+
+    match sessions_query {
+        Ok(sessions) => Ok(Json(GetSessionsResponse{ response_status: ResponseStatus::success(), sessions })),
+        Err(error) => {
+            error!("Failed to get the sessions of user_id {}: {}", user.user_id, error);
+            Err(ServerError::internal("Sessions not found. Internal server error"))
+        }
+    }
+}
+
+/// Logs one of the user's devices out. Other servers reject its token within `session_token_revocation_refresh_seconds`.
+#[utoipa::path(post, path = "/revoke_session", tag = "authentication", security(("token" = [])), request_body = RevokeSessionRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "SESSION_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn revoke_session(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<RevokeSessionRequest>) -> Result<Json<RevokeSessionResponse>, ServerError> {
+    match state.session_tokens.revoke_session(user.user_id, &payload.session_id).await? {
+        true => Ok(Json(ResponseStatus::success())),
+        false => Err(ServerError::not_found("SESSION_NOT_FOUND", "No active session with this id")),
+    }
+}
+
+/// Logs out the session of the token that made the request.
+#[utoipa::path(post, path = "/logout", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn logout(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<LogoutResponse>, ServerError> {
+    match &user.session_id {
+        Some(session_id) => {
+            state.session_tokens.revoke_session(user.user_id, session_id).await?;
+        },
+        None => {
+            clear_legacy_token(&state.db_pool, user.user_id).await?;
+        }
+    }
+
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Logs out every session of the user, the one of the requesting token included.
+#[utoipa::path(post, path = "/logout_all", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn logout_all(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<LogoutAllResponse>, ServerError> {
+    state.session_tokens.revoke_user_sessions(user.user_id, None).await?;
+    // The session of a linked character belongs to the character that logged in.
+    if let Some(session_id) = &user.session_id {
+        state.session_tokens.revoke_session(user.user_id, session_id).await?;
+    }
+    unlink_character(&state.db_pool, user.user_id, None).await?;
+    clear_legacy_token(&state.db_pool, user.user_id).await?;
+
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Changes the password after checking the old one. Every other session of the user is logged out.
+#[utoipa::path(post, path = "/change_password", tag = "authentication", security(("token" = [])), request_body = ChangePasswordRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "WRONG_PASSWORD", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn change_password(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ChangePasswordRequest>) -> Result<Json<ChangePasswordResponse>, ServerError> {
+    state.input_limits.validate_password(&payload.old_password)?;
+    state.input_limits.validate_password(&payload.new_password)?;
+
+    #[derive(sqlx::FromRow)]
+    struct PasswordQuery {
+        password: String,
+        password_pepper_version: i32,
+    }
+
+    let password_query = sqlx::query_as::<_, PasswordQuery>("SELECT password, password_pepper_version FROM users WHERE id = $1")
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .timed("change_password_old_password")
+        .await;
+
+    let stored_password = match password_query {
+        Ok(Some(stored_password)) => stored_password,
+        Ok(None) => {
+            return Err(ServerError::invalid_token());
+        },
+        Err(error) => {
+            error!("Password change failed for user_id {}. Error: {}", user.user_id, error);
+            return Err(ServerError::internal("Password change database error."));
+        }
+    };
+
+    let password_check = state.password_hashing.verify_in_background(stored_password.password, stored_password.password_pepper_version, payload.old_password).await;
+    match password_check {
+        Ok(PasswordCheck::Correct | PasswordCheck::CorrectOutdated) => {},
+        Ok(PasswordCheck::Wrong) => {
+            return Err(ServerError::forbidden("WRONG_PASSWORD", "Wrong old password."));
+        },
+        Err(error) => {
+            error!("Password change failed for user_id {}. Error: {}", user.user_id, error);
+            return Err(ServerError::internal("Password decodeing error."));
+        }
+    }
+
+    let new_password = match state.password_hashing.hash_in_background(payload.new_password).await {
+        Ok(new_password) => new_password,
+        Err(error) => {
+            error!("Password change failed for user_id {}. Error: {}", user.user_id, error);
+            return Err(ServerError::internal("Password hashing error."));
+        }
+    };
+
+    if let Err(error) = store_password(&state.db_pool, user.user_id, &new_password, None).await {
+        error!("Password change failed for user_id {}. Error: {}", user.user_id, error);
+        return Err(ServerError::internal("Password change database error."));
+    }
+
+    // A legacy token can only be told apart from the other legacy tokens by clearing it, so it is kept when it made the request.
+    state.session_tokens.revoke_user_sessions(user.user_id, user.session_id.as_deref()).await?;
+    unlink_character(&state.db_pool, user.user_id, user.session_id.as_deref()).await?;
+    if user.session_id.is_some() {
+        clear_legacy_token(&state.db_pool, user.user_id).await?;
+    }
+
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Adds another character to the session after checking its credentials, so the device can switch to it
+/// with `/switch_character`. Shares the login rate limit and lockout.
+#[utoipa::path(post, path = "/link_character", tag = "authentication", security(("token" = [])), request_body = LinkCharacterRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT, SESSION_REQUIRED", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN, WRONG_CREDENTIALS", body = ErrorResponse),
+    (status = 403, description = "USER_DISABLED", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED, LOGIN_LOCKED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn link_character(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, ClientIp(client_ip): ClientIp, Json(payload): Json<LinkCharacterRequest>) -> Result<Json<LinkCharacterResponse>, ServerError> {
+    let session_id = required_session_id(&user)?;
+    state.input_limits.validate_login(&payload.username, &payload.password)?;
+
+    let character = check_credentials(&state, &payload.username, &payload.password, client_ip).await?;
+
+    // The character that logged in is part of the session already.
+    let link_insertion = sqlx::query(
+        r#"
+        INSERT INTO session_characters (session_id, user_id)
+        SELECT $1, $2
+        WHERE NOT EXISTS (SELECT 1 FROM sessions WHERE id = $1 AND user_id = $2)
+        ON CONFLICT DO NOTHING
+        "#
+        )
+        .bind(session_id)
+        .bind(character.id)
+        .execute(&state.db_pool)
+        .timed("link_character")
+        .await;
+
+    match link_insertion {
+        Ok(_) => {
+            server_metrics::record_login(true);
+            Ok(Json(ResponseStatus::success()))
+        },
+        Err(error) => {
+            error!("Failed to link user_id {} to a session of user_id {}: {}", character.id, user.user_id, error);
+            Err(ServerError::internal("Internal server error"))
+        }
+    }
+}
+
+/// Returns a token of the current session for another of its characters. Every server acts as the
+/// character of the token it receives, so the client switches by using the new token.
+#[utoipa::path(post, path = "/switch_character", tag = "authentication", security(("token" = [])), request_body = SwitchCharacterRequest, responses(
+    (status = 200, body = LoginResponse),
+    (status = 400, description = "SESSION_REQUIRED", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "CHARACTER_NOT_LINKED", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn switch_character(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<SwitchCharacterRequest>) -> Result<Json<SwitchCharacterResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct CharacterQuery {
+        id: i32,
+        #[sqlx(flatten)]
+        permissions: UserPermissions,
+        expires_at: DateTime<Utc>,
+    }
+
+    let session_id = required_session_id(&user)?;
+
+    // The requesting character is checked as well, it may have been unlinked since its token was issued.
+    let character_query = sqlx::query_as::<_, CharacterQuery>(
+        r#"
+        WITH bundle AS (
+            SELECT user_id, expires_at FROM sessions WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ),
+        members AS (
+            SELECT user_id FROM bundle
+            UNION
+            SELECT user_id FROM session_characters WHERE session_id = $1
+        )
+        SELECT users.id, user_permissions(users.id) AS permissions, bundle.expires_at
+        FROM bundle
+        JOIN users ON users.username = $2
+        WHERE users.id IN (SELECT user_id FROM members) AND $3 IN (SELECT user_id FROM members)
+        "#
+        )
+        .bind(session_id)
+        .bind(&payload.username)
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .timed("switch_character")
+        .await;
+
+    let character = match character_query {
+        Ok(Some(character)) => character,
+        Ok(None) => {
+            return Err(ServerError::not_found("CHARACTER_NOT_LINKED", "No character with this username is linked to the session."));
+        },
+        Err(error) => {
+            error!("Failed to switch the character of a session of user_id {}: {}", user.user_id, error);
+            return Err(ServerError::internal("Internal server error"));
+        }
+    };
+
+    match state.session_tokens.issue_for_session(session_id, character.expires_at, character.id, character.permissions, user.impersonated_by) {
+        Ok(issued_token) => Ok(Json(LoginResponse{ response_status: ResponseStatus::success(), token: issued_token.token, token_expires_at: issued_token.expires_at, session_id: issued_token.token_id })),
+        Err(error) => {
+            error!("Switching to user_id {} failed for user_id {}. Error: {}", character.id, user.user_id, error);
+            Err(ServerError::internal("Internal server error"))
+        }
+    }
+}
+
+/// Characters of the current session, the one that logged in first.
+#[utoipa::path(get, path = "/get_linked_characters", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = GetLinkedCharactersResponse),
+    (status = 400, description = "SESSION_REQUIRED", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_linked_characters(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetLinkedCharactersResponse>, ServerError> {
+    let session_id = required_session_id(&user)?;
+
+    let characters_query = sqlx::query_as::<_, LinkedCharacter>(
+        r#"
+        SELECT users.username, users.id = sessions.user_id AS is_owner, users.id = $2 AS is_active
+        FROM sessions
+        JOIN users ON users.id = sessions.user_id OR users.id IN (SELECT user_id FROM session_characters WHERE session_id = sessions.id)
+        WHERE sessions.id = $1
+            AND (sessions.user_id = $2 OR EXISTS (SELECT 1 FROM session_characters WHERE session_id = $1 AND user_id = $2))
+        ORDER BY is_owner DESC, users.username
+        "#
+        )
+        .bind(session_id)
+        .bind(user.user_id)
+        .fetch_all(&state.db_pool)
+        .timed("get_linked_characters")
+        .await;
+
+    match characters_query {
+        Ok(characters) => Ok(Json(GetLinkedCharactersResponse{ response_status: ResponseStatus::success(), characters })),
+        Err(error) => {
+            error!("Failed to get the linked characters of a session of user_id {}: {}", user.user_id, error);
+            Err(ServerError::internal("Linked characters not found. Internal server error"))
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CredentialsQuery {
+    id: i32,
+    password: String,
+    password_pepper_version: i32,
+    #[sqlx(flatten)]
+    permissions: UserPermissions,
+    is_disabled: bool,
+}
+
+async fn check_credentials(state: &ServerState, username: &str, password: &str, client_ip: Option<IpAddr>) -> Result<CredentialsQuery, ServerError> {
+    state.login_lockout.check(username, client_ip)?;
+
+    let password_query = sqlx::query_as::<_, CredentialsQuery>(
+        r#"SELECT id, password, password_pepper_version, user_permissions(id) AS permissions, disabled_at IS NOT NULL AS is_disabled FROM users WHERE username = $1"#
+        )
+        .bind(username)
+        .fetch_optional(&state.db_pool)
+        .timed("login_password")
+        .await;
+
+    let password_query = match password_query {
+        Ok(password_query) => password_query,
+        Err(error) => {
+            error!("Login failed for user: {}. Error: {}", username, error);
+            return Err(ServerError::internal("Login database error."));
+        }
+    };
+
+    let password_query = match password_query {
+        Some(password_query) => password_query,
+        None => {
+            state.password_hashing.verify_dummy_in_background(password.to_string()).await;
+            server_metrics::record_login(false);
+            state.login_lockout.record_failure(username, client_ip).await;
+            return Err(wrong_credentials());
+        }
+    };
+
+    let password_check = state.password_hashing.verify_in_background(password_query.password.clone(), password_query.password_pepper_version, password.to_string()).await;
+    match password_check {
+        Ok(PasswordCheck::Correct) => {},
+        Ok(PasswordCheck::CorrectOutdated) => {
+            rehash_password(state, &password_query, password).await;
+        },
+        Ok(PasswordCheck::Wrong) => {
+            server_metrics::record_login(false);
+            state.login_lockout.record_failure(username, client_ip).await;
+            return Err(wrong_credentials());
+        },
+        Err(error) => {
+            error!("Login failed for user: {}. Error: {}", username, error);
+            return Err(ServerError::internal("Password decodeing error."));
+        }
+    }
+
+    state.login_lockout.record_success(username);
+
+    // Only told to players who know the password.
+    if password_query.is_disabled {
+        server_metrics::record_login(false);
+        return Err(ServerError::forbidden("USER_DISABLED", "This user is disabled."));
+    }
+
+    Ok(password_query)
+}
+
+// Unknown usernames and wrong passwords get the same error, so guessing tells nothing about which usernames exist.
+fn wrong_credentials() -> ServerError {
+    ServerError::unauthorized("WRONG_CREDENTIALS", "Wrong username or password.")
+}
+
+/// Replaces a hash made with old Argon2 parameters or an old pepper. The login succeeds even when this fails.
+async fn rehash_password(state: &ServerState, credentials: &CredentialsQuery, password: &str) {
+    let new_password = match state.password_hashing.hash_in_background(password.to_string()).await {
+        Ok(new_password) => new_password,
+        Err(error) => {
+            error!("Rehashing the password of user_id {} failed. Error: {}", credentials.id, error);
+            return;
+        }
+    };
+
+    // A password changed since it was read is not overwritten.
+    if let Err(error) = store_password(&state.db_pool, credentials.id, &new_password, Some(&credentials.password)).await {
+        error!("Rehashing the password of user_id {} failed. Error: {}", credentials.id, error);
+    }
+}
+
+// Legacy tokens have no session to link characters to.
+fn required_session_id(user: &AuthenticatedUser) -> Result<&str, ServerError> {
+    match &user.session_id {
+        Some(session_id) => Ok(session_id),
+        None => Err(ServerError::bad_request("SESSION_REQUIRED", "Log in again to use linked characters.")),
+    }
+}
+
+/// Stores a new password hash, only over `replaced_hash` when it is given.
+async fn store_password(db_pool: &PgPool, user_id: i32, password: &HashedPassword, replaced_hash: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET password = $1, password_pepper_version = $2 WHERE id = $3 AND ($4::TEXT IS NULL OR password = $4)")
+        .bind(&password.hash)
+        .bind(password.pepper_version)
+        .bind(user_id)
+        .bind(replaced_hash)
+        .execute(db_pool)
+        .timed("store_password")
+        .await
+        .map(|_| ())
+}
+
+async fn clear_legacy_token(db_pool: &PgPool, user_id: i32) -> Result<(), ServerError> {
+    let token_deletion = sqlx::query("UPDATE users SET user_token = NULL, user_token_hash = NULL WHERE id = $1")
+        .bind(user_id)
+        .execute(db_pool)
+        .timed("clear_legacy_token")
+        .await;
+
+    match token_deletion {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            error!("Failed to clear the legacy token of user_id {}: {}", user_id, error);
+            Err(ServerError::internal("Internal server error"))
+        }
+    }
+}
+
+/// Removes the user from the sessions it is a linked character of, except `except_session_id`.
+async fn unlink_character(db_pool: &PgPool, user_id: i32, except_session_id: Option<&str>) -> Result<(), ServerError> {
+    let link_deletion = sqlx::query("DELETE FROM session_characters WHERE user_id = $1 AND session_id IS DISTINCT FROM $2")
+        .bind(user_id)
+        .bind(except_session_id)
+        .execute(db_pool)
+        .timed("unlink_character")
+        .await;
+
+    match link_deletion {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            error!("Failed to unlink user_id {} from its sessions: {}", user_id, error);
+            Err(ServerError::internal("Internal server error"))
+        }
+    }
+}
@@ -0,0 +1,936 @@
+use shared_server_lib::{common::{ErrorResponse, ResponseStatus, ServerError}, authentication::AuthenticatedUser, authorization::UserPermissions, input_validation::InputLimits, password_hashing::HashedPassword, server_metrics::TimedQuery};
+
+use axum::extract::{Json, State};
+
+use std::sync::Arc;
+use sqlx::{PgConnection, PgPool};
+use tracing::{error, info};
+
+use cyber_crush_protocol::authentication::{Permission, CreateUserRequest, CreatedUser, CreateUserResponse, ImportUsersRequest, ImportUsersResponse, UpdateUserRequest, UpdateUserResponse, ManagedUserRequest, DisableUserResponse, EnableUserResponse, DeleteUserResponse, ResetPasswordRequest, ResetPasswordResponse, Role, GetRolesResponse, SetRoleRequest, SetRoleResponse, ImpersonateResponse};
+
+use crate::{ServerState, clear_legacy_token, unlink_character};
+
+// Serializes user creation, so concurrent requests never pick the same personal number.
+const USER_CREATION_LOCK_KEY: i64 = 7_243_002;
+
+/// Creates a user with a random personal number. Every `/admin` endpoint needs the `manage_users` permission.
+#[utoipa::path(post, path = "/admin/create_user", tag = "authentication", security(("token" = [])), request_body = CreateUserRequest, responses(
+    (status = 200, body = CreateUserResponse),
+    (status = 400, description = "INVALID_INPUT, UNKNOWN_ROLE", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 409, description = "USERNAME_TAKEN, NO_PERSONAL_NUMBERS_LEFT", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn create_user(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<CreateUserRequest>) -> Result<Json<CreateUserResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut created_users = insert_users(&state, vec![payload]).await?;
+    let created_user = created_users.remove(0);
+
+    info!("User {} created by user_id {}", created_user.username, user.user_id);
+    Ok(Json(CreateUserResponse{ response_status: ResponseStatus::success(), user: created_user }))
+}
+
+/// Creates every user of an `example_users.json` array, or none of them when one fails.
+#[utoipa::path(post, path = "/admin/import_users", tag = "authentication", security(("token" = [])), request_body = ImportUsersRequest, responses(
+    (status = 200, body = ImportUsersResponse),
+    (status = 400, description = "INVALID_INPUT, UNKNOWN_ROLE", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 409, description = "USERNAME_TAKEN, NO_PERSONAL_NUMBERS_LEFT", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn import_users(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ImportUsersRequest>) -> Result<Json<ImportUsersResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+
+    let users = insert_users(&state, payload).await?;
+
+    info!("{} user(s) imported by user_id {}", users.len(), user.user_id);
+    Ok(Json(ImportUsersResponse{ response_status: ResponseStatus::success(), users }))
+}
+
+/// Changes the roles, cyber defence level or extra data of a user. A change of the user's permissions logs
+/// the user out of every session, so it applies at once. User managers can not change their own roles.
+#[utoipa::path(post, path = "/admin/update_user", tag = "authentication", security(("token" = [])), request_body = UpdateUserRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT, UNKNOWN_ROLE", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "OWN_USER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn update_user(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<UpdateUserRequest>) -> Result<Json<UpdateUserResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct UpdateQuery {
+        id: i32,
+        permissions: Vec<String>,
+    }
+
+    user.require(Permission::ManageUsers)?;
+    if let Some(cyber_defence_level) = payload.cyber_defence_level {
+        state.input_limits.validate_cyber_defence_level(cyber_defence_level)?;
+    }
+    if let Some(extra_data) = &payload.extra_data {
+        state.input_limits.validate_extra_data(extra_data)?;
+    }
+
+    let mut transaction = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Failed to start a user update transaction: {}", error);
+            return Err(ServerError::internal("User update database error."));
+        }
+    };
+
+    // The permissions are returned from before the roles change, the row lock serializes updates of the user.
+    let update_query = sqlx::query_as::<_, UpdateQuery>(
+        r#"
+        UPDATE users SET
+            cyber_defence_level = COALESCE($2, cyber_defence_level),
+            extra_data = COALESCE($3, extra_data)
+        WHERE username = $1
+        RETURNING id, user_permissions(id) AS permissions
+        "#
+        )
+        .bind(&payload.username)
+        .bind(payload.cyber_defence_level)
+        .bind(payload.extra_data.as_ref().map(sqlx::types::Json))
+        .fetch_optional(&mut *transaction)
+        .timed("update_user")
+        .await;
+
+    let updated_user = match update_query {
+        Ok(Some(updated_user)) => updated_user,
+        Ok(None) => {
+            return Err(user_not_found());
+        },
+        Err(error) => {
+            error!("Failed to update user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User update database error."));
+        }
+    };
+
+    if payload.roles.is_some() && updated_user.id == user.user_id {
+        let _ = transaction.rollback().await;
+        return Err(ServerError::conflict("OWN_USER", "Another user manager has to change your own roles."));
+    }
+
+    let mut permissions_changed = false;
+    if let Some(roles) = &payload.roles {
+        let roles_removal = sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
+            .bind(updated_user.id)
+            .execute(&mut *transaction)
+            .timed("remove_user_roles")
+            .await;
+
+        if let Err(error) = roles_removal {
+            error!("Failed to remove the roles of user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User update database error."));
+        }
+
+        assign_roles(&mut transaction, updated_user.id, roles.clone()).await?;
+        permissions_changed = user_permissions(&mut transaction, updated_user.id).await? != updated_user.permissions;
+    }
+
+    if let Err(error) = transaction.commit().await {
+        error!("Failed to commit the update of user {} for user_id {}: {}", payload.username, user.user_id, error);
+        return Err(ServerError::internal("User update database error."));
+    }
+
+    if permissions_changed {
+        log_out_everywhere(&state, updated_user.id).await?;
+    }
+
+    info!("User {} updated by user_id {}", payload.username, user.user_id);
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Logs the user out of every session and blocks its logins until `/admin/enable_user`.
+#[utoipa::path(post, path = "/admin/disable_user", tag = "authentication", security(("token" = [])), request_body = ManagedUserRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "OWN_USER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn disable_user(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ManagedUserRequest>) -> Result<Json<DisableUserResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+
+    let disable_query = sqlx::query_scalar::<_, i32>("UPDATE users SET disabled_at = COALESCE(disabled_at, NOW()) WHERE username = $1 AND id <> $2 RETURNING id")
+        .bind(&payload.username)
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .timed("disable_user")
+        .await;
+
+    let user_id = match disable_query {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            return Err(managed_user_not_found(&state.db_pool, &payload.username, user.user_id).await);
+        },
+        Err(error) => {
+            error!("Failed to disable user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User disabling database error."));
+        }
+    };
+
+    log_out_everywhere(&state, user_id).await?;
+
+    info!("User {} disabled by user_id {}", payload.username, user.user_id);
+    Ok(Json(ResponseStatus::success()))
+}
+
+#[utoipa::path(post, path = "/admin/enable_user", tag = "authentication", security(("token" = [])), request_body = ManagedUserRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "OWN_USER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn enable_user(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ManagedUserRequest>) -> Result<Json<EnableUserResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+
+    let enable_query = sqlx::query("UPDATE users SET disabled_at = NULL WHERE username = $1 AND id <> $2")
+        .bind(&payload.username)
+        .bind(user.user_id)
+        .execute(&state.db_pool)
+        .timed("enable_user")
+        .await;
+
+    match enable_query {
+        Ok(result) if result.rows_affected() == 1 => {
+            info!("User {} enabled by user_id {}", payload.username, user.user_id);
+            Ok(Json(ResponseStatus::success()))
+        },
+        Ok(_) => Err(managed_user_not_found(&state.db_pool, &payload.username, user.user_id).await),
+        Err(error) => {
+            error!("Failed to enable user {} for user_id {}: {}", payload.username, user.user_id, error);
+            Err(ServerError::internal("User enabling database error."))
+        }
+    }
+}
+
+/// Deletes the user with its chats messages, news articles and hack log. Users with bank transactions
+/// can only be disabled, the transactions are part of the other players' history.
+#[utoipa::path(post, path = "/admin/delete_user", tag = "authentication", security(("token" = [])), request_body = ManagedUserRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "OWN_USER, USER_HAS_TRANSACTIONS", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn delete_user(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ManagedUserRequest>) -> Result<Json<DeleteUserResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+
+    let mut transaction = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Failed to start a user deletion transaction: {}", error);
+            return Err(ServerError::internal("User deletion database error."));
+        }
+    };
+
+    // Locking the bank account blocks transfers of the user until the deletion is committed, and as the
+    // transactions are checked after the lock is taken, the check sees every transfer committed before it.
+    let user_query = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT users.id FROM users
+        LEFT JOIN LATERAL (SELECT id FROM bank_accounts WHERE bank_accounts.user_id = users.id FOR UPDATE) AS bank_account ON TRUE
+        WHERE username = $1 AND users.id <> $2
+        FOR UPDATE OF users
+        "#
+        )
+        .bind(&payload.username)
+        .bind(user.user_id)
+        .fetch_optional(&mut *transaction)
+        .timed("delete_user_lock")
+        .await;
+
+    let deleted_user_id = match user_query {
+        Ok(Some(deleted_user_id)) => deleted_user_id,
+        Ok(None) => {
+            let _ = transaction.rollback().await;
+            return Err(managed_user_not_found(&state.db_pool, &payload.username, user.user_id).await);
+        },
+        Err(error) => {
+            error!("Failed to delete user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User deletion database error."));
+        }
+    };
+
+    let transactions_query = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM bank_transactions
+            JOIN bank_accounts ON bank_accounts.id IN (bank_transactions.sender_id, bank_transactions.receiver_id)
+            WHERE bank_accounts.user_id = $1
+        )
+        "#
+        )
+        .bind(deleted_user_id)
+        .fetch_one(&mut *transaction)
+        .timed("delete_user_check")
+        .await;
+
+    match transactions_query {
+        Ok(false) => (),
+        Ok(true) => {
+            let _ = transaction.rollback().await;
+            return Err(ServerError::conflict("USER_HAS_TRANSACTIONS", "The user has bank transactions, disable it instead."));
+        },
+        Err(error) => {
+            error!("Failed to check the bank transactions of user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User deletion database error."));
+        }
+    }
+
+    // Revoked before the deletion, the revoked sessions outlive the user so its tokens stay rejected.
+    let revoked_session_ids = state.session_tokens.revoke_user_sessions_in(&mut transaction, deleted_user_id, None).await?;
+
+    let user_deletion = sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(deleted_user_id)
+        .execute(&mut *transaction)
+        .timed("delete_user")
+        .await;
+
+    if let Err(error) = user_deletion {
+        error!("Failed to delete user {} for user_id {}: {}", payload.username, user.user_id, error);
+        return Err(ServerError::internal("User deletion database error."));
+    }
+
+    if let Err(error) = transaction.commit().await {
+        error!("Failed to commit the deletion of user {} for user_id {}: {}", payload.username, user.user_id, error);
+        return Err(ServerError::internal("User deletion database error."));
+    }
+
+    state.session_tokens.record_revoked_sessions(revoked_session_ids).await;
+
+    let expired_sessions_deletion = sqlx::query("DELETE FROM sessions WHERE user_id IS NULL AND expires_at < NOW()")
+        .execute(&state.db_pool)
+        .timed("delete_orphaned_sessions")
+        .await;
+
+    if let Err(error) = expired_sessions_deletion {
+        error!("Failed to delete the expired sessions of deleted users: {}", error);
+    }
+
+    info!("User {} deleted by user_id {}", payload.username, user.user_id);
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Sets a new password, logs the user out of every session and lifts its login lockout.
+#[utoipa::path(post, path = "/admin/reset_password", tag = "authentication", security(("token" = [])), request_body = ResetPasswordRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn reset_password(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ResetPasswordRequest>) -> Result<Json<ResetPasswordResponse>, ServerError> {
+    user.require(Permission::ManageUsers)?;
+    state.input_limits.validate_new_user(&payload.username, &payload.new_password)?;
+
+    let new_password = match state.password_hashing.hash_in_background(payload.new_password.clone()).await {
+        Ok(new_password) => new_password,
+        Err(error) => {
+            error!("Password reset of user {} failed. Error: {}", payload.username, error);
+            return Err(ServerError::internal("Password hashing error."));
+        }
+    };
+
+    let password_update = sqlx::query_scalar::<_, i32>("UPDATE users SET password = $1, password_pepper_version = $2 WHERE username = $3 RETURNING id")
+        .bind(&new_password.hash)
+        .bind(new_password.pepper_version)
+        .bind(&payload.username)
+        .fetch_optional(&state.db_pool)
+        .timed("reset_password")
+        .await;
+
+    let user_id = match password_update {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            return Err(user_not_found());
+        },
+        Err(error) => {
+            error!("Password reset of user {} failed for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("Password reset database error."));
+        }
+    };
+
+    log_out_everywhere(&state, user_id).await?;
+    state.login_lockout.record_success(&payload.username);
+
+    info!("Password of user {} reset by user_id {}", payload.username, user.user_id);
+    Ok(Json(ResponseStatus::success()))
+}
+
+#[utoipa::path(get, path = "/admin/get_roles", tag = "authentication", security(("token" = [])), responses(
+    (status = 200, body = GetRolesResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn get_roles(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetRolesResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct RoleQuery {
+        name: String,
+        description: String,
+        permissions: Vec<String>,
+    }
+
+    user.require(Permission::ManageUsers)?;
+
+    let roles_query = sqlx::query_as::<_, RoleQuery>(
+        r#"
+        SELECT roles.name, roles.description,
+            COALESCE(ARRAY_AGG(role_permissions.permission ORDER BY role_permissions.permission) FILTER (WHERE role_permissions.permission IS NOT NULL), '{}') AS permissions
+        FROM roles
+        LEFT JOIN role_permissions ON role_permissions.role_id = roles.id
+        GROUP BY roles.id
+        ORDER BY roles.name
+        "#
+        )
+        .fetch_all(&state.db_pool)
+        .timed("get_roles")
+        .await;
+
+    match roles_query {
+        Ok(roles) => {
+            let roles = roles.into_iter()
+                .map(|role| Role{
+                    name: role.name,
+                    description: role.description,
+                    permissions: role.permissions.iter().filter_map(|permission| Permission::from_name(permission)).collect(),
+                })
+                .collect();
+
+            Ok(Json(GetRolesResponse{ response_status: ResponseStatus::success(), roles }))
+        },
+        Err(error) => {
+            error!("Failed to get the roles for user_id {}: {}", user.user_id, error);
+            Err(ServerError::internal("Role database error."))
+        }
+    }
+}
+
+/// Creates a role or replaces one. When its permissions change, every user of the role is logged out of
+/// every session, so the change applies at once.
+#[utoipa::path(post, path = "/admin/set_role", tag = "authentication", security(("token" = [])), request_body = SetRoleRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn set_role(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<SetRoleRequest>) -> Result<Json<SetRoleResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct RoleQuery {
+        id: i32,
+        permissions: Vec<String>,
+    }
+
+    user.require(Permission::ManageUsers)?;
+    state.input_limits.validate_role(&payload.name, &payload.description)?;
+
+    let mut permissions: Vec<String> = payload.permissions.iter().map(|permission| permission.name().to_string()).collect();
+    permissions.sort();
+    permissions.dedup();
+
+    let mut transaction = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Failed to start a role update transaction: {}", error);
+            return Err(ServerError::internal("Role database error."));
+        }
+    };
+
+    // The permissions are read from before the statement, so they are the old ones of an existing role.
+    let role_query = sqlx::query_as::<_, RoleQuery>(
+        r#"
+        WITH role AS (
+            INSERT INTO roles (name, description) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET description = EXCLUDED.description
+            RETURNING id
+        )
+        SELECT id, ARRAY(SELECT permission FROM role_permissions WHERE role_id = role.id ORDER BY permission) AS permissions
+        FROM role
+        "#
+        )
+        .bind(&payload.name)
+        .bind(&payload.description)
+        .fetch_one(&mut *transaction)
+        .timed("set_role")
+        .await;
+
+    let role = match role_query {
+        Ok(role) => role,
+        Err(error) => {
+            error!("Failed to set role {} for user_id {}: {}", payload.name, user.user_id, error);
+            return Err(ServerError::internal("Role database error."));
+        }
+    };
+
+    let mut role_users = Vec::new();
+    if role.permissions != permissions {
+        let permissions_replacement = sqlx::query_scalar::<_, i32>(
+            r#"
+            WITH removed_permissions AS (
+                DELETE FROM role_permissions WHERE role_id = $1
+            ),
+            added_permissions AS (
+                INSERT INTO role_permissions (role_id, permission) SELECT $1, UNNEST($2::TEXT[])
+            )
+            SELECT user_id FROM user_roles WHERE role_id = $1
+            "#
+            )
+            .bind(role.id)
+            .bind(&permissions)
+            .fetch_all(&mut *transaction)
+            .timed("set_role_permissions")
+            .await;
+
+        role_users = match permissions_replacement {
+            Ok(role_users) => role_users,
+            Err(error) => {
+                error!("Failed to set the permissions of role {} for user_id {}: {}", payload.name, user.user_id, error);
+                return Err(ServerError::internal("Role database error."));
+            }
+        };
+    }
+
+    if let Err(error) = transaction.commit().await {
+        error!("Failed to commit role {} for user_id {}: {}", payload.name, user.user_id, error);
+        return Err(ServerError::internal("Role database error."));
+    }
+
+    for role_user_id in &role_users {
+        log_out_everywhere(&state, *role_user_id).await?;
+    }
+
+    info!("Role {} set by user_id {}, {} user(s) logged out", payload.name, user.user_id, role_users.len());
+    Ok(Json(ResponseStatus::success()))
+}
+
+/// Signs a short-lived token of another user for the game master, e.g. to act as an NPC. The token is flagged
+/// with the game master's user id and every request made with it is recorded in the impersonation audit log.
+#[utoipa::path(post, path = "/admin/impersonate", tag = "authentication", security(("token" = [])), request_body = ManagedUserRequest, responses(
+    (status = 200, body = ImpersonateResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_USER_MANAGEMENT_PRIVILEGE, USER_DISABLED", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "OWN_USER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn impersonate(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ManagedUserRequest>) -> Result<Json<ImpersonateResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct ImpersonatedUserQuery {
+        id: i32,
+        #[sqlx(flatten)]
+        permissions: UserPermissions,
+        is_disabled: bool,
+    }
+
+    user.require(Permission::ManageUsers)?;
+
+    let user_query = sqlx::query_as::<_, ImpersonatedUserQuery>(
+        "SELECT id, user_permissions(id) AS permissions, disabled_at IS NOT NULL AS is_disabled FROM users WHERE username = $1"
+        )
+        .bind(&payload.username)
+        .fetch_optional(&state.db_pool)
+        .timed("impersonate")
+        .await;
+
+    let impersonated_user = match user_query {
+        Ok(Some(impersonated_user)) => impersonated_user,
+        Ok(None) => {
+            return Err(user_not_found());
+        },
+        Err(error) => {
+            error!("Failed to impersonate user {} for user_id {}: {}", payload.username, user.user_id, error);
+            return Err(ServerError::internal("User management database error."));
+        }
+    };
+
+    if impersonated_user.id == user.user_id {
+        return Err(ServerError::conflict("OWN_USER", "Use your own token to act as yourself."));
+    }
+
+    if impersonated_user.is_disabled {
+        return Err(ServerError::forbidden("USER_DISABLED", "This user is disabled."));
+    }
+
+    let issued_token = state.session_tokens.start_impersonation_session(user.user_id, impersonated_user.id, impersonated_user.permissions).await?;
+
+    let impersonation = AuthenticatedUser{
+        user_id: impersonated_user.id,
+        permissions: impersonated_user.permissions,
+        session_id: Some(issued_token.token_id.clone()),
+        impersonated_by: Some(user.user_id),
+    };
+    state.session_tokens.record_impersonated_action(&impersonation, "Impersonation started", None).await?;
+
+    Ok(Json(ImpersonateResponse{
+        response_status: ResponseStatus::success(),
+        token: issued_token.token,
+        token_expires_at: issued_token.expires_at,
+        session_id: issued_token.token_id,
+    }))
+}
+
+fn user_not_found() -> ServerError {
+    ServerError::not_found("USER_NOT_FOUND", "No user with this username")
+}
+
+// Tells a missing user apart from the requesting one, which can not disable, enable or delete itself.
+async fn managed_user_not_found(db_pool: &PgPool, username: &str, user_id: i32) -> ServerError {
+    let own_user_query = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1 AND id = $2)")
+        .bind(username)
+        .bind(user_id)
+        .fetch_one(db_pool)
+        .timed("managed_user_not_found")
+        .await;
+
+    match own_user_query {
+        Ok(true) => ServerError::conflict("OWN_USER", "Another user manager has to disable, enable or delete your own user."),
+        Ok(false) => user_not_found(),
+        Err(error) => {
+            error!("Failed to look up user {}: {}", username, error);
+            ServerError::internal("User management database error.")
+        }
+    }
+}
+
+async fn log_out_everywhere(state: &ServerState, user_id: i32) -> Result<(), ServerError> {
+    state.session_tokens.revoke_user_sessions(user_id, None).await?;
+    unlink_character(&state.db_pool, user_id, None).await?;
+    clear_legacy_token(&state.db_pool, user_id).await
+}
+
+/// Validates and hashes every user, then inserts them with their roles in one transaction.
+async fn insert_users(state: &Arc<ServerState>, users: Vec<CreateUserRequest>) -> Result<Vec<CreatedUser>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct InsertedUser {
+        id: i32,
+        #[sqlx(flatten)]
+        user: CreatedUser,
+    }
+
+    validate_new_users(&state.input_limits, &users)?;
+    let hashed_users = hash_passwords(state, users).await?;
+
+    let mut transaction = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Failed to start a user creation transaction: {}", error);
+            return Err(ServerError::internal("User creation database error."));
+        }
+    };
+
+    if let Err(error) = sqlx::query("SELECT pg_advisory_xact_lock($1)").bind(USER_CREATION_LOCK_KEY).execute(&mut *transaction).await {
+        error!("Failed to take the user creation lock: {}", error);
+        return Err(ServerError::internal("User creation database error."));
+    }
+
+    let personal_numbers_query = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT personal_number FROM generate_series(1000, 9999) AS personal_number
+        WHERE personal_number NOT IN (SELECT personal_number FROM users)
+        ORDER BY random()
+        LIMIT $1
+        "#
+        )
+        .bind(hashed_users.len() as i64)
+        .fetch_all(&mut *transaction)
+        .timed("get_free_personal_numbers")
+        .await;
+
+    let personal_numbers = match personal_numbers_query {
+        Ok(personal_numbers) if personal_numbers.len() == hashed_users.len() => personal_numbers,
+        Ok(personal_numbers) => {
+            return Err(ServerError::conflict("NO_PERSONAL_NUMBERS_LEFT", format!("Only {} free personal numbers left.", personal_numbers.len())));
+        },
+        Err(error) => {
+            error!("Failed to get free personal numbers: {}", error);
+            return Err(ServerError::internal("User creation database error."));
+        }
+    };
+
+    let mut created_users = Vec::with_capacity(hashed_users.len());
+    for ((new_user, password), personal_number) in hashed_users.into_iter().zip(personal_numbers) {
+        let user_insertion = sqlx::query_as::<_, InsertedUser>(
+            r#"
+            INSERT INTO users (username, password, password_pepper_version, personal_number, cyber_defence_level, extra_data)
+            VALUES ($1, $2, $3, $4, $5, COALESCE($6, '{}'::JSONB))
+            RETURNING id, username, personal_number
+            "#
+            )
+            .bind(&new_user.username)
+            .bind(&password.hash)
+            .bind(password.pepper_version)
+            .bind(personal_number)
+            .bind(new_user.cyber_defence_level)
+            .bind(new_user.extra_data.as_ref().map(sqlx::types::Json))
+            .fetch_one(&mut *transaction)
+            .timed("create_user")
+            .await;
+
+        let inserted_user = match user_insertion {
+            Ok(inserted_user) => inserted_user,
+            Err(error) if error.as_database_error().and_then(|error| error.constraint()) == Some("users_username_key") => {
+                return Err(ServerError::conflict("USERNAME_TAKEN", format!("Username {} is taken.", new_user.username)));
+            },
+            Err(error) => {
+                error!("Failed to create user {}: {}", new_user.username, error);
+                return Err(ServerError::internal("User creation database error."));
+            }
+        };
+
+        assign_roles(&mut transaction, inserted_user.id, requested_roles(&new_user)).await?;
+        created_users.push(inserted_user.user);
+    }
+
+    if let Err(error) = transaction.commit().await {
+        error!("Failed to commit the creation of {} user(s): {}", created_users.len(), error);
+        return Err(ServerError::internal("User creation database error."));
+    }
+
+    Ok(created_users)
+}
+
+// The error names the first invalid user, so a users file can be fixed before it is imported again.
+fn validate_new_users(input_limits: &InputLimits, users: &[CreateUserRequest]) -> Result<(), ServerError> {
+    for new_user in users {
+        let user_validation = input_limits.validate_new_user(&new_user.username, &new_user.password)
+            .and_then(|_| input_limits.validate_cyber_defence_level(new_user.cyber_defence_level))
+            .and_then(|_| match &new_user.extra_data {
+                Some(extra_data) => input_limits.validate_extra_data(extra_data),
+                None => Ok(()),
+            });
+
+        if let Err(error) = user_validation {
+            return Err(ServerError::bad_request("INVALID_INPUT", format!("User {}: {}", new_user.username, error)));
+        }
+    }
+
+    Ok(())
+}
+
+// Hashing a whole import takes seconds, so it runs off the request threads.
+async fn hash_passwords(state: &Arc<ServerState>, users: Vec<CreateUserRequest>) -> Result<Vec<(CreateUserRequest, HashedPassword)>, ServerError> {
+    let hashing_state = state.clone();
+    let hashing = tokio::task::spawn_blocking(move || {
+        users.into_iter()
+            .map(|new_user| hashing_state.password_hashing.hash(&new_user.password).map(|password| (new_user, password)))
+            .collect::<Result<Vec<_>, String>>()
+    }).await;
+
+    match hashing {
+        Ok(Ok(hashed_users)) => Ok(hashed_users),
+        Ok(Err(error)) => {
+            error!("Hashing the passwords of new users failed. Error: {}", error);
+            Err(ServerError::internal("Password hashing error."))
+        },
+        Err(error) => {
+            error!("Hashing the passwords of new users failed. Error: {}", error);
+            Err(ServerError::internal("Password hashing error."))
+        }
+    }
+}
+
+// The flags of the users files stand for the roles with the same permission.
+fn requested_roles(new_user: &CreateUserRequest) -> Vec<String> {
+    let flag_roles = [(new_user.can_publish_posts, "journalist"), (new_user.can_hack, "hacker")];
+
+    flag_roles.into_iter()
+        .filter(|(granted, _)| *granted)
+        .map(|(_, role)| role.to_string())
+        .chain(new_user.roles.iter().cloned())
+        .collect()
+}
+
+async fn assign_roles(connection: &mut PgConnection, user_id: i32, mut roles: Vec<String>) -> Result<(), ServerError> {
+    roles.sort();
+    roles.dedup();
+
+    let roles_assignment = sqlx::query("INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = ANY($2)")
+        .bind(user_id)
+        .bind(&roles)
+        .execute(connection)
+        .timed("assign_roles")
+        .await;
+
+    match roles_assignment {
+        Ok(result) if result.rows_affected() == roles.len() as u64 => Ok(()),
+        Ok(_) => Err(ServerError::bad_request("UNKNOWN_ROLE", format!("Unknown role in: {}.", roles.join(", ")))),
+        Err(error) => {
+            error!("Failed to assign roles to user_id {}: {}", user_id, error);
+            Err(ServerError::internal("Role database error."))
+        }
+    }
+}
+
+async fn user_permissions(connection: &mut PgConnection, user_id: i32) -> Result<Vec<String>, ServerError> {
+    let permissions_query = sqlx::query_scalar::<_, Vec<String>>("SELECT user_permissions($1)")
+        .bind(user_id)
+        .fetch_one(connection)
+        .timed("get_user_permissions")
+        .await;
+
+    match permissions_query {
+        Ok(permissions) => Ok(permissions),
+        Err(error) => {
+            error!("Failed to get the permissions of user_id {}: {}", user_id, error);
+            Err(ServerError::internal("Role database error."))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use serde_json::json;
+    use argon2::Params;
+    use shared_server_lib::{server_database, session_tokens::{SessionTokenConfiguration, SessionTokens}, login_lockout::{LoginLockout, LoginLockoutConfiguration}, password_hashing::{PasswordHashing, PasswordHashingConfiguration}};
+
+    fn input_limits() -> InputLimits {
+        InputLimits{
+            max_username_length: 16,
+            max_password_length: 16,
+            max_extra_data_length: 32,
+            max_group_chat_members: 8,
+            max_chat_message_length: 64,
+            max_transfer_message_length: 64,
+            max_title_length: 64,
+            max_device_label_length: 64,
+            max_cyber_defence_level: 10,
+        }
+    }
+
+    // A test fails fast if a handler reaches the database.
+    fn server_state() -> Arc<ServerState> {
+        let db_pool = server_database::unreachable_pool();
+        let session_tokens = SessionTokenConfiguration{
+            signing_key: "test_signing_key_0123456789abcdef".into(),
+            lifetime: Duration::from_secs(60),
+            hack_lifetime: Duration::from_secs(60),
+            impersonation_lifetime: Duration::from_secs(60),
+            revocation_refresh_interval: Duration::from_secs(60),
+            legacy_tokens_accepted_until: None,
+        };
+        let login_lockout = LoginLockoutConfiguration{ username_failures: 0, ip_failures: 0, lockout: Duration::from_secs(60), max_lockout: Duration::from_secs(60) };
+        let password_hashing = PasswordHashingConfiguration{ params: Params::new(8, 1, 1, None).unwrap(), pepper: "pepper".into(), pepper_version: 1, old_peppers: Default::default() };
+
+        Arc::new(ServerState{
+            password_hashing: Arc::new(PasswordHashing::new(&password_hashing)),
+            session_tokens: SessionTokens::new(&session_tokens, db_pool.clone()),
+            login_lockout: LoginLockout::new(login_lockout, db_pool.clone()),
+            db_pool,
+            input_limits: input_limits(),
+            trust_forwarded_for: false,
+        })
+    }
+
+    fn user_manager() -> AuthenticatedUser {
+        AuthenticatedUser{ user_id: 6, permissions: UserPermissions::default().with(Permission::ManageUsers), session_id: Some("session".into()), impersonated_by: None }
+    }
+
+    fn player() -> AuthenticatedUser {
+        AuthenticatedUser{ user_id: 7, permissions: UserPermissions::default().with(Permission::PublishNews).with(Permission::Hack), session_id: Some("session".into()), impersonated_by: None }
+    }
+
+    fn new_user(username: &str, password: &str) -> CreateUserRequest {
+        CreateUserRequest{
+            username: username.into(),
+            password: password.into(),
+            can_publish_posts: false,
+            cyber_defence_level: 1,
+            can_hack: false,
+            roles: Vec::new(),
+            extra_data: None,
+        }
+    }
+
+    fn error_code<T>(result: Result<T, ServerError>) -> &'static str {
+        match result {
+            Ok(_) => panic!("The request succeeded"),
+            Err(error) => error.code(),
+        }
+    }
+
+    #[tokio::test]
+    async fn user_management_needs_the_permission() {
+        let state = server_state();
+        let managed_user = ManagedUserRequest{ username: "bob".into() };
+        let update = UpdateUserRequest{ username: "bob".into(), ..Default::default() };
+        let reset = ResetPasswordRequest{ username: "bob".into(), new_password: "pass2".into() };
+
+        assert_eq!(error_code(create_user(State(state.clone()), player(), Json(new_user("carol", "pass1"))).await), "NO_USER_MANAGEMENT_PRIVILEGE");
+        assert_eq!(error_code(import_users(State(state.clone()), player(), Json(vec![new_user("carol", "pass1")])).await), "NO_USER_MANAGEMENT_PRIVILEGE");
+        assert_eq!(error_code(update_user(State(state.clone()), player(), Json(update)).await), "NO_USER_MANAGEMENT_PRIVILEGE");
+        assert_eq!(error_code(reset_password(State(state.clone()), player(), Json(reset)).await), "NO_USER_MANAGEMENT_PRIVILEGE");
+        assert_eq!(error_code(enable_user(State(state), player(), Json(managed_user)).await), "NO_USER_MANAGEMENT_PRIVILEGE");
+    }
+
+    #[tokio::test]
+    async fn invalid_users_are_rejected_before_the_database() {
+        let state = server_state();
+
+        assert_eq!(error_code(create_user(State(state.clone()), user_manager(), Json(new_user("carol", ""))).await), "INVALID_INPUT");
+
+        let mut too_much_data = new_user("dave", "pass1");
+        too_much_data.extra_data = Some(json!({ "biography": "far more than thirty two bytes of data" }));
+        assert_eq!(error_code(import_users(State(state.clone()), user_manager(), Json(vec![new_user("carol", "pass1"), too_much_data])).await), "INVALID_INPUT");
+
+        let update = UpdateUserRequest{ username: "bob".into(), extra_data: Some(json!({ "biography": "far more than thirty two bytes of data" })), ..Default::default() };
+        assert_eq!(error_code(update_user(State(state.clone()), user_manager(), Json(update)).await), "INVALID_INPUT");
+
+        let mut negative_level = new_user("erin", "pass1");
+        negative_level.cyber_defence_level = -1;
+        assert_eq!(error_code(create_user(State(state.clone()), user_manager(), Json(negative_level)).await), "INVALID_INPUT");
+
+        let update = UpdateUserRequest{ username: "bob".into(), cyber_defence_level: Some(11), ..Default::default() };
+        assert_eq!(error_code(update_user(State(state.clone()), user_manager(), Json(update)).await), "INVALID_INPUT");
+
+        let reset = ResetPasswordRequest{ username: "bob".into(), new_password: "a password far too long".into() };
+        assert_eq!(error_code(reset_password(State(state), user_manager(), Json(reset)).await), "INVALID_INPUT");
+    }
+
+    #[test]
+    fn new_user_errors_name_the_invalid_user() {
+        let users = [new_user("carol", "pass1"), new_user("a username far too long", "pass1"), new_user("dave", "")];
+
+        let error = validate_new_users(&input_limits(), &users).unwrap_err();
+        assert_eq!(error.code(), "INVALID_INPUT");
+        assert!(error.message().starts_with("User a username far too long: Invalid username"), "{}", error.message());
+
+        assert!(validate_new_users(&input_limits(), &users[..1]).is_ok());
+    }
+
+    #[test]
+    fn user_file_flags_become_roles() {
+        let mut user = new_user("carol", "pass1");
+        assert!(requested_roles(&user).is_empty());
+
+        user.can_publish_posts = true;
+        user.can_hack = true;
+        user.roles = vec!["hacker".into(), "game_master".into()];
+        assert_eq!(requested_roles(&user), vec!["journalist", "hacker", "hacker", "game_master"]);
+    }
+}
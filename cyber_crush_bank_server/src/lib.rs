@@ -0,0 +1,358 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics::{self, TimedQuery}, server_openapi, common::{self, LegacyFailureBody, ErrorResponse, ResponseStatus, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, input_validation::InputLimits, server_rate_limit::{RateLimitLayer, RateLimitGroup}};
+
+use axum::{
+    extract::{Json, State},
+    routing::{get, post},
+    Router,
+};
+
+use std::sync::Arc;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{error, info};
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::{authentication::Permission, bank::{GetUserFundsResponse, TransactionEntry, GetUserTransactionHistoryResponse, TransferFundsRequest, TransferFundsResponse, MintFundsRequest, MintFundsResponse}};
+
+#[derive(OpenApi)]
+#[openapi(paths(hello, get_user_funds, get_user_transaction_history, transfer_funds, mint_funds))]
+struct BankApi;
+
+#[derive(Debug)]
+struct ServerState {
+    db_pool: PgPool, //This is thread safe
+    session_tokens: SessionTokens,
+    input_limits: InputLimits,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+/// OpenAPI document of the bank server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("bank", env!("CARGO_PKG_VERSION"), BankApi::openapi())
+}
+
+pub fn create_router(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{ session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()), db_pool, input_limits: server_configuration.input_limits.clone() });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/get_user_funds", get(get_user_funds).post(get_user_funds))
+        .route("/get_user_transaction_history", get(get_user_transaction_history).post(get_user_transaction_history))
+        .route("/transfer_funds", post(transfer_funds).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Transfer)))
+        .route("/mint_funds", post(mint_funds))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("bank", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "bank", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/get_user_funds", LegacyFailureBody::Fields(json!({ "funds": -1 }))),
+        ("/get_user_transaction_history", LegacyFailureBody::Fields(json!({ "transactions": [] }))),
+        ("/transfer_funds", LegacyFailureBody::Status),
+    ]
+}
+
+#[utoipa::path(get, path = "/hello", tag = "bank", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush bank server!"
+}
+
+/// Funds on the bank account of the logged in user.
+#[utoipa::path(method(get, post), path = "/get_user_funds", tag = "bank", security(("token" = [])), responses(
+    (status = 200, body = GetUserFundsResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "BANK_ACCOUNT_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_user_funds(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetUserFundsResponse>, ServerError> {
+    let funds_query : Result<Option<i32>, sqlx::Error> = sqlx::query_scalar(
+    r#"
+        SELECT funds FROM bank_accounts
+        WHERE user_id = $1
+    "#)
+    .bind(user.user_id)
+    .fetch_optional(&state.db_pool)
+    .timed("get_user_funds")
+    .await;
+
+    match funds_query {
+        Ok(Some(funds)) => Ok(Json(GetUserFundsResponse{ response_status: ResponseStatus::success(), funds })),
+        Ok(None) => Err(ServerError::not_found("BANK_ACCOUNT_NOT_FOUND", "No account found for this user")),
+        Err(error) => {
+            error!("Getting user funds failed for user id {} Error:{}", user.user_id, error);
+            Err(ServerError::internal("No funds found. Server error!"))
+        }
+    }
+}
+
+/// Transfers sent or received by the logged in user, newest first.
+#[utoipa::path(method(get, post), path = "/get_user_transaction_history", tag = "bank", security(("token" = [])), responses(
+    (status = 200, body = GetUserTransactionHistoryResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_user_transaction_history(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetUserTransactionHistoryResponse>, ServerError> {
+    let transactions_query = sqlx::query_as::<_, TransactionEntry>(
+    r#"
+        SELECT 
+            sender.username AS sender_username,
+            receiver.username AS receiver_username,
+            t.message,
+            t.amount,
+            t.time_stamp
+        FROM bank_transactions t
+        JOIN bank_accounts sender_acc ON t.sender_id = sender_acc.id
+        JOIN users sender ON sender_acc.user_id = sender.id
+        JOIN bank_accounts receiver_acc ON t.receiver_id = receiver_acc.id
+        JOIN users receiver ON receiver_acc.user_id = receiver.id
+        WHERE sender.id = $1 OR receiver.id = $1
+        ORDER BY t.time_stamp DESC;
+    "#)
+    .bind(user.user_id)
+    .fetch_all(&state.db_pool)
+    .timed("get_user_transaction_history")
+    .await;
+   
+    match transactions_query {
+        Ok(transactions) => Ok(Json(GetUserTransactionHistoryResponse{ response_status: ResponseStatus::success(), transactions })),
+        Err(error) => {
+            error!("Getting user transaction history failed for user id {} Error: {}", user.user_id, error);
+            Err(ServerError::internal("No transaction found. Server error!"))
+        }
+    }
+}
+
+/// Moves `amount`, which has to be positive, from the logged in user to `receiver_username`.
+#[utoipa::path(post, path = "/transfer_funds", tag = "bank", security(("token" = [])), request_body = TransferFundsRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "BANK_ACCOUNT_NOT_FOUND or RECEIVER_NOT_FOUND", body = ErrorResponse),
+    (status = 409, description = "INSUFFICIENT_FUNDS", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn transfer_funds(State(state): State<Arc<ServerState>>, sender: AuthenticatedUser, Json(payload): Json<TransferFundsRequest>) -> Result<Json<TransferFundsResponse>, ServerError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct BankAccount {
+        id: i32
+    }
+
+    state.input_limits.validate_transfer_message(&payload.message)?;
+    // A negative amount would move funds from the receiver to the sender.
+    if payload.amount <= 0 {
+        return Err(ServerError::bad_request("INVALID_INPUT", "Invalid amount: has to be positive"));
+    }
+    
+    // Retrieve sender account by user id
+    let sender_account_query = sqlx::query_as::<_, BankAccount>(
+     r#"
+        SELECT 
+            id
+        FROM bank_accounts
+        WHERE user_id = $1
+    "#
+    )
+    .bind(sender.user_id)
+    .fetch_optional(&state.db_pool)
+    .timed("transfer_funds_sender_account")
+    .await;
+    
+    let sender_account = match sender_account_query {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            return Err(ServerError::not_found("BANK_ACCOUNT_NOT_FOUND", "No bank account found"));
+        },
+        Err(error) => {
+            error!("Transfering funds failed while getting sender account for user id {}, Error: {}", sender.user_id, error);
+            return Err(ServerError::internal("No bank account found(sender). Server Error!"));
+        }
+    };
+    
+    // Retrieve receiver account by username
+    let receiver_account_query = sqlx::query_as::<_, BankAccount>(
+    r#"
+        SELECT 
+            b.id
+        FROM bank_accounts b
+        JOIN users u ON b.user_id = u.id
+        WHERE u.username = $1
+    "#
+    )
+    .bind(&payload.receiver_username)
+    .fetch_optional(&state.db_pool)
+    .timed("transfer_funds_receiver_account")
+    .await;
+
+    let receiver_account = match receiver_account_query {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            return Err(ServerError::not_found("RECEIVER_NOT_FOUND", "Receiver not found"));
+        },
+        Err(error) => {
+            error!("Transfering funds failed while getting receiver account {}, Error: {}", payload.receiver_username, error);
+            return Err(ServerError::internal("No bank account found(receiver). Server Error!"));
+        }
+    };
+
+    let mut transaction: Transaction<'_, Postgres> = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Transfering funds failed while starting the transaction. Error: {}", error);
+            return Err(ServerError::internal("Internal server Error: 1!"));
+        }
+    };
+
+    let subtract_funds_query = sqlx::query(
+    r#"
+        UPDATE bank_accounts
+        SET funds = funds - $1
+        WHERE id = $2 AND funds > $1
+    "#)
+    .bind(payload.amount)
+    .bind(sender_account.id)
+    .execute(&mut *transaction)
+    .timed("transfer_funds_subtract")
+    .await;
+
+    match subtract_funds_query {
+        Ok(result) => {
+            if result.rows_affected() == 0 {
+                return Err(ServerError::conflict("INSUFFICIENT_FUNDS", "Not enough funds"));
+            }
+            if result.rows_affected() != 1 {
+                error!("Transfering funds failed too may rows affected while subtracting funds!");
+                return Err(ServerError::internal("Internal server Error: 2!"));
+            }
+        }
+        Err(error) => {
+            error!("Transfering funds failed while subtracting funds. Error: {}", error);
+            return Err(ServerError::internal("Internal server Error: 3!"));
+        }
+    };
+
+    let add_funds_query = sqlx::query(
+    r#"
+        UPDATE bank_accounts
+        SET funds = funds + $1
+        WHERE id = $2
+    "#)
+    .bind(payload.amount)
+    .bind(receiver_account.id)
+    .execute(&mut *transaction)
+    .timed("transfer_funds_add")
+    .await;
+
+    match add_funds_query {
+        Ok(result) => {
+            if result.rows_affected() == 0 {
+                return Err(ServerError::not_found("RECEIVER_NOT_FOUND", "Receiver not found"));
+            }
+            if result.rows_affected() != 1 {
+                error!("Transfering funds failed too may rows affected while adding funds!");
+                return Err(ServerError::internal("Internal server Error: 4!"));
+            }
+        }
+        Err(error) => {
+            error!("Transfering funds failed while adding funds. Error: {}", error);
+            return Err(ServerError::internal("Internal server Error: 5!"));
+        }    
+    };
+
+    let create_transaction_query = sqlx::query(
+    r#"
+        INSERT INTO bank_transactions
+        (sender_id, receiver_id, message, amount, time_stamp)
+        VALUES ($1, $2, $3, $4, NOW())
+    "#)
+    .bind(sender_account.id)
+    .bind(receiver_account.id)
+    .bind(payload.message)
+    .bind(payload.amount)
+    .execute(&mut *transaction)
+    .timed("transfer_funds_insert_transaction")
+    .await;
+
+    match create_transaction_query {
+        Ok(result) => {
+            if result.rows_affected() == 0 {
+                return Err(ServerError::not_found("RECEIVER_NOT_FOUND", "Receiver not found"));
+            }
+            if result.rows_affected() != 1 {
+                error!("Transfering funds failed too may rows affected while inserting transaction!");
+                return Err(ServerError::internal("Internal server Error: 6!"));
+            }
+        }
+        Err(error) => {
+            error!("Transfering funds failed while inserting transaction. Error: {}", error);
+            return Err(ServerError::internal("Internal server Error: 7!"));
+        }    
+    };
+
+    if let Err(error) = transaction.commit().await {
+        error!("Transfering funds failed while commiting transaction. Error: {}", error);
+        return Err(ServerError::internal("Internal server Error: 8!"));
+    }
+
+    server_metrics::record_funds_transfer(payload.amount.unsigned_abs());
+    Ok(Json(TransferFundsResponse::success()))
+}
+
+/// Creates `amount` new funds, which has to be positive, on the bank account of `receiver_username`.
+/// Needs the `mint_funds` permission of game masters.
+#[utoipa::path(post, path = "/mint_funds", tag = "bank", security(("token" = [])), request_body = MintFundsRequest, responses(
+    (status = 200, body = MintFundsResponse),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_MINTING_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "RECEIVER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn mint_funds(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<MintFundsRequest>) -> Result<Json<MintFundsResponse>, ServerError> {
+    user.require(Permission::MintFunds)?;
+    if payload.amount <= 0 {
+        return Err(ServerError::bad_request("INVALID_INPUT", "Invalid amount: has to be positive"));
+    }
+
+    let mint_query = sqlx::query_scalar::<_, i32>(
+    r#"
+        UPDATE bank_accounts
+        SET funds = funds + $1
+        WHERE user_id = (SELECT id FROM users WHERE username = $2)
+        RETURNING funds
+    "#)
+    .bind(payload.amount)
+    .bind(&payload.receiver_username)
+    .fetch_optional(&state.db_pool)
+    .timed("mint_funds")
+    .await;
+
+    match mint_query {
+        Ok(Some(funds)) => {
+            info!("{} funds minted for {} by user_id {}", payload.amount, payload.receiver_username, user.user_id);
+            Ok(Json(MintFundsResponse{ response_status: ResponseStatus::success(), funds }))
+        },
+        Ok(None) => Err(ServerError::not_found("RECEIVER_NOT_FOUND", "Receiver not found")),
+        // Postgres numeric_value_out_of_range, the new balance does not fit the funds column.
+        Err(error) if error.as_database_error().and_then(|error| error.code()).as_deref() == Some("22003") => {
+            Err(ServerError::bad_request("INVALID_INPUT", "Invalid amount: the account can not hold that many funds"))
+        },
+        Err(error) => {
+            error!("Minting funds failed for receiver {} and user id {}, Error: {}", payload.receiver_username, user.user_id, error);
+            Err(ServerError::internal("Minting funds failed. Server error!"))
+        }
+    }
+}
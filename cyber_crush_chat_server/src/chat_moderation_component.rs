@@ -0,0 +1,189 @@
+use shared_server_lib::{common::{ErrorResponse, ResponseStatus, ServerError}, authentication::AuthenticatedUser, server_metrics::TimedQuery};
+
+use axum::extract::{Json, State};
+
+use std::sync::Arc;
+use tracing::{error, info};
+
+use cyber_crush_protocol::{authentication::Permission, chat::{ChatModeration, ModerateChatRequest, ModerateChatResponse}};
+
+use crate::common_chat::{ ServerState, ChatType };
+use crate::common_chat;
+
+/// Deletes a message of a chat, or mutes, unmutes or removes one of its members. Needs the `moderate_chat`
+/// permission, moderators do not have to be members of the chat.
+#[utoipa::path(post, path = "/moderate_chat", tag = "chat", security(("token" = [])), request_body = ModerateChatRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_MODERATION_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "CHAT_NOT_FOUND, MESSAGE_NOT_FOUND, MEMBER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn moderate_chat(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<ModerateChatRequest>) -> Result<Json<ModerateChatResponse>, ServerError> {
+    user.require(Permission::ModerateChat)?;
+
+    let chat_type = common_chat::get_chat_type(&state.db_pool, payload.chat_id).await?;
+
+    match &payload.moderation {
+        ChatModeration::DeleteMessage{ in_chat_index } => delete_message(&state, payload.chat_id, chat_type, *in_chat_index).await?,
+        ChatModeration::MuteMember{ username, minutes } => {
+            if *minutes == 0 {
+                return Err(ServerError::bad_request("INVALID_INPUT", "Invalid minutes: has to be positive"));
+            }
+
+            let member_id = find_member(&state, payload.chat_id, username).await?;
+            set_muted_for(&state, payload.chat_id, member_id, Some(*minutes)).await?;
+        },
+        ChatModeration::UnmuteMember{ username } => {
+            let member_id = find_member(&state, payload.chat_id, username).await?;
+            set_muted_for(&state, payload.chat_id, member_id, None).await?;
+        },
+        ChatModeration::RemoveMember{ username } => {
+            if matches!(chat_type, ChatType::Direct) {
+                return Err(ServerError::bad_request("INVALID_INPUT", "Members can only be removed from group chats"));
+            }
+
+            let member_id = find_member(&state, payload.chat_id, username).await?;
+            remove_member(&state, payload.chat_id, member_id).await?;
+            common_chat::close_member_connections(&state, payload.chat_id, member_id, "Removed from the chat by a moderator");
+        },
+    }
+
+    info!("Chat {} moderated by user_id {}: {:?}", payload.chat_id, user.user_id, payload.moderation);
+    Ok(Json(ModerateChatResponse::success()))
+}
+
+// The last message shown in the chat list is replaced by the newest message left.
+async fn delete_message(state: &ServerState, chat_id: i32, chat_type: ChatType, in_chat_index: i32) -> Result<(), ServerError> {
+    let mut transaction = match state.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(error) => {
+            error!("Chat moderation failed while creating transaction for chat id: {}, error: {}", chat_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
+        }
+    };
+
+    let delete_message_query = sqlx::query("DELETE FROM chat_messages WHERE chat_id = $1 AND in_chat_index = $2")
+        .bind(chat_id)
+        .bind(in_chat_index)
+        .execute(&mut *transaction)
+        .timed("moderate_chat_delete_message")
+        .await;
+
+    match delete_message_query {
+        Ok(result) if result.rows_affected() == 0 => {
+            let _ = transaction.rollback().await;
+            return Err(ServerError::not_found("MESSAGE_NOT_FOUND", "Message not found"));
+        },
+        Ok(_) => {},
+        Err(error) => {
+            error!("Chat moderation failed to delete message {} of chat id: {}, error: {}", in_chat_index, chat_id, error);
+            return Err(ServerError::internal("Internal server error: 2"));
+        }
+    }
+
+    let chat_to_update = match chat_type {
+        ChatType::Direct => "direct_chats",
+        ChatType::Group => "group_chats"
+    };
+
+    let update_last_metadata_sql = format!(
+        r#"
+            UPDATE {}
+            SET (last_message, last_time_stamp) = (
+                SELECT content, time_stamp FROM chat_messages WHERE chat_id = $1 ORDER BY in_chat_index DESC LIMIT 1
+            )
+            WHERE chat_id = $1
+        "#,
+        chat_to_update
+    );
+
+    let update_last_metadata_query = sqlx::query(&update_last_metadata_sql)
+        .bind(chat_id)
+        .execute(&mut *transaction)
+        .timed("moderate_chat_update_metadata")
+        .await;
+
+    if let Err(error) = update_last_metadata_query {
+        error!("Chat moderation failed to update last metadata of chat id: {}, error: {}", chat_id, error);
+        let _ = transaction.rollback().await;
+        return Err(ServerError::internal("Internal server error: 3"));
+    }
+
+    if let Err(error) = transaction.commit().await {
+        error!("Chat moderation failed to commit message deletion for chat id: {}, error: {}", chat_id, error);
+        return Err(ServerError::internal("Internal server error: 4"));
+    }
+
+    Ok(())
+}
+
+async fn find_member(state: &ServerState, chat_id: i32, username: &str) -> Result<i32, ServerError> {
+    let member_query = sqlx::query_scalar::<_, i32>(
+    r#"
+        SELECT uc.user_id
+        FROM user_chats uc
+        JOIN users u ON u.id = uc.user_id
+        WHERE uc.chat_id = $1 AND u.username = $2
+    "#)
+    .bind(chat_id)
+    .bind(username)
+    .fetch_optional(&state.db_pool)
+    .timed("moderate_chat_member")
+    .await;
+
+    match member_query {
+        Ok(Some(member_id)) => Ok(member_id),
+        Ok(None) => Err(ServerError::not_found("MEMBER_NOT_FOUND", "User is not a member of this chat")),
+        Err(error) => {
+            error!("Chat moderation failed to find member {} of chat id: {}, error: {}", username, chat_id, error);
+            Err(ServerError::internal("Internal server error: 5"))
+        }
+    }
+}
+
+// `None` minutes lifts the mute.
+async fn set_muted_for(state: &ServerState, chat_id: i32, member_id: i32, minutes: Option<u32>) -> Result<(), ServerError> {
+    let mute_query = sqlx::query("UPDATE user_chats SET muted_until = NOW() + $3 * INTERVAL '1 minute' WHERE chat_id = $1 AND user_id = $2")
+        .bind(chat_id)
+        .bind(member_id)
+        .bind(minutes.map(i64::from))
+        .execute(&state.db_pool)
+        .timed("moderate_chat_mute")
+        .await;
+
+    match mute_query {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            error!("Chat moderation failed to mute user id: {} in chat id: {}, error: {}", member_id, chat_id, error);
+            Err(ServerError::internal("Internal server error: 6"))
+        }
+    }
+}
+
+// The admin stays, a group chat always has one.
+async fn remove_member(state: &ServerState, chat_id: i32, member_id: i32) -> Result<(), ServerError> {
+    let remove_member_query = sqlx::query(
+    r#"
+        DELETE FROM user_chats
+        WHERE chat_id = $1
+            AND user_id = $2
+            AND user_id NOT IN (SELECT admin_id FROM group_chats WHERE chat_id = $1)
+    "#)
+    .bind(chat_id)
+    .bind(member_id)
+    .execute(&state.db_pool)
+    .timed("moderate_chat_remove_member")
+    .await;
+
+    match remove_member_query {
+        Ok(result) if result.rows_affected() == 0 => Err(ServerError::bad_request("INVALID_INPUT", "The group admin can not be removed")),
+        Ok(_) => Ok(()),
+        Err(error) => {
+            error!("Chat moderation failed to remove user id: {} from chat id: {}, error: {}", member_id, chat_id, error);
+            Err(ServerError::internal("Internal server error: 7"))
+        }
+    }
+}
@@ -1,16 +1,19 @@
-use shared_server_lib::common;
+use shared_server_lib::{common::ErrorResponse, authentication::AuthenticatedUser, server_logging, server_metrics::{self, TimedQuery}};
 
 use axum::{
     extract::{State, ws::{WebSocket, WebSocketUpgrade, Message, CloseFrame}},
+    http::StatusCode,
     response::IntoResponse,
 };
 
 use sqlx::{ PgPool, types::chrono::NaiveDateTime };
-use serde::{Deserialize, Serialize};
 use futures::{SinkExt, StreamExt};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use futures_util::stream::{ SplitSink, SplitStream };
+use tracing::{error, Instrument, Span};
+
+use cyber_crush_protocol::chat::{ChatClientMessage, ChatResponse};
 
 use crate::common_chat::{ ServerState, ChatType };
 use crate::common_chat;
@@ -19,31 +22,22 @@ type WsSender = SplitSink<WebSocket, Message>;
 type WsReceiver = SplitStream<WebSocket>;
 type SendingChannel = tokio::sync::mpsc::UnboundedSender<axum::extract::ws::Message>;
 
-#[derive(Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum ChatClientMessage {
-    Init { token: String, chat_id: i32 },
-    Msg { token: String, message: String },
-    Exit { token: String },
-}
-
 struct ConnectionData {
     token: String,
-    user_id: i32,
+    user: AuthenticatedUser,
     chat_id: i32,
     chat_type: ChatType,
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-enum ChatResponse {
-    Info { text: String },
-    Error { text: String },
-    ChatMessage { chat_id: i32, in_chat_index: i32, sender: String, message: String, time_stamp: String },
-}
-
+/// Upgrades to a WebSocket for one chat. The client sends `ChatClientMessage` frames, starting with
+/// `init`, and receives `ChatResponse` frames with the messages of the chat.
+#[utoipa::path(get, path = "/realtime_chat", tag = "chat", responses(
+    (status = 101, description = "Switching to the WebSocket protocol"),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+))]
 pub async fn web_socket_handler(ws: WebSocketUpgrade, State(state): State<Arc<ServerState>>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let request_span = Span::current();
+    ws.on_upgrade(|socket| handle_socket(socket, state).instrument(request_span))
 }
 
 pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
@@ -56,8 +50,11 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
 
     let (sending_channel, mut receiving_channel) = mpsc::unbounded_channel::<Message>();
 
-    state.token_to_chat_id.insert(connection_data.token.clone(), connection_data.chat_id);
-    state.chat_connections.entry(connection_data.chat_id).or_default().push((connection_data.user_id, sending_channel.clone()));
+    {
+        let mut chat_connections = state.chat_connections.entry(connection_data.chat_id).or_default();
+        chat_connections.push((connection_data.user.user_id, sending_channel.clone()));
+        server_metrics::set_active_chat_connections(connection_data.chat_id, chat_connections.len());
+    }
 
     let connection_success_response = ChatResponse::Info{ text: "user connection succeeded".into() };
     ws_send_chat_response(&mut sender, &connection_success_response).await;
@@ -65,13 +62,38 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
     //Thread that sends messages from the channel to the websocket client
     tokio::spawn(async move {
         while let Some(msg) = receiving_channel.recv().await {
-            if sender.send(msg).await.is_err() {
+            let is_close = matches!(msg, Message::Close(_));
+            if sender.send(msg).await.is_err() || is_close {
                 break;
             }
         }
     });
 
-    while let Some(Ok(msg)) = receiver.next().await {
+    // Sockets outlive the token check of `init`, so the session is checked again to close the socket once it is revoked or expired.
+    let mut session_check = tokio::time::interval(state.session_tokens.revocation_refresh_interval());
+    session_check.reset();
+
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = session_check.tick() => {
+                if let Some(reason) = session_end_reason(&state, &connection_data).await {
+                    let error_response = ChatResponse::Error{ text: reason.clone() };
+                    channel_send_chat_response(&sending_channel, &error_response);
+                    let _ = sending_channel.send(Message::Close(Some(CloseFrame{ code: axum::extract::ws::close_code::POLICY, reason: reason.into() })));
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            _ => {
+                break;
+            }
+        };
+
         let client_message = match msg {
             Message::Text(text) => serde_json::from_str::<ChatClientMessage>(&text),
             Message::Close(_close_frame) => {
@@ -90,10 +112,34 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
             },
             Ok(ChatClientMessage::Msg{ token, message }) => {
                 if token != connection_data.token {
-                    eprintln!("Error: Token mismatch in message for user id: {}", connection_data.user_id);
+                    error!("Token mismatch in message for user id: {}", connection_data.user.user_id);
                     break;
                 }
 
+                if let Err(retry_after) = state.message_rate_limiter.acquire(connection_data.user.user_id) {
+                    let error_response = ChatResponse::Error{ text: format!("RATE_LIMITED: Too many messages, retry in {} seconds", retry_after.as_secs().max(1)) };
+                    channel_send_chat_response(&sending_channel, &error_response);
+                    continue;
+                }
+
+                if let Err(error) = state.input_limits.validate_chat_message(&message) {
+                    let error_response = ChatResponse::Error{ text: error.to_string() };
+                    channel_send_chat_response(&sending_channel, &error_response);
+                    continue;
+                }
+
+                if let Some(error_response) = muted_response(&state, &connection_data).await {
+                    channel_send_chat_response(&sending_channel, &error_response);
+                    continue;
+                }
+
+                let action = format!("Realtime chat message to chat {}", connection_data.chat_id);
+                if let Err(error) = state.session_tokens.record_impersonated_action(&connection_data.user, &action, None).await {
+                    let error_response = ChatResponse::Error{ text: error.message().to_string() };
+                    channel_send_chat_response(&sending_channel, &error_response);
+                    continue;
+                }
+
                 let time_stamp = chrono::Utc::now().naive_utc();
 
                 let message_index = match update_database(&connection_data, &message, &time_stamp, &state.db_pool).await {
@@ -112,8 +158,9 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
                     FROM users
                     WHERE id = $1
                 "#)
-                .bind(connection_data.user_id)
+                .bind(connection_data.user.user_id)
                 .fetch_optional(&state.db_pool)
+                .timed("realtime_chat_sender_username")
                 .await;
 
                 let username = match sender_username_query {
@@ -126,7 +173,7 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
                     Err(error) => {
                         let error_response = ChatResponse::Error{ text: "An error has occured while sending message through chat. Internal server error!".to_string() };
                         channel_send_chat_response(&sending_channel, &error_response);
-                        eprintln!("Getting username for user id {} failed. Error: {}", connection_data.user_id, error);
+                        error!("Getting username for user id {} failed. Error: {}", connection_data.user.user_id, error);
                         continue
                     }
                 };
@@ -139,14 +186,16 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
                     time_stamp: time_stamp.to_string(),
                 };
 
+                server_metrics::record_chat_message_sent();
+
                 // Send message to all chat connected members
                 if let Some(users) = state.chat_connections.get(&connection_data.chat_id) {
                     for (user_id, user_sender) in users.iter() {
-                        if *user_id == connection_data.user_id {
+                        if *user_id == connection_data.user.user_id {
                             continue;
                         }
 
-                        channel_send_chat_response(&user_sender, &chat_response);
+                        channel_send_chat_response(user_sender, &chat_response);
                     }
                 }
             },
@@ -156,7 +205,7 @@ pub async fn handle_socket(web_socket: WebSocket, state: Arc<ServerState>) {
                 }
             },
             Err(error) => {
-                eprintln!("Error: Realtime chat component failed to receive chat client message, error: {}", error);
+                error!("Realtime chat component failed to receive chat client message, error: {}", error);
             }
         }
     }
@@ -177,7 +226,7 @@ async fn initialize_connection(sender: &mut WsSender, receiver: &mut WsReceiver,
                     return None;
                 },
                 Err(error) => {
-                    eprintln!("Error: Realtime chat component failed while weceiving init message failed: {}", error);
+                    error!("Realtime chat component failed while weceiving init message failed: {}", error);
                     let error_response = ChatResponse::Error{ text: "Internal connection request server error".into() };
                     ws_send_chat_response(sender, &error_response).await;
                     close_connection(sender).await;
@@ -185,8 +234,8 @@ async fn initialize_connection(sender: &mut WsSender, receiver: &mut WsReceiver,
                 }
             };
 
-            let (user_id, chat_type) = match validate_user_and_chat(state, &token, chat_id).await {
-                Ok((id, chat_type)) => (id, chat_type),
+            let (user, chat_type) = match validate_user_and_chat(state, &token, chat_id).await {
+                Ok((user, chat_type)) => (user, chat_type),
                 Err(error) => {
                     ws_send_chat_response(sender, &error).await;
                     close_connection(sender).await;
@@ -194,38 +243,43 @@ async fn initialize_connection(sender: &mut WsSender, receiver: &mut WsReceiver,
                 }
             };
 
-            return Some(ConnectionData{ token, user_id, chat_id, chat_type });
+            server_logging::record_user_id(user.user_id);
+            if let Some(game_master_id) = user.impersonated_by {
+                server_logging::record_impersonated_by(game_master_id);
+            }
+
+            Some(ConnectionData{ token, user, chat_id, chat_type })
         }
         Some(_) => {
             let error_response = ChatResponse::Error{ text: "Wrong socket message type".into() };
             ws_send_chat_response(sender, &error_response).await;
             close_connection(sender).await;
-            return None;
+            None
         },
         None => {
             let error_response = ChatResponse::Error{ text: "No message sent. Close connection".into() };
             ws_send_chat_response(sender, &error_response).await;
             close_connection(sender).await;
-            return None;
+            None
         }
-    };
-}
-
-async fn validate_user_and_chat(state: &ServerState, token: &String, chat_id: i32) -> Result<(i32, ChatType), ChatResponse> {
-    let validated = common::validate_token(&state.db_pool, token).await;
-    
-    if validated.response_status.success == false {
-        return Err(ChatResponse::Error{ text: "User not validated".into() })
     }
+}
 
-    let user_id = validated.id.unwrap();
+async fn validate_user_and_chat(state: &ServerState, token: &str, chat_id: i32) -> Result<(AuthenticatedUser, ChatType), ChatResponse> {
+    let user = match state.session_tokens.authenticate(token).await {
+        Ok(user) => user,
+        Err(_) => {
+            return Err(ChatResponse::Error{ text: "User not validated".into() });
+        }
+    };
 
     let chat_id_query = sqlx::query_scalar::<_, i32>(
         "SELECT id FROM user_chats WHERE chat_id = $1 AND user_id = $2"
     )
     .bind(chat_id)
-    .bind(user_id)
+    .bind(user.user_id)
     .fetch_optional(&state.db_pool)
+    .timed("realtime_chat_membership")
     .await;
 
     match chat_id_query {
@@ -234,7 +288,7 @@ async fn validate_user_and_chat(state: &ServerState, token: &String, chat_id: i3
             return Err(ChatResponse::Error{ text: "User dones not belong to this chat".into() });
         }
         Err(error) => {
-            eprintln!("Error: Realtime chat component failed while checking chat id for chat: {}, error: {}", chat_id, error);
+            error!("Realtime chat component failed while checking chat id for chat: {}, error: {}", chat_id, error);
             return Err(ChatResponse::Error{ text: "Internal validation server error 1".into() });
         }
     };
@@ -242,18 +296,59 @@ async fn validate_user_and_chat(state: &ServerState, token: &String, chat_id: i3
     let chat_type = match common_chat::get_chat_type(&state.db_pool, chat_id).await {
         Ok(chat_type) => chat_type,
         Err(error) => {
-            return Err(ChatResponse::Error{ text: error });
+            return Err(ChatResponse::Error{ text: error.message().to_string() });
         }
     };
 
-    Ok((user_id, chat_type))
+    let action = format!("Realtime chat connection to chat {}", chat_id);
+    if let Err(error) = state.session_tokens.record_impersonated_action(&user, &action, None).await {
+        return Err(ChatResponse::Error{ text: error.message().to_string() });
+    }
+
+    Ok((user, chat_type))
+}
+
+/// Why the session of the connection is no longer valid, or `None` while it is. Internal errors keep the connection open.
+async fn session_end_reason(state: &ServerState, connection_data: &ConnectionData) -> Option<String> {
+    match state.session_tokens.authenticate(&connection_data.token).await {
+        Ok(_) => None,
+        Err(error) if error.status_code() == StatusCode::UNAUTHORIZED => Some(error.message().to_string()),
+        Err(error) => {
+            error!("Realtime chat component failed to check the session of user id: {}, error: {}", connection_data.user.user_id, error.message());
+            None
+        }
+    }
+}
+
+/// The error to send back while a moderator muted the user in the chat, or `None` while they may write.
+async fn muted_response(state: &ServerState, connection_data: &ConnectionData) -> Option<ChatResponse> {
+    let muted_seconds_query = sqlx::query_scalar::<_, i64>(
+    r#"
+        SELECT CEIL(EXTRACT(EPOCH FROM muted_until - NOW()))::BIGINT
+        FROM user_chats
+        WHERE chat_id = $1 AND user_id = $2 AND muted_until > NOW()
+    "#)
+    .bind(connection_data.chat_id)
+    .bind(connection_data.user.user_id)
+    .fetch_optional(&state.db_pool)
+    .timed("realtime_chat_muted")
+    .await;
+
+    match muted_seconds_query {
+        Ok(Some(seconds)) => Some(ChatResponse::Error{ text: format!("MUTED: Muted in this chat for {} more seconds", seconds.max(1)) }),
+        Ok(None) => None,
+        Err(error) => {
+            error!("Realtime chat component failed to check the mute of user id: {} in chat id: {}, error: {}", connection_data.user.user_id, connection_data.chat_id, error);
+            Some(ChatResponse::Error{ text: "Failed to send message. Internal server error: 6".into() })
+        }
+    }
 }
 
-async fn update_database(connection_data: &ConnectionData, message: &String, time_stamp: &NaiveDateTime, db_pool :&PgPool) -> Result<i32, String> {
+async fn update_database(connection_data: &ConnectionData, message: &str, time_stamp: &NaiveDateTime, db_pool :&PgPool) -> Result<i32, String> {
     let mut transaction = match db_pool.begin().await {
         Ok(tx) => tx,
         Err(error) => {
-            eprintln!("Error: Realtime chat component failed to create transaction for user id: {} and chat id: {}, error: {}", connection_data.user_id, connection_data.chat_id, error);
+            error!("Realtime chat component failed to create transaction for user id: {} and chat id: {}, error: {}", connection_data.user.user_id, connection_data.chat_id, error);
             return Err("Failed to send message. Internal server error: 1".into());
         }
     };
@@ -274,14 +369,15 @@ async fn update_database(connection_data: &ConnectionData, message: &String, tim
     "#)
     .bind(connection_data.chat_id)
     .bind(message_index)
-    .bind(connection_data.user_id)
-    .bind(message.clone())
-    .bind(time_stamp.clone())
+    .bind(connection_data.user.user_id)
+    .bind(message)
+    .bind(*time_stamp)
     .execute(&mut *transaction)
+    .timed("realtime_chat_insert_message")
     .await;
 
     if let Err(error) = insert_message_query {
-        eprintln!("Error: Realtime chat component failed to insert message for user id: {} and chat id: {}, error: {}", connection_data.user_id, connection_data.chat_id, error);
+        error!("Realtime chat component failed to insert message for user id: {} and chat id: {}, error: {}", connection_data.user.user_id, connection_data.chat_id, error);
         let _ = transaction.rollback().await;
         return Err("Failed to send message. Internal server error: 3".into());
     }
@@ -301,20 +397,21 @@ async fn update_database(connection_data: &ConnectionData, message: &String, tim
     );
 
     let update_last_metadata_query = sqlx::query(&update_last_metadata_sql)
-        .bind(message.clone())
-        .bind(time_stamp.clone())
+        .bind(message)
+        .bind(*time_stamp)
         .bind(connection_data.chat_id)
         .execute(&mut *transaction)
+        .timed("realtime_chat_update_metadata")
         .await;
 
     if let Err(error) = update_last_metadata_query {
-        eprintln!("Error: Realtime chat component failed to update last metadata for user id: {} and chat id: {}, error: {}", connection_data.user_id, connection_data.chat_id, error);
+        error!("Realtime chat component failed to update last metadata for user id: {} and chat id: {}, error: {}", connection_data.user.user_id, connection_data.chat_id, error);
         let _ = transaction.rollback().await;
         return Err("Failed to send message. Internal server error: 4".into());
     }
 
     if let Err(error) = transaction.commit().await {
-        eprintln!("Error: Realtime chat component failed to commit transaction for user id: {} and chat id: {}, error: {}", connection_data.user_id, connection_data.chat_id, error);
+        error!("Realtime chat component failed to commit transaction for user id: {} and chat id: {}, error: {}", connection_data.user.user_id, connection_data.chat_id, error);
         return Err("Failed to send message. Internal server error: 5".into());
     }
 
@@ -342,30 +439,29 @@ async fn get_next_message_index(connection_data: &ConnectionData, db_pool :&PgPo
     "#)
     .bind(connection_data.chat_id)
     .fetch_optional(db_pool)
+    .timed("realtime_chat_next_message_index")
     .await;
 
-    let next_message_index = match next_message_index_query {
+    match next_message_index_query {
         Ok(index) => index,
         Err(error) => {
-            eprintln!("Error: Realtime chat failed to find next message id for chat id: {}, error: {}", connection_data.chat_id, error);
+            error!("Realtime chat failed to find next message id for chat id: {}, error: {}", connection_data.chat_id, error);
             None
         }
-    };
-
-    next_message_index
+    }
 }
 
 async fn ws_send_chat_response(sender: &mut WsSender, chat_response: &ChatResponse) {
     let parsed_response = match serde_json::to_string(&chat_response) {
         Ok(json) => json.into(),
         Err(error) => {
-            eprintln!("Error: Realtime chat component failed while parsing json for websocket: {}", error);
+            error!("Realtime chat component failed while parsing json for websocket: {}", error);
             return;
         }
     };
 
     if let Err(error) = sender.send(parsed_response).await {
-        eprintln!("Error: Realtime chat component failed while sending using websocket: {}", error);
+        error!("Realtime chat component failed while sending using websocket: {}", error);
     }
 }
 
@@ -373,31 +469,30 @@ fn channel_send_chat_response(sender: &SendingChannel, chat_response: &ChatRespo
     let parsed_response = match serde_json::to_string(&chat_response) {
         Ok(json) => json.into(),
         Err(error) => {
-            eprintln!("Error: Realtime chat component failed while parsing json for channel response: {}", error);
+            error!("Realtime chat component failed while parsing json for channel response: {}", error);
             return;
         }
     };
 
     if let Err(error) = sender.send(Message::Text(parsed_response)) {
-        eprintln!("Error: Realtime chat component failed while sending text using channel response: {}", error);
+        error!("Realtime chat component failed while sending text using channel response: {}", error);
     }
 }
 
 async fn close_connection(sender: &mut WsSender) {
     if let Err(error) = sender.send(Message::Close(Some(CloseFrame{ code: axum::extract::ws::close_code::NORMAL, reason: "close".into() }))).await {
-        eprintln!("Error: Realtime chat component failed while closeing a connection: {}", error);
+        error!("Realtime chat component failed while closeing a connection: {}", error);
         return;
     }
 
     if let Err(error) = sender.flush().await {
-        eprintln!("Error: Realtime chat component failed while flushing close frame: {}", error);
-        return;
+        error!("Realtime chat component failed while flushing close frame: {}", error);
     }
 }
 
 fn close_chat(connection_data: ConnectionData, state: Arc<ServerState>) {
-    state.token_to_chat_id.remove(&connection_data.token);
     if let Some(mut vec) = state.chat_connections.get_mut(&connection_data.chat_id) {
-        vec.retain(|(uid, _)| *uid != connection_data.user_id);
+        vec.retain(|(uid, _)| *uid != connection_data.user.user_id);
+        server_metrics::set_active_chat_connections(connection_data.chat_id, vec.len());
     }
 }
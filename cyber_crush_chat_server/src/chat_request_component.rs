@@ -1,188 +1,28 @@
-use shared_server_lib::{common, common::ResponseStatus};
+use shared_server_lib::{common, common::{ErrorResponse, ResponseStatus, ServerError}, authentication::AuthenticatedUser, server_metrics::TimedQuery};
 
-use axum::{
-    extract::{Json, State},
-    response::IntoResponse,
-};
+use axum::extract::{Json, State};
 
-use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use chrono;
+use tracing::error;
+
+use cyber_crush_protocol::chat::{DirectChat, GroupChat, GetUserChatsResponse, GetChatHistoryRequest, ChatMessage, GetChatHistoryResponse, GetChatMetaDataRequest, DirectChatMetaData, GroupChatMetaData, ChatMetaData, GetChatMetaDataResponse, GroupMemberUpdate, UpdateGroupChatMemberRequest, UpdateGroupChatMemberResponse, CreateNewDirectChatRequest, CreateNewDirectChatResponse, CreateNewGroupChatRequest, CreateNewGroupChatResponse};
 
 use crate::common_chat::{ ServerState, ChatType };
 use crate::common_chat;
 
-#[derive(Debug, Deserialize)]
-pub struct GetUserChatsRequest {
-    token: String,
-}
-
-#[derive(Debug, Serialize, sqlx::FromRow)]
-pub struct DirectChat {
-    chat_id: i32,
-    chat_partner: String,
-    last_message: Option<String>,
-    last_message_time_stamp: Option<chrono::NaiveDateTime>,
-}
-
-#[derive(Debug, Serialize, sqlx::FromRow)]
-pub struct GroupChat {
-    chat_id: i32,
-    title: String,
-    last_message: Option<String>,
-    last_message_time_stamp: Option<chrono::NaiveDateTime>
-}
-
-#[derive(Debug, Serialize)]
-pub struct GetUserChatsResponse {
-    response_status: ResponseStatus,
-    direct_chats: Option<Vec<DirectChat>>,
-    group_chats: Option<Vec<GroupChat>>
-}
-
-#[derive(Debug, Deserialize)]
-pub struct GetChatHistoryRequest {
-    token: String,
-    chat_id: i32,
-    history_last_index: Option<i32>,
-}
-
-#[derive(Debug, Serialize, sqlx::FromRow)]
-pub struct ChatMessage {
-    sender: String,
-    in_chat_index: i32,
-    message: String,
-    time_stamp: chrono::NaiveDateTime,
-}
-
-#[derive(Debug, Serialize)]
-pub struct GetChatHistoryResponse {
-    response_status: ResponseStatus,
-    messages: Vec<ChatMessage>,
-}
-
-impl GetChatHistoryResponse {
-    fn fail(reason: &str) -> GetChatHistoryResponse {
-        GetChatHistoryResponse{
-            response_status: ResponseStatus::fail(reason.into()),
-            messages: vec![] }
-    }
-
-    fn success(messages: Vec<ChatMessage>) -> GetChatHistoryResponse {
-        GetChatHistoryResponse{
-            response_status: ResponseStatus::success(),
-            messages }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct GetChatMetaDataRequest {
-    token: String,
-    chat_id: i32,
-}
-
-//TODO rename to Metadata
-#[derive(Debug, Serialize)]
-pub struct DirectChatMetaData {
-    username_a: String,
-    username_b: String,
-}
-
-//TODO rename to Metadata
-#[derive(Debug, Serialize)]
-pub struct GroupChatMetaData {
-    admin_username: String,
-    title: String,
-    members: Vec<String>,
-}
-
-#[derive(Debug, Serialize)]
-enum ChatMetaData {
-    Direct(DirectChatMetaData),
-    Group(GroupChatMetaData),
-}
-
-#[derive(Debug, Serialize)]
-pub struct GetChatMetaDataResponse {
-    response_status: ResponseStatus,
-    metadata: Option<ChatMetaData>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(tag = "action", content = "username")]
-enum GroupMemberUpdate {
-    AddMember(String),
-    DeleteMember(String),
-}
-
-#[derive(Debug, Deserialize)]
-pub struct UpdateGroupChatMemberRequest {
-    admin_token: String,
-    chat_id: i32,
-    update: GroupMemberUpdate,
-}
-
-use ResponseStatus as UpdateGroupChatMemberResponse;
-
-#[derive(Debug, Deserialize)]
-pub struct CreateNewDirectChatRequest {
-    token: String,
-    partner_username: String,
-    creation_message: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct CreateNewDirectChatResponse {
-    response_status: ResponseStatus,
-    chat_id: Option<i32>,
-}
-
-impl CreateNewDirectChatResponse {
-    fn fail(reason: &str) -> CreateNewDirectChatResponse {
-        CreateNewDirectChatResponse{
-            response_status: ResponseStatus::fail(reason.into()),
-            chat_id: None }
-    }
-
-    fn success(chat_id: i32) -> CreateNewDirectChatResponse {
-        CreateNewDirectChatResponse{
-            response_status: ResponseStatus::success(),
-            chat_id: Some(chat_id) }
-    }
-
-    fn chat_exists(chat_id: i32) -> CreateNewDirectChatResponse {
-        CreateNewDirectChatResponse{
-            response_status: ResponseStatus::fail("Direct chat already exsits!".into()),
-            chat_id: Some(chat_id) }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CreateNewGroupChatRequest {
-    token: String,
-    title: String,
-    creation_message: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct CreateNewGroupChatResponse {
-    response_status: ResponseStatus,
-    chat_id: Option<i32>,
-}
-
+#[utoipa::path(get, path = "/hello", tag = "chat", responses((status = 200, body = String)))]
 pub async fn hello() -> &'static str {
     "Hello, cyber crush chat server!"
 }
 
-pub async fn get_user_chats(State(state): State<Arc<ServerState>>, Json(payload): Json<GetUserChatsRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.token).await;
-    
-    if validated.response_status.success == false {
-        return Json(GetUserChatsResponse{ response_status: ResponseStatus::fail("User not validated".into()), direct_chats: None, group_chats: None });
-    }
-
-    let user_id = validated.id.unwrap();
-    
+/// Direct and group chats of the logged in user with their last message.
+#[utoipa::path(method(get, post), path = "/get_user_chats", tag = "chat", security(("token" = [])), responses(
+    (status = 200, body = GetUserChatsResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn get_user_chats(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id, .. }: AuthenticatedUser) -> Result<Json<GetUserChatsResponse>, ServerError> {
     let direct_chats_query = sqlx::query_as::<_, DirectChat>(
     r#"
         SELECT dc.chat_id, u.username AS chat_partner, dc.last_message, dc.last_time_stamp AS last_message_time_stamp
@@ -194,13 +34,14 @@ pub async fn get_user_chats(State(state): State<Arc<ServerState>>, Json(payload)
     "#)
     .bind(user_id)
     .fetch_all(&state.db_pool)
+    .timed("get_user_chats_direct")
     .await;
 
     let direct_chats = match direct_chats_query {
         Ok(chats) => chats,
         Err(error) => {
-            eprintln!("Error: Getting user chats failed while querying direct chats for user id: {}, error: {}", user_id, error);
-            return Json(GetUserChatsResponse{ response_status: ResponseStatus::fail("Internal server error: 1".into()), direct_chats: None, group_chats: None });
+            error!("Getting user chats failed while querying direct chats for user id: {}, error: {}", user_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         }
     };
 
@@ -213,45 +54,49 @@ pub async fn get_user_chats(State(state): State<Arc<ServerState>>, Json(payload)
     "#)
     .bind(user_id)
     .fetch_all(&state.db_pool)
+    .timed("get_user_chats_group")
     .await;
 
     let group_chats = match group_chats_query {
         Ok(chats) => chats,
         Err(error) => {
-            eprintln!("Error: Getting user chats failed while querying group chats for user id: {}, error: {}", user_id, error);
-            return Json(GetUserChatsResponse{ response_status: ResponseStatus::fail("Internal server error: 2".into()), direct_chats: None, group_chats: None });
+            error!("Getting user chats failed while querying group chats for user id: {}, error: {}", user_id, error);
+            return Err(ServerError::internal("Internal server error: 2"));
         }
     };
 
-    Json(GetUserChatsResponse{ response_status: ResponseStatus::success(), direct_chats: Some(direct_chats), group_chats: Some(group_chats) })
+    Ok(Json(GetUserChatsResponse{ response_status: ResponseStatus::success(), direct_chats: Some(direct_chats), group_chats: Some(group_chats) }))
 }
 
-pub async fn get_chat_history(State(state): State<Arc<ServerState>>, Json(payload): Json<GetChatHistoryRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.token).await;
-    
-    if validated.response_status.success == false {
-        return Json(GetChatHistoryResponse::fail("Token validation failed".into()));
-    }
-
+/// Messages of a chat, newest first. Pass the lowest `in_chat_index` received as `history_last_index` to get older messages.
+#[utoipa::path(post, path = "/get_chat_history", tag = "chat", security(("token" = [])), request_body = GetChatHistoryRequest, responses(
+    (status = 200, body = GetChatHistoryResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NOT_A_CHAT_MEMBER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn get_chat_history(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id, .. }: AuthenticatedUser, Json(payload): Json<GetChatHistoryRequest>) -> Result<Json<GetChatHistoryResponse>, ServerError> {
     let membership_query = sqlx::query_scalar::<_, i32>(
     r#"
         SELECT 1 FROM user_chats WHERE chat_id = $1 AND user_id = $2      
     "#)
     .bind(payload.chat_id)
-    .bind(validated.id)
+    .bind(user_id)
     .fetch_optional(&state.db_pool)
+    .timed("chat_membership")
     .await;
 
     let membership_check = match membership_query {
         Ok(member) => member,
         Err(error) => {
-            eprintln!("Error: Checking user chat membership failed for user id: {:?} in chat id {}, Error: {}", validated.id, payload.chat_id, error);
-            return Json(GetChatHistoryResponse::fail("Internal server error: 1".into()));
+            error!("Checking user chat membership failed for user id: {} in chat id {}, Error: {}", user_id, payload.chat_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         }
     };
 
     if membership_check.is_none() {
-        return Json(GetChatHistoryResponse::fail("Not a chat member".into()));
+        return Err(ServerError::forbidden("NOT_A_CHAT_MEMBER", "Not a chat member"));
     }
 
     let message_query = match payload.history_last_index {
@@ -284,54 +129,51 @@ pub async fn get_chat_history(State(state): State<Arc<ServerState>>, Json(payloa
         }
     };
 
-    let response = match message_query.await {
+    match message_query.timed("get_chat_history").await {
         Ok(mut messages) => {
             messages.reverse();
-            GetChatHistoryResponse::success(messages)
+            Ok(Json(GetChatHistoryResponse::success(messages)))
         },
         Err(error) => {
-            eprintln!("Error: Getting user chat history failed for user id: {:?} and chat id: {}, error: {}", validated.id, payload.chat_id, error);
-            GetChatHistoryResponse::fail("Internal server error: 2".into())
+            error!("Getting user chat history failed for user id: {} and chat id: {}, error: {}", user_id, payload.chat_id, error);
+            Err(ServerError::internal("Internal server error: 2"))
         }
-    };
-    
-    Json(response)
-}
-
-pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(payload): Json<GetChatMetaDataRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.token).await;
-    
-    if validated.response_status.success == false {
-        return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Token validation failed".into()), metadata:  None });
     }
+}
 
+/// Members of a direct chat, or admin, title and members of a group chat.
+#[utoipa::path(post, path = "/get_chat_metadata", tag = "chat", security(("token" = [])), request_body = GetChatMetaDataRequest, responses(
+    (status = 200, body = GetChatMetaDataResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NOT_A_CHAT_MEMBER", body = ErrorResponse),
+    (status = 404, description = "CHAT_NOT_FOUND or CHAT_ADMIN_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id, .. }: AuthenticatedUser, Json(payload): Json<GetChatMetaDataRequest>) -> Result<Json<GetChatMetaDataResponse>, ServerError> {
     let membership_query = sqlx::query_scalar::<_, i32>(
     r#"
         SELECT 1 FROM user_chats WHERE chat_id = $1 AND user_id = $2;        
     "#)
     .bind(payload.chat_id)
-    .bind(validated.id)
+    .bind(user_id)
     .fetch_optional(&state.db_pool)
+    .timed("chat_membership")
     .await;
 
     let membership_check = match membership_query {
         Ok(member) => member,
         Err(error) => {
-            eprintln!("Error: Checking user chat membership failed for user id: {:?} and chat id: {}, error: {}", validated.id, payload.chat_id, error);
-            return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Internal server error: 1".into()), metadata:  None });
+            error!("Checking user chat membership failed for user id: {} and chat id: {}, error: {}", user_id, payload.chat_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         }
     };
 
     if membership_check.is_none() {
-        return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Not a chat member".into()), metadata:  None });
+        return Err(ServerError::forbidden("NOT_A_CHAT_MEMBER", "Not a chat member"));
     }
     
-    let chat_type = match common_chat::get_chat_type(&state.db_pool, payload.chat_id).await {
-        Ok(chat_type) => chat_type,
-        Err(error) => {
-            return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail(error), metadata:  None });
-        }
-    };
+    let chat_type = common_chat::get_chat_type(&state.db_pool, payload.chat_id).await?;
 
     let response = match chat_type {
         ChatType::Direct => {
@@ -341,18 +183,19 @@ pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(paylo
             "#)
             .bind(payload.chat_id)
             .fetch_all(&state.db_pool)
+            .timed("get_chat_metadata_users")
             .await;
             
             let users = match users_query {
                 Ok(users) => users,
                 Err(error) => {
-                    eprintln!("Error: Getting direct chat members usernames failed for chat id: {}, error: {}", payload.chat_id, error);
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Internal server error: 3".into()), metadata:  None });
+                    error!("Getting direct chat members usernames failed for chat id: {}, error: {}", payload.chat_id, error);
+                    return Err(ServerError::internal("Internal server error: 3"));
                 }
             };
 
             if users.len() != 2 {
-                return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Invalid user amount".into()), metadata: None });
+                return Err(ServerError::internal("Invalid user amount"));
             }
 
             let direct_chat_metadata = ChatMetaData::Direct(DirectChatMetaData{ username_a: users[0].clone(), username_b: users[1].clone() });
@@ -369,16 +212,17 @@ pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(paylo
             )
             .bind(payload.chat_id)
             .fetch_optional(&state.db_pool)
+            .timed("get_chat_metadata_admin")
             .await;
 
             let admin_username = match admin_query {
                 Ok(Some(username)) => username,
                 Ok(None) => {
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Admin not found".into()), metadata:  None });
+                    return Err(ServerError::not_found("CHAT_ADMIN_NOT_FOUND", "Admin not found"));
                 },
                 Err(error) => {
-                    eprintln!("Error: Getting group chat admin username failed for chat id: {}, error: {}", payload.chat_id, error);
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Internal server error: 4".into()), metadata:  None });
+                    error!("Getting group chat admin username failed for chat id: {}, error: {}", payload.chat_id, error);
+                    return Err(ServerError::internal("Internal server error: 4"));
                 }
             };
 
@@ -390,16 +234,17 @@ pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(paylo
             "#)
             .bind(payload.chat_id)
             .fetch_optional(&state.db_pool)
+            .timed("get_chat_metadata_title")
             .await;
 
             let title = match title_query {
                 Ok(Some(title)) => title,
                 Ok(None) => {
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Chat title not found".into()), metadata:  None });
+                    return Err(ServerError::not_found("CHAT_NOT_FOUND", "Chat title not found"));
                 },
                 Err(error) => {
-                    eprintln!("Error: Getting group chat title failed for chat id: {}, error: {}", payload.chat_id, error);
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Internal server error: 5".into()), metadata:  None });
+                    error!("Getting group chat title failed for chat id: {}, error: {}", payload.chat_id, error);
+                    return Err(ServerError::internal("Internal server error: 5"));
                 }
             };
 
@@ -412,6 +257,7 @@ pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(paylo
             "#)
             .bind(payload.chat_id)
             .fetch_all(&state.db_pool)
+            .timed("get_chat_metadata_members")
             .await;
             
             match members_query {
@@ -420,45 +266,106 @@ pub async fn get_chat_metadata(State(state): State<Arc<ServerState>>, Json(paylo
                     GetChatMetaDataResponse{ response_status: ResponseStatus::success(), metadata: Some(group_chat_metadata) }
                 },
                 Err(error) => {
-                    eprintln!("Error: Getting group chat members failed for chat id: {}, error: {}", payload.chat_id, error);
-                    return Json(GetChatMetaDataResponse{ response_status: ResponseStatus::fail("Internal server error: 6".into()), metadata:  None });
+                    error!("Getting group chat members failed for chat id: {}, error: {}", payload.chat_id, error);
+                    return Err(ServerError::internal("Internal server error: 6"));
                 }
             }
         }
     };
 
-    Json(response)
+    Ok(Json(response))
 }
 
-pub async fn update_group_chat_member(State(state): State<Arc<ServerState>>, Json(payload): Json<UpdateGroupChatMemberRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.admin_token).await;
-    
-    if validated.response_status.success == false {
-        return Json(UpdateGroupChatMemberResponse::fail("Token validation failed".into()));
-    }
-
+/// Adds or removes a group chat member, only for the group admin.
+#[utoipa::path(post, path = "/update_group_chat_member", tag = "chat", security(("token" = [])), request_body = UpdateGroupChatMemberRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NOT_A_GROUP_ADMIN", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn update_group_chat_member(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id, .. }: AuthenticatedUser, Json(payload): Json<UpdateGroupChatMemberRequest>) -> Result<Json<UpdateGroupChatMemberResponse>, ServerError> {
     let validate_admin_query = sqlx::query_scalar::<_, i64>(
     r#"
         SELECT COUNT(*) FROM group_chats WHERE chat_id = $1 AND admin_id = $2
     "#)
     .bind(payload.chat_id)
-    .bind(validated.id)
+    .bind(user_id)
     .fetch_one(&state.db_pool)
+    .timed("update_group_chat_member_admin")
     .await;
 
     match validate_admin_query {
         Ok(0) => {
-            return Json(UpdateGroupChatMemberResponse::fail("Not a group admin".into()))
+            return Err(ServerError::forbidden("NOT_A_GROUP_ADMIN", "Not a group admin"));
         },
         Err(error) => {
-            eprintln!("Error: Group member update failed for user id: {:?} and chat id: {}, error: {}", validated.id, payload.chat_id, error);
-            return Json(UpdateGroupChatMemberResponse::fail("Internal server error: 1".into()))
+            error!("Group member update failed for user id: {} and chat id: {}, error: {}", user_id, payload.chat_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         },
         _ => {}
     }
 
     match payload.update {
         GroupMemberUpdate::AddMember(username) => {
+            let mut transaction = match state.db_pool.begin().await {
+                Ok(tx) => tx,
+                Err(error) => {
+                    error!("Group member update failed while creating transaction for chat id: {}, error: {}", payload.chat_id, error);
+                    return Err(ServerError::internal("Internal server error: 5"));
+                }
+            };
+
+            // Locking the group row makes concurrent additions wait, so the count below stays true until commit.
+            let lock_group_query = sqlx::query(
+            r#"
+                SELECT chat_id FROM group_chats WHERE chat_id = $1 FOR UPDATE
+            "#)
+            .bind(payload.chat_id)
+            .execute(&mut *transaction)
+            .timed("update_group_chat_member_lock")
+            .await;
+
+            if let Err(error) = lock_group_query {
+                error!("Group member update failed to lock chat id: {}, error: {}", payload.chat_id, error);
+                let _ = transaction.rollback().await;
+                return Err(ServerError::internal("Internal server error: 6"));
+            }
+
+            // Adding a member again changes nothing, so it is not held against a full group.
+            let member_count_query = sqlx::query_as::<_, (i64, bool)>(
+            r#"
+                SELECT
+                    COUNT(*),
+                    COUNT(*) FILTER (WHERE user_id = (SELECT id FROM users WHERE username = $2)) > 0
+                FROM user_chats WHERE chat_id = $1
+            "#)
+            .bind(payload.chat_id)
+            .bind(&username)
+            .fetch_one(&mut *transaction)
+            .timed("update_group_chat_member_count")
+            .await;
+
+            match member_count_query {
+                Ok((_, true)) => {
+                    let _ = transaction.rollback().await;
+                    return Ok(Json(UpdateGroupChatMemberResponse::success()));
+                },
+                Ok((member_count, false)) => {
+                    if let Err(error) = state.input_limits.validate_group_chat_member_count(member_count as usize + 1) {
+                        let _ = transaction.rollback().await;
+                        return Err(error.into());
+                    }
+                },
+                Err(error) => {
+                    error!("Group member update failed to count members for chat id: {}, error: {}", payload.chat_id, error);
+                    let _ = transaction.rollback().await;
+                    return Err(ServerError::internal("Internal server error: 4"));
+                }
+            }
+
             let add_member_query = sqlx::query(
             r#"
                 INSERT INTO user_chats (chat_id, user_id) 
@@ -470,14 +377,21 @@ pub async fn update_group_chat_member(State(state): State<Arc<ServerState>>, Jso
             "#)
             .bind(payload.chat_id)
             .bind(&username)
-            .execute(&state.db_pool)
+            .execute(&mut *transaction)
+            .timed("update_group_chat_member_add")
             .await;
-            
-            match add_member_query {
-                Ok(_) => Json(UpdateGroupChatMemberResponse::success()),
+
+            if let Err(error) = add_member_query {
+                error!("Group member update failed to add a new member for user: {} and chat id: {}, error: {}", username, payload.chat_id, error);
+                let _ = transaction.rollback().await;
+                return Err(ServerError::internal("Internal server error: 2"));
+            }
+
+            match transaction.commit().await {
+                Ok(_) => Ok(Json(UpdateGroupChatMemberResponse::success())),
                 Err(error) => {
-                    eprintln!("Error: Group member update failed to add a new member for user: {} and chat id: {}, error: {}", username, payload.chat_id, error);
-                    Json(UpdateGroupChatMemberResponse::fail("Internal server error: 2".into()))
+                    error!("Group member update failed while commiting transaction for user: {} and chat id: {}, error: {}", username, payload.chat_id, error);
+                    Err(ServerError::internal("Internal server error: 7"))
                 }
             }
         },
@@ -492,41 +406,47 @@ pub async fn update_group_chat_member(State(state): State<Arc<ServerState>>, Jso
             .bind(payload.chat_id)
             .bind(&username)
             .execute(&state.db_pool)
+            .timed("update_group_chat_member_delete")
             .await;
 
             match delete_member_query {
-                Ok(_) => Json(UpdateGroupChatMemberResponse::success()),
+                Ok(_) => Ok(Json(UpdateGroupChatMemberResponse::success())),
                 Err(error) => {
-                    eprintln!("Error: Group member update failed to delete member for user: {} and chat_id: {}, error: {}", username, payload.chat_id, error);
-                    Json(UpdateGroupChatMemberResponse::fail("Internal server error: 3".into()))
+                    error!("Group member update failed to delete member for user: {} and chat_id: {}, error: {}", username, payload.chat_id, error);
+                    Err(ServerError::internal("Internal server error: 3"))
                 }
             }
         }
     }
 }
 
-pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, Json(payload): Json<CreateNewDirectChatRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.token).await;
-    
-    if validated.response_status.success == false {
-        return Json(CreateNewDirectChatResponse::fail("Token validation failed".into()));
-    }
-
-    let sender_id = validated.id.unwrap();
-
-    let partner_id = common::get_user_id_by_username(&state.db_pool, &payload.partner_username).await;
-
-    if partner_id.response_status.success == false {
-        return Json(CreateNewDirectChatResponse::fail("Partner does not exist".into()));
-    }
-
-    let partner_id = partner_id.id.unwrap();
+/// Starts a direct chat with its first message. An existing chat with the partner is returned with `success: false`.
+#[utoipa::path(post, path = "/create_new_direct_chat", tag = "chat", security(("token" = [])), request_body = CreateNewDirectChatRequest, responses(
+    (status = 200, body = CreateNewDirectChatResponse),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "PARTNER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id: sender_id, .. }: AuthenticatedUser, Json(payload): Json<CreateNewDirectChatRequest>) -> Result<Json<CreateNewDirectChatResponse>, ServerError> {
+    state.input_limits.validate_chat_message(&payload.creation_message)?;
+
+    let partner_id = match common::get_user_id_by_username(&state.db_pool, &payload.partner_username).await {
+        Ok(partner_id) => partner_id,
+        Err(ServerError::NotFound{ .. }) => {
+            return Err(ServerError::not_found("PARTNER_NOT_FOUND", "Partner does not exist"));
+        },
+        Err(error) => {
+            return Err(error);
+        }
+    };
 
     let mut transaction = match state.db_pool.begin().await {
         Ok(tx) => tx,
         Err(error) => {
-            eprintln!("Error: Creating new direct chat failed while creating transaction for user id: {}, error: {}", sender_id, error);
-            return Json(CreateNewDirectChatResponse::fail("Internal server error: 1".into()));
+            error!("Creating new direct chat failed while creating transaction for user id: {}, error: {}", sender_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         }
     };
 
@@ -541,11 +461,12 @@ pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, Json(
     .bind(sender_id)
     .bind(partner_id)
     .fetch_optional(&mut *transaction)
+    .timed("create_new_direct_chat_existing")
     .await;
 
     if let Ok(Some(chat_id)) = chat_instance_check_query {
         let _ = transaction.rollback().await;
-        return Json(CreateNewDirectChatResponse::chat_exists(chat_id));
+        return Ok(Json(CreateNewDirectChatResponse::chat_exists(chat_id)));
     }
     
     let chat_id_query = sqlx::query_scalar::<_, i32>(
@@ -553,14 +474,15 @@ pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, Json(
         INSERT INTO chats DEFAULT VALUES RETURNING id
     "#)
     .fetch_one(&mut *transaction)
+    .timed("create_new_direct_chat_id")
     .await;
 
     let chat_id = match chat_id_query {
         Ok(id) => id,
         Err(error) => {
-            eprintln!("Error: Creating new direct chat failed while creating new chat for user id: {}, error: {}", sender_id, error);
+            error!("Creating new direct chat failed while creating new chat for user id: {}, error: {}", sender_id, error);
             let _ = transaction.rollback().await;
-            return Json(CreateNewDirectChatResponse::fail("Internal server error: 2"));
+            return Err(ServerError::internal("Internal server error: 2"));
         }
     };
 
@@ -572,12 +494,13 @@ pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, Json(
     .bind(sender_id)
     .bind(partner_id)
     .execute(&mut *transaction)
+    .timed("create_new_direct_chat_members")
     .await;
 
     if let Err(error) = add_chat_for_users_query {
-        eprintln!("Error: Creating new direct chat failed while attaching to users for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
+        error!("Creating new direct chat failed while attaching to users for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
         let _ = transaction.rollback().await;
-        return Json(CreateNewDirectChatResponse::fail("Internal server error: 3"));
+        return Err(ServerError::internal("Internal server error: 3"));
     }
 
     let time_stamp = chrono::Utc::now().naive_utc();
@@ -591,36 +514,40 @@ pub async fn create_new_direct_chat(State(state): State<Arc<ServerState>>, Json(
     .bind(payload.creation_message)
     .bind(time_stamp)
     .execute(&mut *transaction)
+    .timed("create_new_direct_chat_info")
     .await;
 
     if let Err(error) = create_direct_chat_query {
-        eprintln!("Error: Creating new direct chat failed for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
+        error!("Creating new direct chat failed for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
         let _ = transaction.rollback().await;
-        return Json(CreateNewDirectChatResponse::fail("Internal server error: 4"));
+        return Err(ServerError::internal("Internal server error: 4"));
     }
 
     if let Err(error) = transaction.commit().await {
-        eprintln!("Error: Creating new direct chat failed while commiting transaction for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
-        return Json(CreateNewDirectChatResponse::fail("Internal server error: 5"));
+        error!("Creating new direct chat failed while commiting transaction for sender id: {} and partner id: {}, error: {}", sender_id, partner_id, error);
+        return Err(ServerError::internal("Internal server error: 5"));
     }
 
-    Json(CreateNewDirectChatResponse::success(chat_id))
+    Ok(Json(CreateNewDirectChatResponse::success(chat_id)))
 }
 
-pub async fn create_new_group_chat(State(state): State<Arc<ServerState>>, Json(payload): Json<CreateNewGroupChatRequest>) -> impl IntoResponse {
-    let validated = common::validate_token(&state.db_pool, &payload.token).await;
-    
-    if validated.response_status.success == false {
-        return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Token validation failed".into()), chat_id: None });
-    }
-
-    let admin_id = validated.id.unwrap();
+/// Creates a group chat administered by the logged in user, with its first message.
+#[utoipa::path(post, path = "/create_new_group_chat", tag = "chat", security(("token" = [])), request_body = CreateNewGroupChatRequest, responses(
+    (status = 200, body = CreateNewGroupChatResponse),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+pub async fn create_new_group_chat(State(state): State<Arc<ServerState>>, AuthenticatedUser{ user_id: admin_id, .. }: AuthenticatedUser, Json(payload): Json<CreateNewGroupChatRequest>) -> Result<Json<CreateNewGroupChatResponse>, ServerError> {
+    state.input_limits.validate_group_chat_title(&payload.title)?;
+    state.input_limits.validate_chat_message(&payload.creation_message)?;
 
     let mut transaction = match state.db_pool.begin().await {
         Ok(tx) => tx,
         Err(error) => {
-            eprintln!("Error: Creating new direct chat failed while creating transaction for user id: {}, error: {}", admin_id, error);
-            return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Internal server error: 1".into()), chat_id: None });
+            error!("Creating new direct chat failed while creating transaction for user id: {}, error: {}", admin_id, error);
+            return Err(ServerError::internal("Internal server error: 1"));
         }
     };
 
@@ -629,14 +556,15 @@ pub async fn create_new_group_chat(State(state): State<Arc<ServerState>>, Json(p
         INSERT INTO chats DEFAULT VALUES RETURNING id
     "#)
     .fetch_one(&mut *transaction)
+    .timed("create_new_group_chat_id")
     .await;
 
     let chat_id = match chat_id_query {
         Ok(id) => id,
         Err(error) => {
-            eprintln!("Error: Creating new group chat failed while creating new chat for user id: {}, error: {}", admin_id, error);
+            error!("Creating new group chat failed while creating new chat for user id: {}, error: {}", admin_id, error);
             let _ = transaction.rollback().await;
-            return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Internal server error: 2".into()), chat_id: None });
+            return Err(ServerError::internal("Internal server error: 2"));
         }
     };
 
@@ -647,12 +575,13 @@ pub async fn create_new_group_chat(State(state): State<Arc<ServerState>>, Json(p
     .bind(chat_id)
     .bind(admin_id)
     .execute(&mut *transaction)
+    .timed("create_new_group_chat_admin")
     .await;
 
     if let Err(error) = assign_admin_to_chat_query {
-        eprintln!("Error: Creating new group chat failed while attaching admin for user id: {}, error: {}", admin_id, error);
+        error!("Creating new group chat failed while attaching admin for user id: {}, error: {}", admin_id, error);
         let _ = transaction.rollback().await;
-        return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Internal server error: 3".into()), chat_id: None });
+        return Err(ServerError::internal("Internal server error: 3"));
     }
     
 
@@ -668,19 +597,20 @@ pub async fn create_new_group_chat(State(state): State<Arc<ServerState>>, Json(p
     .bind(payload.creation_message)
     .bind(time_stamp)
     .execute(&mut *transaction)
+    .timed("create_new_group_chat_info")
     .await;
 
     if let Err(error) = create_group_chat_query {
-        eprintln!("Error: Creating new group chat failed for user id: {}, error: {}", admin_id, error);
+        error!("Creating new group chat failed for user id: {}, error: {}", admin_id, error);
         let _ = transaction.rollback().await;
-        return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Internal server error: 4".into()), chat_id: None })
+        return Err(ServerError::internal("Internal server error: 4"));
     }
 
     if let Err(error) = transaction.commit().await {
-        eprintln!("Error: Creating new group chat failed while commiting transaction for user id: {}, error: {}", admin_id, error);
-        return Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::fail("Internal server error: 5".into()), chat_id: None });
+        error!("Creating new group chat failed while commiting transaction for user id: {}, error: {}", admin_id, error);
+        return Err(ServerError::internal("Internal server error: 5"));
     }
 
-    Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::success(), chat_id: Some(chat_id) })
+    Ok(Json(CreateNewGroupChatResponse{ response_status: ResponseStatus::success(), chat_id: Some(chat_id) }))
 }
 
@@ -1,21 +1,72 @@
 use dashmap::DashMap;
 use tokio::sync::mpsc;
-use axum::extract::ws::Message;
+use axum::extract::ws::{Message, CloseFrame, close_code};
 use sqlx::PgPool;
+use shared_server_lib::{common::ServerError, authentication::AuthenticationState, session_tokens::SessionTokens, input_validation::InputLimits, server_metrics::TimedQuery, server_rate_limit::UserRateLimiter};
 use std::sync::Arc;
+use tokio::time::{ sleep, Duration, Instant };
+use tracing::{error, info};
+
+const CONNECTION_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub enum ChatType {
     Direct,
     Group,
 }
 
+pub type ChatConnections = DashMap<i32, Vec<(i32, mpsc::UnboundedSender<Message>)>>; //chat_id -> Vec<(user_id, sending stream)>
+
 pub struct ServerState {
     pub db_pool: PgPool, //This is thread safe
-    pub token_to_chat_id: Arc<DashMap<String, i32>>,
-    pub chat_connections: Arc<DashMap<i32, Vec<(i32, mpsc::UnboundedSender<Message>)>>>, //chat_id -> Vec<(user_id, sending stream)>
+    pub session_tokens: SessionTokens,
+    pub chat_connections: Arc<ChatConnections>,
+    pub input_limits: InputLimits,
+    pub message_rate_limiter: UserRateLimiter,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+/// Sends a "server restarting" close frame to every realtime chat connection, then waits until
+/// the clients acknowledged it or `CONNECTION_DRAIN_TIMEOUT` passed.
+pub async fn close_all_connections(state: &ServerState) {
+    let mut connection_count = 0;
+    for chat in state.chat_connections.iter() {
+        for (_, user_sender) in chat.value() {
+            let close_frame = CloseFrame{ code: close_code::RESTART, reason: "Server restarting".into() };
+            if user_sender.send(Message::Close(Some(close_frame))).is_ok() {
+                connection_count += 1;
+            }
+        }
+    }
+
+    info!("Closing {} realtime chat connection(s)", connection_count);
+
+    let drain_start = Instant::now();
+    while state.chat_connections.iter().any(|chat| !chat.value().is_empty()) {
+        if drain_start.elapsed() > CONNECTION_DRAIN_TIMEOUT {
+            error!("Realtime chat connections did not close within {} seconds", CONNECTION_DRAIN_TIMEOUT.as_secs());
+            return;
+        }
+
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Closes the realtime connections of one member of a chat, e.g. after they were removed from it.
+pub fn close_member_connections(state: &ServerState, chat_id: i32, user_id: i32, reason: &str) {
+    if let Some(chat) = state.chat_connections.get(&chat_id) {
+        for (_, user_sender) in chat.value().iter().filter(|(connected_user_id, _)| *connected_user_id == user_id) {
+            let close_frame = CloseFrame{ code: close_code::POLICY, reason: reason.to_string().into() };
+            let _ = user_sender.send(Message::Close(Some(close_frame)));
+        }
+    }
 }
 
-pub async fn get_chat_type(db_pool: &PgPool, chat_id: i32) -> Result<ChatType, String> {
+pub async fn get_chat_type(db_pool: &PgPool, chat_id: i32) -> Result<ChatType, ServerError> {
     let chat_type_query = sqlx::query_scalar::<_, i32>(
     r#"
         SELECT
@@ -27,6 +78,7 @@ pub async fn get_chat_type(db_pool: &PgPool, chat_id: i32) -> Result<ChatType, S
     "#)
     .bind(chat_id)
     .fetch_one(db_pool)
+    .timed("get_chat_type")
     .await;
 
     match chat_type_query {
@@ -37,11 +89,11 @@ pub async fn get_chat_type(db_pool: &PgPool, chat_id: i32) -> Result<ChatType, S
             Ok(ChatType::Group)
         }
         Ok(_) => {
-            Err(String::from("Unknown chat type"))
+            Err(ServerError::not_found("CHAT_NOT_FOUND", "Unknown chat type"))
         }
         Err(error) => {
-            eprintln!("Error: Getting chat type failed for chat id: {}, Error: {}", chat_id, error);
-            Err(String::from("Internal chat identification server error"))
+            error!("Getting chat type failed for chat id: {}, Error: {}", chat_id, error);
+            Err(ServerError::internal("Internal chat identification server error"))
         }
     }
 }
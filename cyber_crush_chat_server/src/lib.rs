@@ -0,0 +1,79 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics, server_openapi, common::{self, LegacyFailureBody}, session_tokens::SessionTokens, authentication, server_rate_limit::{RateLimitLayer, RateLimitGroup, UserRateLimiter}};
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use sqlx::PgPool;
+use std::sync::Arc;
+use serde_json::json;
+use dashmap::DashMap;
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::chat::{ChatClientMessage, ChatResponse};
+
+mod common_chat;
+mod chat_request_component;
+mod chat_realtime_component;
+mod chat_moderation_component;
+
+pub use crate::common_chat::{ServerState, close_all_connections};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        chat_request_component::hello,
+        chat_request_component::get_user_chats,
+        chat_request_component::get_chat_history,
+        chat_request_component::get_chat_metadata,
+        chat_request_component::update_group_chat_member,
+        chat_request_component::create_new_direct_chat,
+        chat_request_component::create_new_group_chat,
+        chat_realtime_component::web_socket_handler,
+        chat_moderation_component::moderate_chat,
+    ),
+    components(schemas(ChatClientMessage, ChatResponse))
+)]
+struct ChatApi;
+
+pub fn create_server_state(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Arc<ServerState> {
+    Arc::new(ServerState{ session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()), db_pool, chat_connections: Arc::new(DashMap::new()), input_limits: server_configuration.input_limits.clone(), message_rate_limiter: UserRateLimiter::from_configuration(server_configuration, RateLimitGroup::ChatMessage) })
+}
+
+/// OpenAPI document of the chat server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("chat", env!("CARGO_PKG_VERSION"), ChatApi::openapi())
+}
+
+/// Keep the state to close the realtime chat connections with `close_all_connections` on shutdown.
+pub fn create_router(server_state: Arc<ServerState>, server_configuration: &ServerConfiguration) -> Router {
+    let app = Router::new()
+        .route("/hello", get(chat_request_component::hello))
+        .route("/get_user_chats", get(chat_request_component::get_user_chats).post(chat_request_component::get_user_chats))
+        .route("/get_chat_history", post(chat_request_component::get_chat_history))
+        .route("/get_chat_metadata", post(chat_request_component::get_chat_metadata))
+        .route("/update_group_chat_member", post(chat_request_component::update_group_chat_member))
+        .route("/create_new_direct_chat", post(chat_request_component::create_new_direct_chat))
+        .route("/create_new_group_chat", post(chat_request_component::create_new_group_chat))
+        .route("/moderate_chat", post(chat_moderation_component::moderate_chat))
+        .route("/realtime_chat", get(chat_realtime_component::web_socket_handler).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::ChatSocket)))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("chat", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "chat", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/get_user_chats", LegacyFailureBody::Fields(json!({ "direct_chats": null, "group_chats": null }))),
+        ("/get_chat_history", LegacyFailureBody::Fields(json!({ "messages": [] }))),
+        ("/get_chat_metadata", LegacyFailureBody::Fields(json!({ "metadata": null }))),
+        ("/update_group_chat_member", LegacyFailureBody::Status),
+        ("/create_new_direct_chat", LegacyFailureBody::Fields(json!({ "chat_id": null }))),
+        ("/create_new_group_chat", LegacyFailureBody::Fields(json!({ "chat_id": null }))),
+    ]
+}
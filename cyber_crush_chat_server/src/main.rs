@@ -1,41 +1,36 @@
-use shared_server_lib::{server_configurator::{ServerConfiguration, ServerType}, server_database};
+use shared_server_lib::{server_configurator::{ServerConfiguration, ServerType}, server_logging, server_shutdown, server_database, server_listener, server_cors, common};
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
-
-use tokio::net::TcpListener;
-use std::sync::Arc;
-use dashmap::DashMap;
-
-mod common_chat;
-mod chat_request_component;
-mod chat_realtime_component;
-
-use crate::common_chat::ServerState;
+use tracing::{error, info};
 
 #[tokio::main]
 async fn main() {
-    let server_configuration = ServerConfiguration::load("../server.conf");
-    let db_pool = server_database::connect_to_database(server_configuration.get_postgres_connection_url()).await;
-    let server_state = Arc::new(ServerState{ db_pool, token_to_chat_id: Arc::new(DashMap::new()), chat_connections: Arc::new(DashMap::new()) });
+    let server_configuration = match ServerConfiguration::load() {
+        Ok(configuration) => configuration,
+        Err(error) => {
+            eprintln!("Error: Reading server configuration failed: {}", error);
+            std::process::exit(1);
+        }
+    };
+    server_logging::init_logging(&server_configuration);
+    let db_pool = server_database::connect_to_database(&server_configuration, ServerType::Chat).await;
+    let server_state = cyber_crush_chat_server::create_server_state(db_pool, &server_configuration);
 
     let socket_addr = server_configuration.get_socket_addr(ServerType::Chat);
-
-    let listener = TcpListener::bind(socket_addr).await.unwrap();
-    println!("Chat server running at: {}", socket_addr);
-
-    let app = Router::new()
-        .route("/hello", get(chat_request_component::hello))
-        .route("/get_user_chats", post(chat_request_component::get_user_chats))
-        .route("/get_chat_history", post(chat_request_component::get_chat_history))
-        .route("/get_chat_metadata", post(chat_request_component::get_chat_metadata))
-        .route("/update_group_chat_member", post(chat_request_component::update_group_chat_member))
-        .route("/create_new_direct_chat", post(chat_request_component::create_new_direct_chat))
-        .route("/create_new_group_chat", post(chat_request_component::create_new_group_chat))
-        .route("/realtime_chat", get(chat_realtime_component::web_socket_handler))
-        .with_state(server_state.clone());
-
-    axum::serve(listener, app).await.unwrap();
+    info!("Chat server running at: {}", socket_addr);
+
+    let app = cyber_crush_chat_server::create_router(server_state.clone(), &server_configuration);
+    let app = server_logging::apply_request_tracing(app);
+    let app = common::apply_response_compatibility_mode(app, server_configuration.response_compatibility_mode);
+    let app = server_cors::apply_cors(app, &server_configuration.cors_allowed_origins);
+
+    let shutdown_signal = async move {
+        server_shutdown::shutdown_signal().await;
+        cyber_crush_chat_server::close_all_connections(&server_state).await;
+    };
+
+    if let Err(error) = server_listener::serve(socket_addr, app, &server_configuration, shutdown_signal).await {
+        error!("Server failed: {}", error);
+        std::process::exit(1);
+    }
+    info!("Server stopped");
 }
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use cyber_crush_protocol::ErrorResponse;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The endpoint needs a token, log in or call `set_token` first.
+    NotLoggedIn,
+    /// The server answered with an error body. `status` is 200 when the server runs in `response_compatibility_mode`.
+    Server { status: u16, error: ErrorResponse },
+    /// The server answered with a status and body the client does not understand, e.g. a proxy error page.
+    UnexpectedResponse { status: u16, body: String },
+    /// The realtime chat refused the connection or a message.
+    RealtimeChat(String),
+    Http(reqwest::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    Json(serde_json::Error),
+}
+
+impl ClientError {
+    /// Stable error code of a server error, e.g. `INSUFFICIENT_FUNDS`.
+    pub fn error_code(&self) -> Option<&str> {
+        match self {
+            ClientError::Server{ error, .. } => Some(&error.error_code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::NotLoggedIn => write!(f, "not logged in"),
+            ClientError::Server{ status, error } => write!(f, "server error {} ({}): {}", error.error_code, status, error.response_status.status_message),
+            ClientError::UnexpectedResponse{ status, body } => write!(f, "unexpected response ({}): {}", status, body),
+            ClientError::RealtimeChat(text) => write!(f, "realtime chat error: {}", text),
+            ClientError::Http(error) => write!(f, "HTTP error: {}", error),
+            ClientError::WebSocket(error) => write!(f, "WebSocket error: {}", error),
+            ClientError::Json(error) => write!(f, "JSON error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> ClientError {
+        ClientError::Http(error)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for ClientError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> ClientError {
+        ClientError::WebSocket(Box::new(error))
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(error: serde_json::Error) -> ClientError {
+        ClientError::Json(error)
+    }
+}
@@ -0,0 +1,558 @@
+//! Typed async client for every CyberCrush server endpoint and the realtime chat, built on the
+//! `cyber_crush_protocol` types. Meant for integration and load tests, NPC bots and admin tooling.
+
+use reqwest::{Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use cyber_crush_protocol::{
+    authentication::{
+        ChangePasswordRequest, ChangePasswordResponse, CreateUserRequest, CreateUserResponse, DeleteUserResponse, DisableUserResponse,
+        EnableUserResponse, GetLinkedCharactersResponse, GetRolesResponse, GetSessionsResponse, ImpersonateResponse, ImportUsersResponse, LinkCharacterRequest,
+        LinkCharacterResponse, LoginRequest, LoginResponse, LogoutAllResponse, LogoutResponse, ManagedUserRequest, ResetPasswordRequest,
+        ResetPasswordResponse, RevokeSessionRequest, RevokeSessionResponse, SetRoleRequest, SetRoleResponse, SwitchCharacterRequest, SwitchCharacterResponse,
+        UpdateUserRequest, UpdateUserResponse, ValidateTokenResponse,
+    },
+    bank::{GetUserFundsResponse, GetUserTransactionHistoryResponse, MintFundsRequest, MintFundsResponse, TransferFundsRequest, TransferFundsResponse},
+    chat::{
+        ChatModeration, CreateNewDirectChatRequest, CreateNewDirectChatResponse, CreateNewGroupChatRequest, CreateNewGroupChatResponse,
+        GetChatHistoryRequest, GetChatHistoryResponse, GetChatMetaDataRequest, GetChatMetaDataResponse, GetUserChatsResponse,
+        GroupMemberUpdate, ModerateChatRequest, ModerateChatResponse, UpdateGroupChatMemberRequest, UpdateGroupChatMemberResponse,
+    },
+    data::{GetAllUsernamesResponse, GetUserDataResponse},
+    game_state::{GameStateResponse, SetGameStateRequest},
+    hacking::{
+        AvailableHackTypesRequest, AvailableHackTypesResponse, GetHackableUsersResponse, GetHackerInfoRequest, GetHackerInfoResponse,
+        HackStateResultRequest, HackStateResultResponse, HackTokenRequest, HackTokenResponse,
+    },
+    health::{LivenessResponse, ReadinessResponse},
+    news::{DeleteNewsArticleRequest, DeleteNewsArticleResponse, GetNewsFeedResponse, PostNewsArticleRequest, PostNewsArticleResponse},
+    ErrorResponse, ResponseStatus,
+};
+
+mod error;
+mod realtime_chat;
+
+pub use cyber_crush_protocol as protocol;
+pub use crate::error::ClientError;
+pub use crate::realtime_chat::RealtimeChat;
+
+// In compatibility mode, failures of endpoints that answered with a bare `ResponseStatus` keep that flat shape.
+#[derive(Deserialize)]
+struct LegacyStatusError {
+    error_code: String,
+    status_message: String,
+    #[serde(default)]
+    retry_after_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Server {
+    Authentication,
+    Data,
+    Bank,
+    Chat,
+    News,
+    GameState,
+    Hacking,
+}
+
+impl Server {
+    pub const ALL: [Server; 7] = [Server::Authentication, Server::Data, Server::Bank, Server::Chat, Server::News, Server::GameState, Server::Hacking];
+
+    /// Path prefix of the server behind the launcher in `single_port` mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Server::Authentication => "authentication",
+            Server::Data => "data",
+            Server::Bank => "bank",
+            Server::Chat => "chat",
+            Server::News => "news",
+            Server::GameState => "game_state",
+            Server::Hacking => "hacking",
+        }
+    }
+
+    /// Port from the `server.conf` in the repository.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Server::Authentication => 3000,
+            Server::Data => 3001,
+            Server::Bank => 3002,
+            Server::Chat => 3003,
+            Server::News => 3004,
+            Server::GameState => 3005,
+            Server::Hacking => 3006,
+        }
+    }
+}
+
+/// Base URL of every server, without a trailing slash.
+#[derive(Debug, Clone)]
+pub struct ServerUrls {
+    urls: [String; 7],
+}
+
+impl ServerUrls {
+    /// Every server on its default port of `host_url`, e.g. `http://localhost` or `https://cybercrush.example`.
+    pub fn server_ports(host_url: &str) -> ServerUrls {
+        let host_url = host_url.trim_end_matches('/');
+        ServerUrls{ urls: Server::ALL.map(|server| format!("{}:{}", host_url, server.default_port())) }
+    }
+
+    /// Every server behind the launcher in `single_port` mode, e.g. `http://localhost:3100`.
+    pub fn launcher(launcher_url: &str) -> ServerUrls {
+        let launcher_url = launcher_url.trim_end_matches('/');
+        ServerUrls{ urls: Server::ALL.map(|server| format!("{}/{}", launcher_url, server.name())) }
+    }
+
+    pub fn with_server_url(mut self, server: Server, url: &str) -> ServerUrls {
+        self.urls[server as usize] = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn url(&self, server: Server) -> &str {
+        &self.urls[server as usize]
+    }
+}
+
+/// One player session. The token returned by `login` is sent as `Authorization: Bearer` with every
+/// request that needs it. Cloning is cheap and shares the connection pool.
+#[derive(Debug, Clone)]
+pub struct CyberCrushClient {
+    http_client: reqwest::Client,
+    server_urls: ServerUrls,
+    token: Option<String>,
+}
+
+impl CyberCrushClient {
+    pub fn new(server_urls: ServerUrls) -> CyberCrushClient {
+        CyberCrushClient{ http_client: reqwest::Client::new(), server_urls, token: None }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Uses a token from another session, e.g. one returned by `get_hack_token`.
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    pub async fn hello(&self, server: Server) -> Result<String, ClientError> {
+        let response = self.request(Method::GET, server, "/hello").send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if status.is_success() {
+            Ok(body)
+        }
+        else {
+            Err(ClientError::UnexpectedResponse{ status: status.as_u16(), body })
+        }
+    }
+
+    pub async fn health_live(&self, server: Server) -> Result<LivenessResponse, ClientError> {
+        self.send(self.request(Method::GET, server, "/health/live")).await
+    }
+
+    /// A server that is not ready answers with 503, returned as `ClientError::UnexpectedResponse`.
+    pub async fn health_ready(&self, server: Server) -> Result<ReadinessResponse, ClientError> {
+        self.send(self.request(Method::GET, server, "/health/ready")).await
+    }
+
+    // Authentication server
+
+    /// Logs in and keeps the token for the following requests. `device_label` names the session in `get_sessions`.
+    pub async fn login(&mut self, username: &str, password: &str, device_label: Option<&str>) -> Result<LoginResponse, ClientError> {
+        let request = LoginRequest{ username: username.to_string(), password: password.to_string(), device_label: device_label.map(str::to_string) };
+        let response: LoginResponse = self.send(self.request(Method::POST, Server::Authentication, "/login").json(&request)).await?;
+        self.token = Some(response.token.clone());
+        Ok(response)
+    }
+
+    pub async fn validate_token(&self) -> Result<ValidateTokenResponse, ClientError> {
+        self.send(self.authenticated_request(Method::POST, Server::Authentication, "/validate_token")?).await
+    }
+
+    pub async fn get_sessions(&self) -> Result<GetSessionsResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Authentication, "/get_sessions")?).await
+    }
+
+    pub async fn revoke_session(&self, session_id: &str) -> Result<RevokeSessionResponse, ClientError> {
+        self.post_authenticated(Server::Authentication, "/revoke_session", &RevokeSessionRequest{ session_id: session_id.to_string() }).await
+    }
+
+    /// Logs out the current session and forgets the token.
+    pub async fn logout(&mut self) -> Result<LogoutResponse, ClientError> {
+        let response = self.send(self.authenticated_request(Method::POST, Server::Authentication, "/logout")?).await?;
+        self.token = None;
+        Ok(response)
+    }
+
+    /// Logs out every session of the user and forgets the token.
+    pub async fn logout_all(&mut self) -> Result<LogoutAllResponse, ClientError> {
+        let response = self.send(self.authenticated_request(Method::POST, Server::Authentication, "/logout_all")?).await?;
+        self.token = None;
+        Ok(response)
+    }
+
+    /// Changes the password. The current session stays logged in, the other ones are logged out.
+    pub async fn change_password(&self, old_password: &str, new_password: &str) -> Result<ChangePasswordResponse, ClientError> {
+        let request = ChangePasswordRequest{ old_password: old_password.to_string(), new_password: new_password.to_string() };
+        self.post_authenticated(Server::Authentication, "/change_password", &request).await
+    }
+
+    /// Adds another character to the current session, see `switch_character`.
+    pub async fn link_character(&self, username: &str, password: &str) -> Result<LinkCharacterResponse, ClientError> {
+        let request = LinkCharacterRequest{ username: username.to_string(), password: password.to_string() };
+        self.post_authenticated(Server::Authentication, "/link_character", &request).await
+    }
+
+    /// Switches to a linked character and keeps its token, so the following requests act as that character.
+    pub async fn switch_character(&mut self, username: &str) -> Result<SwitchCharacterResponse, ClientError> {
+        let request = SwitchCharacterRequest{ username: username.to_string() };
+        let response: SwitchCharacterResponse = self.post_authenticated(Server::Authentication, "/switch_character", &request).await?;
+        self.token = Some(response.token.clone());
+        Ok(response)
+    }
+
+    pub async fn get_linked_characters(&self) -> Result<GetLinkedCharactersResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Authentication, "/get_linked_characters")?).await
+    }
+
+    // User management, needs the manage_users permission
+
+    pub async fn create_user(&self, request: &CreateUserRequest) -> Result<CreateUserResponse, ClientError> {
+        self.post_authenticated(Server::Authentication, "/admin/create_user", request).await
+    }
+
+    /// Creates all users or none, `users` has the format of `example_users.json`.
+    pub async fn import_users(&self, users: &[CreateUserRequest]) -> Result<ImportUsersResponse, ClientError> {
+        self.post_authenticated(Server::Authentication, "/admin/import_users", &users).await
+    }
+
+    pub async fn update_user(&self, request: &UpdateUserRequest) -> Result<UpdateUserResponse, ClientError> {
+        self.post_authenticated(Server::Authentication, "/admin/update_user", request).await
+    }
+
+    pub async fn disable_user(&self, username: &str) -> Result<DisableUserResponse, ClientError> {
+        let request = ManagedUserRequest{ username: username.to_string() };
+        self.post_authenticated(Server::Authentication, "/admin/disable_user", &request).await
+    }
+
+    pub async fn enable_user(&self, username: &str) -> Result<EnableUserResponse, ClientError> {
+        let request = ManagedUserRequest{ username: username.to_string() };
+        self.post_authenticated(Server::Authentication, "/admin/enable_user", &request).await
+    }
+
+    pub async fn delete_user(&self, username: &str) -> Result<DeleteUserResponse, ClientError> {
+        let request = ManagedUserRequest{ username: username.to_string() };
+        self.post_authenticated(Server::Authentication, "/admin/delete_user", &request).await
+    }
+
+    pub async fn reset_password(&self, username: &str, new_password: &str) -> Result<ResetPasswordResponse, ClientError> {
+        let request = ResetPasswordRequest{ username: username.to_string(), new_password: new_password.to_string() };
+        self.post_authenticated(Server::Authentication, "/admin/reset_password", &request).await
+    }
+
+    pub async fn get_roles(&self) -> Result<GetRolesResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Authentication, "/admin/get_roles")?).await
+    }
+
+    /// Creates the role or replaces it, its users are logged out when its permissions change.
+    pub async fn set_role(&self, role: &SetRoleRequest) -> Result<SetRoleResponse, ClientError> {
+        self.post_authenticated(Server::Authentication, "/admin/set_role", role).await
+    }
+
+    /// A token to act as the user, for another client with `set_token`. Every request made with it is audited.
+    pub async fn impersonate(&self, username: &str) -> Result<ImpersonateResponse, ClientError> {
+        let request = ManagedUserRequest{ username: username.to_string() };
+        self.post_authenticated(Server::Authentication, "/admin/impersonate", &request).await
+    }
+
+    // Data server
+
+    pub async fn get_all_usernames(&self) -> Result<GetAllUsernamesResponse, ClientError> {
+        self.send(self.request(Method::GET, Server::Data, "/get_all_usernames")).await
+    }
+
+    pub async fn get_user_data(&self) -> Result<GetUserDataResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Data, "/get_user_data")?).await
+    }
+
+    // Bank server
+
+    pub async fn get_user_funds(&self) -> Result<GetUserFundsResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Bank, "/get_user_funds")?).await
+    }
+
+    pub async fn get_user_transaction_history(&self) -> Result<GetUserTransactionHistoryResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Bank, "/get_user_transaction_history")?).await
+    }
+
+    pub async fn transfer_funds(&self, receiver_username: &str, amount: i32, message: &str) -> Result<TransferFundsResponse, ClientError> {
+        let request = TransferFundsRequest{ receiver_username: receiver_username.to_string(), message: message.to_string(), amount };
+        self.post_authenticated(Server::Bank, "/transfer_funds", &request).await
+    }
+
+    pub async fn mint_funds(&self, receiver_username: &str, amount: i32) -> Result<MintFundsResponse, ClientError> {
+        let request = MintFundsRequest{ receiver_username: receiver_username.to_string(), amount };
+        self.post_authenticated(Server::Bank, "/mint_funds", &request).await
+    }
+
+    // Chat server
+
+    pub async fn get_user_chats(&self) -> Result<GetUserChatsResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Chat, "/get_user_chats")?).await
+    }
+
+    /// Newest messages with `history_last_index` `None`, older ones with the lowest `in_chat_index` received so far.
+    pub async fn get_chat_history(&self, chat_id: i32, history_last_index: Option<i32>) -> Result<GetChatHistoryResponse, ClientError> {
+        self.post_authenticated(Server::Chat, "/get_chat_history", &GetChatHistoryRequest{ chat_id, history_last_index }).await
+    }
+
+    pub async fn get_chat_metadata(&self, chat_id: i32) -> Result<GetChatMetaDataResponse, ClientError> {
+        self.post_authenticated(Server::Chat, "/get_chat_metadata", &GetChatMetaDataRequest{ chat_id }).await
+    }
+
+    pub async fn update_group_chat_member(&self, chat_id: i32, update: GroupMemberUpdate) -> Result<UpdateGroupChatMemberResponse, ClientError> {
+        self.post_authenticated(Server::Chat, "/update_group_chat_member", &UpdateGroupChatMemberRequest{ chat_id, update }).await
+    }
+
+    pub async fn moderate_chat(&self, chat_id: i32, moderation: ChatModeration) -> Result<ModerateChatResponse, ClientError> {
+        self.post_authenticated(Server::Chat, "/moderate_chat", &ModerateChatRequest{ chat_id, moderation }).await
+    }
+
+    pub async fn create_new_direct_chat(&self, partner_username: &str, creation_message: &str) -> Result<CreateNewDirectChatResponse, ClientError> {
+        let request = CreateNewDirectChatRequest{ partner_username: partner_username.to_string(), creation_message: creation_message.to_string() };
+        self.post_authenticated(Server::Chat, "/create_new_direct_chat", &request).await
+    }
+
+    pub async fn create_new_group_chat(&self, title: &str, creation_message: &str) -> Result<CreateNewGroupChatResponse, ClientError> {
+        let request = CreateNewGroupChatRequest{ title: title.to_string(), creation_message: creation_message.to_string() };
+        self.post_authenticated(Server::Chat, "/create_new_group_chat", &request).await
+    }
+
+    /// Opens `/realtime_chat` for `chat_id`, over `ws://` or `wss://` following the chat server URL.
+    pub async fn connect_realtime_chat(&self, chat_id: i32) -> Result<RealtimeChat, ClientError> {
+        let token = self.token.clone().ok_or(ClientError::NotLoggedIn)?;
+        let chat_url = self.server_urls.url(Server::Chat);
+        let web_socket_url = match chat_url.strip_prefix("https://") {
+            Some(address) => format!("wss://{}/realtime_chat", address),
+            None => format!("ws://{}/realtime_chat", chat_url.trim_start_matches("http://")),
+        };
+
+        RealtimeChat::connect(&web_socket_url, token, chat_id).await
+    }
+
+    // News server
+
+    pub async fn get_news_feed(&self) -> Result<GetNewsFeedResponse, ClientError> {
+        self.send(self.request(Method::GET, Server::News, "/get_news_feed")).await
+    }
+
+    pub async fn post_news_article(&self, title: &str, content: &str) -> Result<PostNewsArticleResponse, ClientError> {
+        let request = PostNewsArticleRequest{ title: title.to_string(), content: content.to_string() };
+        self.post_authenticated(Server::News, "/post_news_article", &request).await
+    }
+
+    pub async fn delete_news_article(&self, post_id: i32) -> Result<DeleteNewsArticleResponse, ClientError> {
+        self.post_authenticated(Server::News, "/delete_news_article", &DeleteNewsArticleRequest{ post_id }).await
+    }
+
+    // Game state server
+
+    pub async fn get_game_state(&self) -> Result<GameStateResponse, ClientError> {
+        self.send(self.request(Method::GET, Server::GameState, "/game_state")).await
+    }
+
+    pub async fn set_game_state(&self, is_game_online: bool, info_panel_text: Option<&str>) -> Result<GameStateResponse, ClientError> {
+        let request = SetGameStateRequest{ is_game_online, info_panel_text: info_panel_text.map(str::to_string) };
+        self.post_authenticated(Server::GameState, "/set_game_state", &request).await
+    }
+
+    // Hacking server
+
+    pub async fn get_hacker_info(&self, personal_number: &str) -> Result<GetHackerInfoResponse, ClientError> {
+        let request = GetHackerInfoRequest{ personal_number: personal_number.to_string() };
+        self.post_authenticated(Server::Hacking, "/get_hacker_info", &request).await
+    }
+
+    pub async fn get_hackable_users(&self) -> Result<GetHackableUsersResponse, ClientError> {
+        self.send(self.authenticated_request(Method::GET, Server::Hacking, "/get_hackable_users")?).await
+    }
+
+    pub async fn get_available_hack_types(&self, hacker_personal_number: &str, victim_personal_number: &str) -> Result<AvailableHackTypesResponse, ClientError> {
+        let request = AvailableHackTypesRequest{ hacker_personal_number: hacker_personal_number.to_string(), victim_personal_number: victim_personal_number.to_string() };
+        self.post_authenticated(Server::Hacking, "/get_available_hack_types", &request).await
+    }
+
+    pub async fn get_hack_token(&self, victim_personal_number: &str) -> Result<HackTokenResponse, ClientError> {
+        let request = HackTokenRequest{ victim_personal_number: victim_personal_number.to_string() };
+        self.post_authenticated(Server::Hacking, "/get_hack_token", &request).await
+    }
+
+    pub async fn log_hack_state_result(&self, request: &HackStateResultRequest) -> Result<HackStateResultResponse, ClientError> {
+        self.post_authenticated(Server::Hacking, "/log_hack_state_result", request).await
+    }
+
+    fn request(&self, method: Method, server: Server, path: &str) -> RequestBuilder {
+        self.http_client.request(method, format!("{}{}", self.server_urls.url(server), path))
+    }
+
+    fn authenticated_request(&self, method: Method, server: Server, path: &str) -> Result<RequestBuilder, ClientError> {
+        match &self.token {
+            Some(token) => Ok(self.request(method, server, path).bearer_auth(token)),
+            None => Err(ClientError::NotLoggedIn),
+        }
+    }
+
+    async fn post_authenticated<B: Serialize, R: DeserializeOwned>(&self, server: Server, path: &str, body: &B) -> Result<R, ClientError> {
+        self.send(self.authenticated_request(Method::POST, server, path)?.json(body)).await
+    }
+
+    // Servers in `response_compatibility_mode` answer errors with 200, so the error body is looked for first.
+    async fn send<R: DeserializeOwned>(&self, request: RequestBuilder) -> Result<R, ClientError> {
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+
+        if let Ok(error) = serde_json::from_slice::<ErrorResponse>(&body) {
+            return Err(ClientError::Server{ status, error });
+        }
+
+        if let Ok(error) = serde_json::from_slice::<LegacyStatusError>(&body) {
+            let error = ErrorResponse{ error_code: error.error_code, response_status: ResponseStatus::fail(error.status_message), retry_after_seconds: error.retry_after_seconds };
+            return Err(ClientError::Server{ status, error });
+        }
+
+        match serde_json::from_slice::<R>(&body) {
+            Ok(response) if (200..300).contains(&status) => Ok(response),
+            _ => Err(ClientError::UnexpectedResponse{ status, body: String::from_utf8_lossy(&body).into_owned() }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use axum::{extract::{Request, State}, http::{header, StatusCode}, response::IntoResponse, Router};
+
+    // Method, path and `Authorization` header of every request the test server received.
+    type ReceivedRequests = Arc<Mutex<Vec<(String, String, Option<String>)>>>;
+
+    // Serves `body` with `status` to every request on a local port, as the launcher in `single_port` mode.
+    async fn test_server(status: StatusCode, body: &'static str) -> (CyberCrushClient, ReceivedRequests) {
+        let received_requests = ReceivedRequests::default();
+        let router = Router::new()
+            .fallback(move |State(received_requests): State<ReceivedRequests>, request: Request| async move {
+                let authorization = request.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()).map(str::to_string);
+                received_requests.lock().unwrap().push((request.method().to_string(), request.uri().path().to_string(), authorization));
+                (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+            })
+            .with_state(received_requests.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let launcher_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router.into_make_service()).await });
+
+        (CyberCrushClient::new(ServerUrls::launcher(&launcher_url)), received_requests)
+    }
+
+    fn logged_in(mut client: CyberCrushClient) -> CyberCrushClient {
+        client.set_token(Some("token_1".into()));
+        client
+    }
+
+    fn received(received_requests: &ReceivedRequests) -> Vec<(String, String, Option<String>)> {
+        received_requests.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_the_server_path_with_the_token() {
+        let (client, received_requests) = test_server(StatusCode::OK, r#"{ "response_status": { "success": true, "status_message": "" }, "funds": 150 }"#).await;
+        let client = logged_in(client);
+
+        let response = client.get_user_funds().await.unwrap();
+
+        assert_eq!(response.funds, 150);
+        assert_eq!(received(&received_requests), vec![("GET".to_string(), "/bank/get_user_funds".to_string(), Some("Bearer token_1".to_string()))]);
+    }
+
+    #[tokio::test]
+    async fn login_keeps_the_token_for_the_following_requests() {
+        let body = r#"{ "response_status": { "success": true, "status_message": "" }, "token": "token_2", "token_expires_at": "2030-01-01T00:00:00Z", "session_id": "session_1" }"#;
+        let (mut client, received_requests) = test_server(StatusCode::OK, body).await;
+
+        client.login("alice", "pass1", None).await.unwrap();
+        let _ = client.get_hackable_users().await;
+
+        assert_eq!(client.token(), Some("token_2"));
+        assert_eq!(received(&received_requests), vec![
+            ("POST".to_string(), "/authentication/login".to_string(), None),
+            ("GET".to_string(), "/hacking/get_hackable_users".to_string(), Some("Bearer token_2".to_string())),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn authenticated_requests_are_not_sent_without_a_token() {
+        let (client, received_requests) = test_server(StatusCode::OK, "{}").await;
+
+        let error = client.transfer_funds("bob", 10, "rent").await.unwrap_err();
+
+        assert!(matches!(error, ClientError::NotLoggedIn));
+        assert!(received(&received_requests).is_empty());
+    }
+
+    #[tokio::test]
+    async fn error_bodies_keep_their_status_and_code() {
+        let body = r#"{ "error_code": "RATE_LIMITED", "response_status": { "success": false, "status_message": "Too many requests, slow down" }, "retry_after_seconds": 30 }"#;
+        let (client, _) = test_server(StatusCode::TOO_MANY_REQUESTS, body).await;
+
+        let error = logged_in(client).transfer_funds("bob", 10, "rent").await.unwrap_err();
+
+        assert_eq!(error.error_code(), Some("RATE_LIMITED"));
+        match error {
+            ClientError::Server{ status, error } => {
+                assert_eq!(status, 429);
+                assert_eq!(error.retry_after_seconds, Some(30));
+            },
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn compatibility_mode_failures_are_errors() {
+        let (client, _) = test_server(StatusCode::OK, r#"{ "success": false, "status_message": "Not enough funds", "error_code": "INSUFFICIENT_FUNDS" }"#).await;
+
+        let error = logged_in(client).transfer_funds("bob", 10, "rent").await.unwrap_err();
+
+        match error {
+            ClientError::Server{ status, error } => {
+                assert_eq!(status, 200);
+                assert_eq!(error.error_code, "INSUFFICIENT_FUNDS");
+                assert_eq!(error.response_status.status_message, "Not enough funds");
+            },
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_bodies_are_unexpected_responses() {
+        let (client, _) = test_server(StatusCode::BAD_GATEWAY, "Bad gateway").await;
+
+        let error = client.get_news_feed().await.unwrap_err();
+
+        assert!(matches!(error, ClientError::UnexpectedResponse{ status: 502, body } if body == "Bad gateway"));
+    }
+
+    #[test]
+    fn server_urls_follow_the_deployment() {
+        let urls = ServerUrls::server_ports("http://localhost/").with_server_url(Server::Chat, "https://chat.example/");
+
+        assert_eq!(urls.url(Server::Bank), "http://localhost:3002");
+        assert_eq!(urls.url(Server::Chat), "https://chat.example");
+        assert_eq!(ServerUrls::launcher("http://localhost:3100").url(Server::GameState), "http://localhost:3100/game_state");
+    }
+}
@@ -0,0 +1,71 @@
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::Message,
+    MaybeTlsStream,
+    WebSocketStream,
+};
+
+use cyber_crush_protocol::chat::{ChatClientMessage, ChatResponse};
+
+use crate::ClientError;
+
+/// An initialized `/realtime_chat` connection to one chat. Messages sent by this connection are not
+/// echoed back, every other member's message arrives as `ChatResponse::ChatMessage`.
+pub struct RealtimeChat {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    token: String,
+    chat_id: i32,
+}
+
+impl RealtimeChat {
+    pub(crate) async fn connect(url: &str, token: String, chat_id: i32) -> Result<RealtimeChat, ClientError> {
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        let mut realtime_chat = RealtimeChat{ socket, token, chat_id };
+
+        realtime_chat.send(&ChatClientMessage::Init{ token: realtime_chat.token.clone(), chat_id }).await?;
+        match realtime_chat.next_response().await {
+            Some(Ok(ChatResponse::Info{ .. })) => Ok(realtime_chat),
+            Some(Ok(ChatResponse::Error{ text })) => Err(ClientError::RealtimeChat(text)),
+            Some(Ok(response)) => Err(ClientError::RealtimeChat(format!("unexpected response to init: {:?}", response))),
+            Some(Err(error)) => Err(error),
+            None => Err(ClientError::RealtimeChat("connection closed during init".to_string())),
+        }
+    }
+
+    pub fn chat_id(&self) -> i32 {
+        self.chat_id
+    }
+
+    pub async fn send_message(&mut self, message: &str) -> Result<(), ClientError> {
+        self.send(&ChatClientMessage::Msg{ token: self.token.clone(), message: message.to_string() }).await
+    }
+
+    /// Waits for the next frame from the server. Returns `None` once the server closed the connection,
+    /// e.g. with code 1012 when it restarts.
+    pub async fn next_response(&mut self) -> Option<Result<ChatResponse, ClientError>> {
+        while let Some(message) = self.socket.next().await {
+            match message {
+                Ok(Message::Text(text)) => return Some(serde_json::from_str::<ChatResponse>(&text).map_err(ClientError::from)),
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(error) => return Some(Err(error.into())),
+            }
+        }
+
+        None
+    }
+
+    /// Leaves the chat and closes the connection.
+    pub async fn exit(mut self) -> Result<(), ClientError> {
+        self.send(&ChatClientMessage::Exit{ token: self.token.clone() }).await?;
+        self.socket.close(None).await?;
+        Ok(())
+    }
+
+    async fn send(&mut self, message: &ChatClientMessage) -> Result<(), ClientError> {
+        let text = serde_json::to_string(message)?;
+        self.socket.send(Message::Text(text.into())).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics::{self, TimedQuery}, server_openapi, common::{self, LegacyFailureBody, ErrorResponse, ResponseStatus, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, server_rate_limit::{RateLimitLayer, RateLimitGroup}};
+
+use axum::{
+    extract::{Json, State},
+    routing::get,
+    Router,
+};
+
+use std::sync::Arc;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::data::{GetAllUsernamesResponse, GetUserDataResponse};
+
+#[derive(OpenApi)]
+#[openapi(paths(hello, get_all_usernames, get_user_data))]
+struct DataApi;
+
+#[derive(Debug)]
+struct ServerState {
+    db_pool: PgPool, //This is thread safe
+    session_tokens: SessionTokens,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+/// OpenAPI document of the data server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("data", env!("CARGO_PKG_VERSION"), DataApi::openapi())
+}
+
+pub fn create_router(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{ session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()), db_pool });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/get_all_usernames", get(get_all_usernames))
+        .route("/get_user_data", get(get_user_data).post(get_user_data))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("data", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "data", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/get_all_usernames", LegacyFailureBody::Fields(json!({ "usernames": [] }))),
+        ("/get_user_data", LegacyFailureBody::Fields(json!({ "username": "", "personal_number": "", "can_publish_posts": false, "cyber_defence_level": 0, "extra_data": "" }))),
+    ]
+}
+
+#[utoipa::path(get, path = "/hello", tag = "data", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush data server!"
+}
+
+/// Usernames of every player.
+#[utoipa::path(get, path = "/get_all_usernames", tag = "data", responses(
+    (status = 200, body = GetAllUsernamesResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_all_usernames(State(state): State<Arc<ServerState>>) -> Result<Json<GetAllUsernamesResponse>, ServerError> {
+    let all_usernames_query: Result<Vec<(String,)>, sqlx::Error> = sqlx::query_as(
+        r#"SELECT username FROM users"#)
+        .fetch_all(&state.db_pool)
+        .timed("get_all_usernames")
+        .await;
+
+    match all_usernames_query {
+        Ok(all_usernames) => {
+            let usernames: Vec<String> = all_usernames.into_iter().map(|(u,)| u).collect();
+            Ok(Json(GetAllUsernamesResponse{ response_status: ResponseStatus::success(), usernames }))
+        },
+        Err(error) => {
+            error!("Getting all usernames failed. Error: {}", error);
+            Err(ServerError::internal("No usernames found: Server error!"))
+        }
+    }
+}
+
+/// Profile of the logged in user. `extra_data` is a JSON document encoded as a string.
+#[utoipa::path(method(get, post), path = "/get_user_data", tag = "data", security(("token" = [])), responses(
+    (status = 200, body = GetUserDataResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "USER_DATA_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_user_data(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetUserDataResponse>, ServerError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct UserDataQuery {
+        username: String,
+        personal_number: i32,
+        can_publish_posts: bool,
+        cyber_defence_level: i32,
+        extra_data: serde_json::Value,
+    }
+
+    let user_data_query = sqlx::query_as::<_, UserDataQuery>(
+        r#"SELECT username, personal_number, 'publish_news' = ANY(user_permissions(id)) AS can_publish_posts, cyber_defence_level, extra_data FROM users WHERE id = $1"#)
+        .bind(user.user_id)
+        .fetch_optional(&state.db_pool)
+        .timed("get_user_data")
+        .await;
+    
+    match user_data_query {
+        Ok(Some(user_data)) => Ok(Json(GetUserDataResponse::success(user_data.username,
+                                                                    user_data.personal_number.to_string(),
+                                                                    user_data.can_publish_posts,
+                                                                    user_data.cyber_defence_level,
+                                                                    user_data.extra_data.to_string()))),
+        Ok(None) => Err(ServerError::not_found("USER_DATA_NOT_FOUND", "No user data found.")),
+        Err(error) => {
+            error!("Getting user data failed for user id: {}. Error: {}", user.user_id, error);
+            Err(ServerError::internal("No user data found. Server error!"))
+        }
+    }
+}
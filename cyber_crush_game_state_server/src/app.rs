@@ -8,14 +8,16 @@ use ratatui::{
     style::{ Style, Modifier, Color },
 };
 
-use crossterm;
-use crossterm::event::{ KeyCode, KeyEventKind };
+use crossterm::event::{ KeyCode, KeyEventKind, KeyModifiers };
 
-use tokio::sync::Mutex;
+use tokio::sync::{ Mutex, watch };
 use std::sync::Arc;
+use std::time::Duration;
 
 use std::io;
 
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
 #[derive(Clone)]
 pub struct GameState {
     pub is_online: bool,
@@ -85,21 +87,25 @@ impl App {
         }
     }
 
-    pub async fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+    pub async fn run(&mut self, terminal: &mut DefaultTerminal, shutdown: watch::Receiver<bool>) -> io::Result<()> {
         self.reset_select();
         self.reset_popup_select();
 
-        while self.is_running {
+        while self.is_running && !*shutdown.borrow() {
             {
                 let game_state_lock = self.game_state.lock().await;
                 self.game_state_snapshot = Some(game_state_lock.clone());
             }
 
-            terminal.draw(|frame| self.draw(frame) ).unwrap();
+            terminal.draw(|frame| self.draw(frame) )?;
 
-            match crossterm::event::read()? {
-                crossterm::event::Event::Key(key_event) => self.handle_key_event(key_event).await?,
-                _ => {}
+            // Poll instead of blocking on read, so a shutdown signal is noticed without a key press.
+            if !crossterm::event::poll(EVENT_POLL_INTERVAL)? {
+                continue;
+            }
+
+            if let crossterm::event::Event::Key(key_event) = crossterm::event::read()? {
+                self.handle_key_event(key_event).await?;
             }
         }
 
@@ -110,23 +116,23 @@ impl App {
         if let Some(popup) = &self.popup_type {
             match popup {
                 PopupType::TextInput => { 
-                    return 0;
+                    0
                 }
                 PopupType::ExitConfirmation => {
-                    return self.exit_options.len();
+                    self.exit_options.len()
                 }
             }
         }
         else {
             match self.screen_state {
                 ScreenState::MainMenu => {
-                    return self.menu_options.len();
+                    self.menu_options.len()
                 },
                 ScreenState::GameState => {
-                    return self.game_state_options.len();
+                    self.game_state_options.len()
                 }
                 ScreenState::GameOfflineInfo => {
-                    return self.offline_info_options.len();
+                    self.offline_info_options.len()
                 }
             }
         }
@@ -145,7 +151,7 @@ impl App {
     }
 
     fn select_next(&mut self) {
-        if self.popup_type == None {
+        if self.popup_type.is_none() {
            let index = match self.select_state.selected() {
                 Some(i) => {
                     if i >= self.get_current_opiton_size() - 1 { 0 } else { i + 1 }
@@ -168,10 +174,10 @@ impl App {
     }
 
     fn select_previus(&mut self) {
-        if self.popup_type == None {
+        if self.popup_type.is_none() {
            let index = match self.select_state.selected() {
                 Some(i) => {
-                    if i <= 0 { self.get_current_opiton_size() - 1 } else { i - 1 }
+                    if i == 0 { self.get_current_opiton_size() - 1 } else { i - 1 }
                 },
                 None => 0,
             };
@@ -181,7 +187,7 @@ impl App {
         else {
            let index = match self.select_popup_state.selected() {
                 Some(i) => {
-                    if i <= 0 { self.get_current_opiton_size() - 1 } else { i - 1 }
+                    if i == 0 { self.get_current_opiton_size() - 1 } else { i - 1 }
                 },
                 None => 0,
             };
@@ -250,7 +256,7 @@ impl App {
                             self.popup_type = Some(PopupType::ExitConfirmation);
                             self.reset_popup_select();
                         },
-                        _ => { return; }
+                        _ => {}
                     }
                 }
                 ScreenState::GameState => {
@@ -267,7 +273,7 @@ impl App {
                             self.screen_state = ScreenState::MainMenu;
                             self.reset_select();
                         }
-                        _ => { return; }
+                        _ => {}
                     }
                 }
                 ScreenState::GameOfflineInfo => {
@@ -283,7 +289,7 @@ impl App {
                             self.screen_state = ScreenState::MainMenu;
                             self.reset_select();
                         }
-                        _ => { return; }
+                        _ => {}
                     }
                 }
             }
@@ -291,7 +297,7 @@ impl App {
     }
 
     fn perform_escape(&mut self) {
-        if self.popup_type != None {
+        if self.popup_type.is_some() {
             self.popup_type = None;
         }
     }
@@ -494,6 +500,12 @@ impl App {
 
     async fn handle_key_event(&mut self, key_event: crossterm::event::KeyEvent) -> io::Result<()> {
         if key_event.kind == KeyEventKind::Press {
+            // Raw mode swallows SIGINT, so Ctrl+C arrives here as a key press.
+            if key_event.code == KeyCode::Char('c') && key_event.modifiers.contains(KeyModifiers::CONTROL) {
+                self.is_running = false;
+                return Ok(());
+            }
+
             match key_event.code {
                 KeyCode::Char(character) => self.char_entered(character),
                 KeyCode::Backspace => self.perform_backspace(),
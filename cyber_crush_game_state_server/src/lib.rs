@@ -0,0 +1,113 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics, server_openapi, common::{ErrorResponse, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, server_rate_limit::{RateLimitLayer, RateLimitGroup}};
+
+use axum::{
+    extract::{ Json, State },
+    response::IntoResponse,
+    routing::{get, post},
+    Router,
+};
+
+use sqlx::PgPool;
+use tokio::sync::{ Mutex, watch };
+
+use std::sync::Arc;
+use tracing::{error, info};
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::{authentication::Permission, game_state::{GameStateResponse, SetGameStateRequest}};
+
+mod app;
+pub use crate::app::GameState;
+use crate::app::App;
+
+#[derive(OpenApi)]
+#[openapi(paths(hello, get_game_state, set_game_state))]
+struct GameStateApi;
+
+struct ServerState {
+    game_state: Arc<Mutex<GameState>>,
+    session_tokens: SessionTokens,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+pub fn create_game_state() -> Arc<Mutex<GameState>> {
+    Arc::new(Mutex::new(GameState{ is_online: true,
+                                   offline_message_info: "#TR-GAME_IS_OFFLINE".into()
+                                   }))
+}
+
+/// OpenAPI document of the game state server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("game state", env!("CARGO_PKG_VERSION"), GameStateApi::openapi())
+}
+
+/// The game state is shared with the game master TUI, both can change it.
+pub fn create_router(game_state: Arc<Mutex<GameState>>, db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{ game_state, session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()) });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/game_state", get(get_game_state))
+        .route("/set_game_state", post(set_game_state).layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default)))
+        .with_state(server_state);
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("game_state", env!("CARGO_PKG_VERSION"), Some(db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    server_metrics::apply_metrics(app, "game_state", Some(db_pool))
+}
+
+/// Runs the game master TUI until it is closed or `shutdown` is set, then sets `shutdown` so the servers stop too.
+/// The TUI owns the terminal, so logs must go to a file while it runs.
+pub async fn run_tui(game_state: Arc<Mutex<GameState>>, shutdown: watch::Sender<bool>) {
+    let mut terminal = ratatui::init();
+
+    let mut app = App::new(game_state);
+    let app_result = app.run(&mut terminal, shutdown.subscribe()).await;
+
+    ratatui::restore();
+
+    if let Err(error) = app_result {
+        error!("App run returned an error: {}", error);
+    }
+
+    shutdown.send_replace(true);
+}
+
+#[utoipa::path(get, path = "/hello", tag = "game_state", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush game state server!"
+}
+
+/// Whether the game is online, and the text for the info panel while it is offline.
+#[utoipa::path(get, path = "/game_state", tag = "game_state", responses((status = 200, body = GameStateResponse)))]
+async fn get_game_state(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
+    let lock = state.game_state.lock().await;
+    let game_state = GameStateResponse{ is_game_online: lock.is_online, info_panel_text: lock.offline_message_info.clone() };
+    Json(game_state)
+}
+
+/// Sets the game online or offline, the same as the game master TUI does.
+#[utoipa::path(post, path = "/set_game_state", tag = "game_state", security(("token" = [])), request_body = SetGameStateRequest, responses(
+    (status = 200, body = GameStateResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_GAME_STATE_PRIVILEGE", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn set_game_state(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<SetGameStateRequest>) -> Result<Json<GameStateResponse>, ServerError> {
+    user.require(Permission::ControlGameState)?;
+
+    let mut lock = state.game_state.lock().await;
+    lock.is_online = payload.is_game_online;
+    if let Some(info_panel_text) = payload.info_panel_text {
+        lock.offline_message_info = info_panel_text;
+    }
+
+    info!("Game state set to {} by user_id {}", if lock.is_online { "online" } else { "offline" }, user.user_id);
+    Ok(Json(GameStateResponse{ is_game_online: lock.is_online, info_panel_text: lock.offline_message_info.clone() }))
+}
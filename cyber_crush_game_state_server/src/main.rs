@@ -1,97 +1,83 @@
-use shared_server_lib::server_configurator::{ServerConfiguration, ServerType};
+use shared_server_lib::{server_configurator::{ServerConfiguration, ServerType}, server_logging, server_shutdown, server_database, server_listener, server_cors, common};
 
-use axum::{
-    extract::{ Json, State },
-    response::IntoResponse,
-    routing::get,
-    Router,
-};
-
-use serde::Serialize;
-use tokio::net::TcpListener;
-use tokio;
-use tokio::sync::Mutex;
-use crate::tokio::time::{ sleep, Duration };
+use tokio::sync::{ Mutex, watch };
+use tokio::time::{ sleep, Duration };
 
 use std::sync::Arc;
+use tracing::{error, info};
 
-mod app;
-use crate::{ app::{App, GameState} };
-
-#[derive(Debug, Serialize)]
-struct GameStateResponse {
-    is_game_online: bool,
-    info_panel_text: String,
-}
+const DEFAULT_LOG_FILE: &str = "game_state_server.log";
 
 #[tokio::main]
 async fn main() {
-    let game_state = Arc::new(Mutex::new(GameState{ is_online: true,
-                                                    offline_message_info: "#TR-GAME_IS_OFFLINE".into()
-                                                    }));
+    let game_state = cyber_crush_game_state_server::create_game_state();
     
     let server_ready = Arc::new(Mutex::new(false));
 
+    let mut server_configuration = match ServerConfiguration::load() {
+        Ok(configuration) => configuration,
+        Err(error) => {
+            eprintln!("Error: Reading server configuration failed: {}", error);
+            std::process::exit(1);
+        }
+    };
+    // The TUI owns the terminal, so logs can not go to stdout.
+    server_configuration.log_file.get_or_insert_with(|| DEFAULT_LOG_FILE.into());
+    server_logging::init_logging(&server_configuration);
+    let db_pool = server_database::connect_to_database(&server_configuration, ServerType::GameState).await;
+
     //====================================================
 
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+
+    let shutdown_sender_signal = shutdown_sender.clone();
+    tokio::spawn(async move {
+        server_shutdown::shutdown_signal().await;
+        shutdown_sender_signal.send_replace(true);
+    });
+
     let game_state_server = game_state.clone();
     let server_ready_server = server_ready.clone();
+    let mut shutdown_receiver_server = shutdown_receiver.clone();
 
     let server_handle = tokio::spawn(async move {
-        let server_configuration = ServerConfiguration::load("../server.conf");
-    
         let socket_addr = server_configuration.get_socket_addr(ServerType::GameState);
-        let listener = TcpListener::bind(socket_addr).await.unwrap();
-        println!("Game state server running at: {}", socket_addr);
+        info!("Game state server running at: {}", socket_addr);
 
-        let app = Router::new()
-            .route("/hello", get(hello))
-            .route("/game_state", get(get_game_state))
-            .with_state(game_state_server);
+        let app = cyber_crush_game_state_server::create_router(game_state_server, db_pool, &server_configuration);
+        let app = server_logging::apply_request_tracing(app);
+        let app = common::apply_response_compatibility_mode(app, server_configuration.response_compatibility_mode);
+        let app = server_cors::apply_cors(app, &server_configuration.cors_allowed_origins);
 
         {
             let mut lock = server_ready_server.lock().await;
             *lock = true;
         }
 
-        axum::serve(listener, app).await.unwrap();
+        let shutdown_signal = async move {
+            let _ = shutdown_receiver_server.wait_for(|is_shutting_down| *is_shutting_down).await;
+        };
+
+        if let Err(error) = server_listener::serve(socket_addr, app, &server_configuration, shutdown_signal).await {
+            error!("Server failed: {}", error);
+            std::process::exit(1);
+        }
+        info!("Server stopped");
     });
 
-    println!("Waiting for server to start...");
+    info!("Waiting for server to start...");
     loop {
         sleep(Duration::from_secs(1)).await;
         let lock = server_ready.lock().await;
 
-        if *lock == true {
+        if *lock {
             break;
         }
     }
-    println!("The server is still running!");
-
-    let game_state_tui = game_state.clone();
-    let tui_handle = tokio::spawn(async move {
-        let mut terminal = ratatui::init();
-        
-        let mut app = App::new(game_state_tui);
-        let app_result = app.run(&mut terminal).await;
-        
-        if let Err(error) = app_result {
-            println!("App run returned an error: {}", error);
-        }
+    info!("The server is still running!");
 
-        ratatui::restore();
-    });
+    let tui_handle = tokio::spawn(cyber_crush_game_state_server::run_tui(game_state, shutdown_sender));
     
     server_handle.await.unwrap();
     tui_handle.await.unwrap();
 }
-
-async fn hello() -> &'static str {
-    "Hello, cyber crush game state server!"
-}
-
-async fn get_game_state(State(state): State<Arc<Mutex<GameState>>>) -> impl IntoResponse {
-    let lock = state.lock().await;
-    let game_state = GameStateResponse{ is_game_online: lock.is_online, info_panel_text: lock.offline_message_info.clone() };
-    Json(game_state)
-}
@@ -0,0 +1,411 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics::{self, TimedQuery}, server_openapi, common::{self, LegacyFailureBody, ErrorResponse, ResponseStatus, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, authorization::UserPermissions, server_rate_limit::{RateLimitLayer, RateLimitGroup}};
+
+use axum::{
+    extract::{Json, State},
+    routing::{get, post},
+    Router,
+};
+
+use std::sync::Arc;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{error, info};
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::{authentication::Permission, hacking::{GetHackerInfoRequest, HackerInfo, GetHackerInfoResponse, HackableUser, GetHackableUsersResponse, HackType, AvailableHackTypesRequest, AvailableHackTypesResponse, HackTokenRequest, HackTokenResponse, HackStateResultRequest, HackStateResultResponse}};
+
+#[derive(OpenApi)]
+#[openapi(paths(hello, get_hacker_info, get_hackable_users, get_available_hack_types, get_hack_token, log_hack_state_result))]
+struct HackingApi;
+
+#[derive(Debug)]
+struct ServerState {
+    db_pool: PgPool, //This is thread safe
+    session_tokens: SessionTokens,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+/// OpenAPI document of the hacking server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("hacking", env!("CARGO_PKG_VERSION"), HackingApi::openapi())
+}
+
+pub fn create_router(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{ session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()), db_pool });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/get_hacker_info", post(get_hacker_info))
+        .route("/get_hackable_users", get(get_hackable_users))
+        .route("/get_available_hack_types", post(get_available_hack_types))
+        .route("/get_hack_token", post(get_hack_token))
+        .route("/log_hack_state_result", post(log_hack_state_result))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("hacking", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "hacking", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/get_hacker_info", LegacyFailureBody::Fields(json!({ "hacker_info": { "username": "", "can_hack": false } }))),
+        ("/get_hackable_users", LegacyFailureBody::Fields(json!({ "users": [] }))),
+        ("/get_available_hack_types", LegacyFailureBody::Fields(json!({ "available_hacks": [] }))),
+        ("/get_hack_token", LegacyFailureBody::Fields(json!({ "token": null }))),
+        ("/log_hack_state_result", LegacyFailureBody::Status),
+    ]
+}
+
+#[utoipa::path(get, path = "/hello", tag = "hacking", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush hacking server!"
+}
+
+/// The hacker with this personal number, only if the user can hack. Every hacking endpoint needs the `hack` permission.
+#[utoipa::path(post, path = "/get_hacker_info", tag = "hacking", security(("token" = [])), request_body = GetHackerInfoRequest, responses(
+    (status = 200, body = GetHackerInfoResponse),
+    (status = 400, description = "INVALID_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_HACKING_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_hacker_info(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<GetHackerInfoRequest>) -> Result<Json<GetHackerInfoResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct HackerQuery {
+        username: String,
+        #[sqlx(flatten)]
+        permissions: UserPermissions,
+    }
+
+    user.require(Permission::Hack)?;
+
+    let user_personal_number = parse_personal_number(&payload.personal_number);
+    if user_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Hacker personal code parsing failed"));
+    }
+
+    let hacker_query = sqlx::query_as::<_, HackerQuery> (
+    r#"
+        SELECT username, user_permissions(id) AS permissions FROM users
+        WHERE personal_number = $1
+    "#)
+    .bind(user_personal_number)
+    .fetch_optional(&state.db_pool)
+    .timed("get_hacker_info")
+    .await;
+
+    match hacker_query {
+        Ok(Some(hacker)) => {
+            if hacker.permissions.contains(Permission::Hack) {
+                Ok(Json(GetHackerInfoResponse::success(HackerInfo{ username: hacker.username, can_hack: true })))
+            }
+            else {
+                Err(ServerError::forbidden("NO_HACKING_PRIVILEGE", "No hacking privilege"))
+            }
+        },
+        Ok(None) => Err(ServerError::not_found("USER_NOT_FOUND", "User not found")),
+        Err(error) => {
+            error!("Getting hacker failed for personal code: {}, error: {}", payload.personal_number, error);
+            Err(ServerError::internal("User not found. Internal server error!"))
+        }
+    }
+}
+
+/// Every user who can not hack.
+#[utoipa::path(get, path = "/get_hackable_users", tag = "hacking", security(("token" = [])), responses(
+    (status = 200, body = GetHackableUsersResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_HACKING_PRIVILEGE", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_hackable_users(State(state): State<Arc<ServerState>>, user: AuthenticatedUser) -> Result<Json<GetHackableUsersResponse>, ServerError> {
+    #[derive(sqlx::FromRow)]
+    struct HackableUserQuery {
+        #[sqlx(flatten)]
+        user: HackableUser,
+        #[sqlx(flatten)]
+        permissions: UserPermissions,
+    }
+
+    user.require(Permission::Hack)?;
+
+    let hackable_users_query = sqlx::query_as::<_, HackableUserQuery> (
+    r#"
+        SELECT username, cyber_defence_level, personal_number, user_permissions(id) AS permissions FROM users
+    "#)
+    .fetch_all(&state.db_pool)
+    .timed("get_hackable_users")
+    .await;
+    
+    match hackable_users_query {
+        Ok(users) => {
+            let users = users.into_iter()
+                .filter(|user| !user.permissions.contains(Permission::Hack))
+                .map(|user| user.user)
+                .collect();
+            Ok(Json(GetHackableUsersResponse{ response_status: ResponseStatus::success(), users }))
+        },
+        Err(error) => {
+            error!("Getting hackable users failed, error: {}", error);
+            Err(ServerError::internal("Hackable users not found. Inertnal server error!"))
+        }
+    }
+}
+
+/// Hacks the logged in hacker can still try on the victim, based on the logged hack results.
+#[utoipa::path(post, path = "/get_available_hack_types", tag = "hacking", security(("token" = [])), request_body = AvailableHackTypesRequest, responses(
+    (status = 200, body = AvailableHackTypesResponse),
+    (status = 400, description = "INVALID_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_HACKING_PRIVILEGE, NOT_OWN_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_available_hack_types(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<AvailableHackTypesRequest>) -> Result<Json<AvailableHackTypesResponse>, ServerError> {
+    #[derive(Debug, sqlx::FromRow)]
+    struct HackQueryResult {
+        hack_type: sqlx::types::Json<HackType>,
+        successful: bool,
+    }
+
+    user.require(Permission::Hack)?;
+
+    let hacker_personal_number = parse_personal_number(&payload.hacker_personal_number);
+    if hacker_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Hacker personal code is not valid. Cannot gather available hacks."));
+    }
+
+    check_own_personal_number(&state, &user, hacker_personal_number).await?;
+
+    let victim_personal_number = parse_personal_number(&payload.victim_personal_number);
+    if victim_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Victim personal code is not valid. Cannot gather available hacks."));
+    }
+
+    let query_result = sqlx::query_as::<_, HackQueryResult>(
+    r#"
+        SELECT
+            hl.hack_type,
+            hl.successful
+        FROM hack_log hl
+        INNER JOIN users victim ON hl.victim_id = victim.id
+        WHERE hl.hacker_id = $1
+            AND victim.personal_number = $2;
+    "#
+    )
+    .bind(user.user_id)
+    .bind(victim_personal_number)
+    .fetch_all(&state.db_pool)
+    .timed("get_available_hack_types")
+    .await;
+
+    let hacks = match query_result {
+        Ok(hacks) => hacks,
+        Err(error) => {
+            error!("Failed to query hacker available hacks for: {}, error: {}", hacker_personal_number, error);
+            return Err(ServerError::internal("Querying available hacks failed. Internal server error."));
+        }
+    };
+
+    let mut can_hack_bank: bool = true;
+    let mut can_hack_chats: bool = false;
+    let mut available_hacks = vec![HackType::PersonalData];
+
+    for hack in hacks {
+        match hack.hack_type.0 {
+            HackType::BankTransaction(_)
+                if hack.successful => {
+                    can_hack_bank = false;
+                },
+            HackType::ChatAccess
+                if hack.successful => {
+                    can_hack_chats = true;
+                },
+            _ => {}
+        }
+    }
+
+    if can_hack_bank {
+        available_hacks.push(HackType::BankTransaction(0));
+    }
+
+    if can_hack_chats {
+        available_hacks.push(HackType::ChatData(0));
+    }
+    else {
+        available_hacks.push(HackType::ChatAccess);
+    }
+
+    Ok(Json(AvailableHackTypesResponse{ response_status: ResponseStatus::success(), available_hacks }))
+}
+
+/// A new token of the victim for the logged in hacker, valid for `hack_token_lifetime_minutes`. `null` when the
+/// victim is not logged in. The victim's own tokens are never handed out, the hack session records the hacker.
+#[utoipa::path(post, path = "/get_hack_token", tag = "hacking", security(("token" = [])), request_body = HackTokenRequest, responses(
+    (status = 200, body = HackTokenResponse),
+    (status = 400, description = "INVALID_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_HACKING_PRIVILEGE", body = ErrorResponse),
+    (status = 404, description = "USER_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_hack_token(State(state): State<Arc<ServerState>>, hacker: AuthenticatedUser, Json(payload): Json<HackTokenRequest>) -> Result<Json<HackTokenResponse>, ServerError> {
+    hacker.require(Permission::Hack)?;
+
+    #[derive(sqlx::FromRow)]
+    struct VictimQuery {
+        id: i32,
+        #[sqlx(flatten)]
+        permissions: UserPermissions,
+        is_logged_in: bool,
+    }
+
+    let victim_personal_number = parse_personal_number(&payload.victim_personal_number);
+    if victim_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Victim personal code parsing failed."));
+    }
+
+    let victim_query = sqlx::query_as::<_, VictimQuery>(
+    r#"
+        SELECT id, user_permissions(id) AS permissions,
+            user_token_hash IS NOT NULL OR EXISTS (
+                SELECT 1 FROM sessions
+                WHERE sessions.user_id = users.id AND NOT is_hack AND impersonated_by IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            ) AS is_logged_in
+        FROM users
+        WHERE personal_number = $1
+    "#
+    )
+    .bind(victim_personal_number)
+    .fetch_optional(&state.db_pool)
+    .timed("get_hack_token")
+    .await;
+    
+    let victim = match victim_query {
+        Ok(Some(victim)) => victim,
+        Ok(None) => {
+            return Err(ServerError::not_found("USER_NOT_FOUND", "Hack token was not found. Personal code is not assigned to a user."));
+        },
+        Err(error) => {
+            error!("Getting hack token failed for code: {}, error: {}", victim_personal_number, error);
+            return Err(ServerError::internal("Hack token not found. Internal server error!"));
+        }
+    };
+
+    if !victim.is_logged_in {
+        return Ok(Json(HackTokenResponse{ response_status: ResponseStatus::success(), token: None }));
+    }
+
+    let issued_token = state.session_tokens.start_hack_session(hacker.user_id, victim.id, victim.permissions).await?;
+    info!("Hack session {} of user_id {} started for hacker user_id {}", issued_token.token_id, victim.id, hacker.user_id);
+    Ok(Json(HackTokenResponse{ response_status: ResponseStatus::success(), token: Some(issued_token.token) }))
+}
+
+/// Records the result of a hack by the logged in hacker.
+#[utoipa::path(post, path = "/log_hack_state_result", tag = "hacking", security(("token" = [])), request_body = HackStateResultRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 400, description = "INVALID_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_HACKING_PRIVILEGE, NOT_OWN_PERSONAL_NUMBER", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn log_hack_state_result(State(state): State<Arc<ServerState>>, user: AuthenticatedUser, Json(payload): Json<HackStateResultRequest>) -> Result<Json<HackStateResultResponse>, ServerError> {
+    user.require(Permission::Hack)?;
+
+    let hacker_personal_number = parse_personal_number(&payload.hacker_personal_number);
+    if hacker_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Hacker personal code is not valid. Cannot log hack."));
+    }
+
+    check_own_personal_number(&state, &user, hacker_personal_number).await?;
+
+    let victim_personal_number = parse_personal_number(&payload.victim_personal_number);
+    if victim_personal_number < 1 {
+        return Err(ServerError::bad_request("INVALID_PERSONAL_NUMBER", "Victim personal code is not valid. Cannot log hack."));
+    }
+
+    let insertion_result = sqlx::query(
+    r#"
+        INSERT INTO hack_log(
+            hacker_id,
+            victim_id,
+            hack_type,
+            successful,
+            timestamp
+        )
+        SELECT
+            $3 AS hacker_id,
+            victim_user.id AS victim_id,
+            $1,
+            $2,
+            NOW()
+        FROM users AS victim_user
+        WHERE victim_user.personal_number = $4
+        RETURNING id;
+    "#
+    )
+    .bind(sqlx::types::Json(&payload.hack_type))
+    .bind(payload.hack_successful)
+    .bind(user.user_id)
+    .bind(victim_personal_number)
+    .fetch_one(&state.db_pool)
+    .timed("log_hack_state_result")
+    .await;
+    
+    match insertion_result {
+        Ok(_) => {
+            server_metrics::record_hack_attempt(payload.hack_successful);
+            Ok(Json(HackStateResultResponse::success()))
+        },
+        Err(error) => {
+            error!("Hack log insertion failed. Error: {}", error);
+            Err(ServerError::internal("Hack log caused a internal server error!"))
+        }
+    }
+}
+
+// The hacker is always the logged in user, the personal number in the request only has to match it.
+async fn check_own_personal_number(state: &ServerState, user: &AuthenticatedUser, hacker_personal_number: i32) -> Result<(), ServerError> {
+    let own_personal_number_query = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE id = $1 AND personal_number = $2)"
+    )
+    .bind(user.user_id)
+    .bind(hacker_personal_number)
+    .fetch_one(&state.db_pool)
+    .timed("hacker_personal_number")
+    .await;
+
+    match own_personal_number_query {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ServerError::forbidden("NOT_OWN_PERSONAL_NUMBER", "The hacker personal code does not belong to the logged in user.")),
+        Err(error) => {
+            error!("Checking hacker personal code failed for user id: {}, error: {}", user.user_id, error);
+            Err(ServerError::internal("Checking hacker personal code failed. Internal server error!"))
+        }
+    }
+}
+
+fn parse_personal_number(personal_number: &String) -> i32 {
+    match personal_number.parse::<i32>() {
+        Ok(code) => { 
+            code
+        },
+        Err(error) => {
+            error!("Hacker code failed to parse: {}, error: {}", personal_number, error);
+            0
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use shared_server_lib::{server_configurator::{ServerConfiguration, ServerType, LauncherMode}, server_logging, server_health::{self, HealthCheck}, server_shutdown, server_database, server_listener, server_cors, server_openapi, common};
+
+use axum::Router;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+const DEFAULT_LOG_FILE: &str = "cyber_crush_launcher.log";
+
+#[tokio::main]
+async fn main() {
+    let mut server_configuration = match ServerConfiguration::load() {
+        Ok(configuration) => configuration,
+        Err(error) => {
+            eprintln!("Error: Reading server configuration failed: {}", error);
+            std::process::exit(1);
+        }
+    };
+    // The game state TUI owns the terminal, so logs can not go to stdout.
+    if server_configuration.launcher_game_state_tui {
+        server_configuration.log_file.get_or_insert_with(|| DEFAULT_LOG_FILE.into());
+    }
+    server_logging::init_logging(&server_configuration);
+
+    let db_pool = server_database::connect_to_database(&server_configuration, ServerType::Launcher).await;
+    let chat_state = cyber_crush_chat_server::create_server_state(db_pool.clone(), &server_configuration);
+    let game_state = cyber_crush_game_state_server::create_game_state();
+
+    let servers = [
+        (ServerType::Authentication, cyber_crush_authentication_server::create_router(db_pool.clone(), &server_configuration)),
+        (ServerType::Data, cyber_crush_data_server::create_router(db_pool.clone(), &server_configuration)),
+        (ServerType::Bank, cyber_crush_bank_server::create_router(db_pool.clone(), &server_configuration)),
+        (ServerType::Chat, cyber_crush_chat_server::create_router(chat_state.clone(), &server_configuration)),
+        (ServerType::News, cyber_crush_news_server::create_router(db_pool.clone(), &server_configuration)),
+        (ServerType::GameState, cyber_crush_game_state_server::create_router(game_state.clone(), db_pool.clone(), &server_configuration)),
+        (ServerType::Hacking, cyber_crush_hacking_server::create_router(db_pool.clone(), &server_configuration)),
+    ];
+
+    let listeners = match server_configuration.launcher_mode {
+        LauncherMode::SinglePort => {
+            let app = servers.into_iter().fold(Router::new(), |app, (server_type, router)| {
+                app.nest(&format!("/{}", server_type.name()), router)
+            });
+            let app = server_health::apply_health_routes(app, HealthCheck::new("launcher", env!("CARGO_PKG_VERSION"), Some(db_pool.clone())));
+            let app = server_openapi::apply_openapi_route(app, &backend_openapi());
+
+            vec![(ServerType::Launcher, app)]
+        },
+        LauncherMode::ServerPorts => servers.into_iter().collect(),
+    };
+
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+
+    let shutdown_sender_signal = shutdown_sender.clone();
+    tokio::spawn(async move {
+        server_shutdown::shutdown_signal().await;
+        shutdown_sender_signal.send_replace(true);
+    });
+
+    let server_configuration = Arc::new(server_configuration);
+    let mut running_servers = JoinSet::new();
+    for (server_type, app) in listeners {
+        let socket_addr = server_configuration.get_socket_addr(server_type);
+        info!("Launcher serving {} at: {}", server_type.name(), socket_addr);
+
+        let app = server_logging::apply_request_tracing(app);
+        let app = common::apply_response_compatibility_mode(app, server_configuration.response_compatibility_mode);
+        let app = server_cors::apply_cors(app, &server_configuration.cors_allowed_origins);
+
+        let mut shutdown_receiver_server = shutdown_receiver.clone();
+        let shutdown_signal = async move {
+            let _ = shutdown_receiver_server.wait_for(|is_shutting_down| *is_shutting_down).await;
+        };
+
+        let server_configuration = server_configuration.clone();
+        running_servers.spawn(async move {
+            server_listener::serve(socket_addr, app, &server_configuration, shutdown_signal).await
+        });
+    }
+
+    let mut shutdown_receiver_chat = shutdown_receiver.clone();
+    running_servers.spawn(async move {
+        let _ = shutdown_receiver_chat.wait_for(|is_shutting_down| *is_shutting_down).await;
+        cyber_crush_chat_server::close_all_connections(&chat_state).await;
+        Ok(())
+    });
+
+    let tui_handle = if server_configuration.launcher_game_state_tui {
+        Some(tokio::spawn(cyber_crush_game_state_server::run_tui(game_state, shutdown_sender)))
+    }
+    else {
+        None
+    };
+
+    while let Some(server_result) = running_servers.join_next().await {
+        match server_result {
+            Ok(Ok(())) => {},
+            Ok(Err(error)) => error!("Launcher server failed: {}", error),
+            Err(error) => error!("Launcher server task failed: {}", error),
+        }
+    }
+
+    if let Some(tui_handle) = tui_handle && let Err(error) = tui_handle.await {
+        error!("Game state TUI task failed: {}", error);
+    }
+
+    info!("Server stopped");
+}
+
+// Every server document under the prefix it has in `single_port` mode.
+fn backend_openapi() -> utoipa::openapi::OpenApi {
+    let server_apis = [
+        (ServerType::Authentication, cyber_crush_authentication_server::openapi()),
+        (ServerType::Data, cyber_crush_data_server::openapi()),
+        (ServerType::Bank, cyber_crush_bank_server::openapi()),
+        (ServerType::Chat, cyber_crush_chat_server::openapi()),
+        (ServerType::News, cyber_crush_news_server::openapi()),
+        (ServerType::GameState, cyber_crush_game_state_server::openapi()),
+        (ServerType::Hacking, cyber_crush_hacking_server::openapi()),
+    ];
+
+    let server_apis = server_apis.into_iter().map(|(server_type, api)| (server_type.name(), api)).collect();
+    server_openapi::nested_openapi("CyberCrush backend", env!("CARGO_PKG_VERSION"), server_apis)
+}
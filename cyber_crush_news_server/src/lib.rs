@@ -0,0 +1,189 @@
+use shared_server_lib::{server_configurator::ServerConfiguration, server_health::{self, HealthCheck}, server_metrics::{self, TimedQuery}, server_openapi, common::{self, LegacyFailureBody, ErrorResponse, ResponseStatus, ServerError}, session_tokens::SessionTokens, authentication::{self, AuthenticatedUser, AuthenticationState}, input_validation::InputLimits, server_rate_limit::{RateLimitLayer, RateLimitGroup}};
+
+use axum::{
+    extract::{Json, State},
+    routing::{get, post},
+    Router,
+};
+
+use std::sync::Arc;
+use serde_json::json;
+use sqlx::{ PgPool, Postgres, Transaction };
+use tracing::error;
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::{authentication::Permission, news::{NewsArticleEntry, GetNewsFeedResponse, PostNewsArticleRequest, PostNewsArticleResponse, DeleteNewsArticleRequest, DeleteNewsArticleResponse}};
+
+#[derive(OpenApi)]
+#[openapi(paths(hello, get_news_feed, post_news_article, delete_news_article))]
+struct NewsApi;
+
+#[derive(Debug)]
+struct ServerState {
+    db_pool: PgPool, //This is thread safe
+    session_tokens: SessionTokens,
+    input_limits: InputLimits,
+}
+
+impl AuthenticationState for ServerState {
+    fn session_tokens(&self) -> &SessionTokens {
+        &self.session_tokens
+    }
+}
+
+/// OpenAPI document of the news server, also served at `/openapi.json`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    server_openapi::server_openapi("news", env!("CARGO_PKG_VERSION"), NewsApi::openapi())
+}
+
+pub fn create_router(db_pool: PgPool, server_configuration: &ServerConfiguration) -> Router {
+    let server_state = Arc::new(ServerState{ session_tokens: SessionTokens::new(&server_configuration.session_tokens, db_pool.clone()), db_pool, input_limits: server_configuration.input_limits.clone() });
+
+    let app = Router::new()
+        .route("/hello", get(hello))
+        .route("/get_news_feed", get(get_news_feed))
+        .route("/post_news_article", post(post_news_article))
+        .route("/delete_news_article", post(delete_news_article))
+        .route_layer(RateLimitLayer::from_configuration(server_configuration, RateLimitGroup::Default))
+        .with_state(server_state.clone());
+    let app = authentication::apply_body_token_fallback(app);
+    let app = server_health::apply_health_routes(app, HealthCheck::new("news", env!("CARGO_PKG_VERSION"), Some(server_state.db_pool.clone())));
+    let app = server_openapi::apply_openapi_route(app, &openapi());
+    let app = server_metrics::apply_metrics(app, "news", Some(server_state.db_pool.clone()));
+    common::apply_legacy_failure_bodies(app, legacy_failure_bodies())
+}
+
+fn legacy_failure_bodies() -> Vec<(&'static str, LegacyFailureBody)> {
+    vec![
+        ("/get_news_feed", LegacyFailureBody::Fields(json!({ "articles": [] }))),
+        ("/post_news_article", LegacyFailureBody::Fields(json!({ "post_id": -1 }))),
+        ("/delete_news_article", LegacyFailureBody::Status),
+    ]
+}
+
+#[utoipa::path(get, path = "/hello", tag = "news", responses((status = 200, body = String)))]
+async fn hello() -> &'static str {
+    "Hello, cyber crush news server!"
+}
+
+//TODO add a timestamp paramteter that returns atricles written after that timestamp
+/// The 75 newest articles, newest first.
+#[utoipa::path(get, path = "/get_news_feed", tag = "news", responses(
+    (status = 200, body = GetNewsFeedResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn get_news_feed(State(state): State<Arc<ServerState>>) -> Result<Json<GetNewsFeedResponse>, ServerError> {
+    let news_articles_query = sqlx::query_as::<_, NewsArticleEntry>(
+    r#"
+        SELECT
+            na.id,
+            pub.username as author,
+            na.title,
+            na.content,
+            na.timestamp
+        FROM news_articles na
+        JOIN users pub ON na.user_id = pub.id
+        ORDER BY na.timestamp DESC
+        LIMIT 75;
+    "#)
+    .fetch_all(&state.db_pool)
+    .timed("get_news_feed")
+    .await;
+
+    match news_articles_query {
+        Ok(articles) => Ok(Json(GetNewsFeedResponse{ response_status:ResponseStatus::success(), articles })),
+        Err(error) => {
+            error!("Getting news feed failed. Error: {}", error);
+            Err(ServerError::internal("News feed failed to query. Server error!"))
+        }
+    }
+}
+
+/// Publishes an article, needs the `publish_news` permission.
+#[utoipa::path(post, path = "/post_news_article", tag = "news", security(("token" = [])), request_body = PostNewsArticleRequest, responses(
+    (status = 200, body = PostNewsArticleResponse),
+    (status = 400, description = "INVALID_INPUT", body = ErrorResponse),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 403, description = "NO_PUBLISHING_PRIVILEGE", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn post_news_article(State(state): State<Arc<ServerState>>, publisher: AuthenticatedUser, Json(payload): Json<PostNewsArticleRequest>) -> Result<Json<PostNewsArticleResponse>, ServerError> {
+    state.input_limits.validate_news_title(&payload.title)?;
+
+    publisher.require(Permission::PublishNews)?;
+
+    let mut transaction: Transaction<'_, Postgres> = match state.db_pool.begin().await {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            error!("Posting news failed while starting the transaction. Error: {}", error); 
+            return Err(ServerError::internal("Posting news internal server Error: 1!"));
+        }
+    };
+
+
+    let create_news_article_query = sqlx::query_scalar::<_, i32>(
+    r#"
+        INSERT INTO news_articles
+        (user_id, title, content, timestamp)
+        VALUES ($1, $2, $3, NOW())
+        RETURNING id
+    "#)
+    .bind(publisher.user_id)
+    .bind(payload.title)
+    .bind(payload.content)
+    .fetch_one(&mut *transaction)
+    .timed("post_news_article")
+    .await;
+
+    let post_id = match create_news_article_query {
+        Ok(id) => id,
+        Err(error) => {
+            error!("Posting news article failed while inserting. Error: {}", error);
+            return Err(ServerError::internal("Posting news internal server Error: 2!"));
+        }
+    };
+
+    if let Err(error) = transaction.commit().await {
+        error!("Posting news failed while commiting transaction. Error: {}", error);
+        return Err(ServerError::internal("Posting news internal server Error: 3!"));
+    }
+
+    Ok(Json(PostNewsArticleResponse{ response_status: ResponseStatus::success(), post_id }))
+}
+
+/// Deletes an article written by the logged in user.
+#[utoipa::path(post, path = "/delete_news_article", tag = "news", security(("token" = [])), request_body = DeleteNewsArticleRequest, responses(
+    (status = 200, body = ResponseStatus),
+    (status = 401, description = "INVALID_TOKEN", body = ErrorResponse),
+    (status = 404, description = "ARTICLE_NOT_FOUND", body = ErrorResponse),
+    (status = 429, description = "RATE_LIMITED", body = ErrorResponse),
+    (status = 500, description = "INTERNAL_ERROR", body = ErrorResponse),
+))]
+async fn delete_news_article(State(state): State<Arc<ServerState>>, publisher: AuthenticatedUser, Json(payload): Json<DeleteNewsArticleRequest>) -> Result<Json<DeleteNewsArticleResponse>, ServerError> {
+    let delete_post_query = sqlx::query(
+    r#"
+        DELETE FROM news_articles
+        WHERE id = $1 and user_id = $2
+    "#)
+    .bind(payload.post_id)
+    .bind(publisher.user_id)
+    .execute(&state.db_pool)
+    .timed("delete_news_article")
+    .await;
+
+    match delete_post_query {
+        Ok(result) => {
+            if result.rows_affected() == 0 {
+                return Err(ServerError::not_found("ARTICLE_NOT_FOUND", "No article was deleted"));
+            } 
+        }
+        Err(error) => {
+            error!("Deleting news article failed while querying for delete for user id: {}, Error: {}", publisher.user_id, error);
+            return Err(ServerError::internal("Deleting news internal server error: 1!"));
+        }
+    }
+
+    Ok(Json(DeleteNewsArticleResponse::success()))
+}
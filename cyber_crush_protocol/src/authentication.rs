@@ -0,0 +1,250 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+    /// Shown in the session list, e.g. "Anna's phone".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LoginResponse {
+    pub response_status: ResponseStatus,
+    pub token: String,
+    pub token_expires_at: DateTime<Utc>,
+    pub session_id: String,
+}
+
+pub type ValidateTokenResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub device_label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The session of the token that made the request.
+    pub is_current: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetSessionsResponse {
+    pub response_status: ResponseStatus,
+    pub sessions: Vec<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevokeSessionRequest {
+    pub session_id: String,
+}
+
+pub type RevokeSessionResponse = ResponseStatus;
+
+pub type LogoutResponse = ResponseStatus;
+
+pub type LogoutAllResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+pub type ChangePasswordResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkCharacterRequest {
+    pub username: String,
+    pub password: String,
+}
+
+pub type LinkCharacterResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SwitchCharacterRequest {
+    pub username: String,
+}
+
+/// A token of the same session for the character switched to. Tokens of the other characters stay valid.
+pub type SwitchCharacterResponse = LoginResponse;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct LinkedCharacter {
+    pub username: String,
+    /// The character that logged in and created the session.
+    pub is_owner: bool,
+    /// The character of the token that made the request.
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetLinkedCharactersResponse {
+    pub response_status: ResponseStatus,
+    pub characters: Vec<LinkedCharacter>,
+}
+
+/// What a role allows. Every server checks the permissions of the roles of the token's user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    PublishNews,
+    Hack,
+    MintFunds,
+    ModerateChat,
+    ManageUsers,
+    ControlGameState,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 6] = [Permission::PublishNews, Permission::Hack, Permission::MintFunds, Permission::ModerateChat, Permission::ManageUsers, Permission::ControlGameState];
+
+    /// Name in the `role_permissions` table, the same as in JSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::PublishNews => "publish_news",
+            Permission::Hack => "hack",
+            Permission::MintFunds => "mint_funds",
+            Permission::ModerateChat => "moderate_chat",
+            Permission::ManageUsers => "manage_users",
+            Permission::ControlGameState => "control_game_state",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Permission> {
+        Permission::ALL.into_iter().find(|permission| permission.name() == name)
+    }
+}
+
+/// A new user, the format of the entries of `utils/database/example_data/example_users.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    /// Same as the `journalist` role, kept for the users files.
+    #[serde(default)]
+    pub can_publish_posts: bool,
+    pub cyber_defence_level: i32,
+    /// Same as the `hacker` role, kept for the users files.
+    #[serde(default)]
+    pub can_hack: bool,
+    /// Names of the roles of the user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    /// Any JSON object, shown to the player by the data server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct CreatedUser {
+    pub username: String,
+    /// Random 4 digit number the player uses in game, e.g. as the hacking target.
+    pub personal_number: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateUserResponse {
+    pub response_status: ResponseStatus,
+    pub user: CreatedUser,
+}
+
+/// The whole `example_users.json` array.
+pub type ImportUsersRequest = Vec<CreateUserRequest>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportUsersResponse {
+    pub response_status: ResponseStatus,
+    pub users: Vec<CreatedUser>,
+}
+
+/// Fields left out keep their value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateUserRequest {
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cyber_defence_level: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<serde_json::Value>,
+    /// Replaces every role of the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+}
+
+pub type UpdateUserResponse = ResponseStatus;
+
+/// Body of `/admin/disable_user`, `/admin/enable_user`, `/admin/delete_user` and `/admin/impersonate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ManagedUserRequest {
+    pub username: String,
+}
+
+pub type DisableUserResponse = ResponseStatus;
+
+pub type EnableUserResponse = ResponseStatus;
+
+pub type DeleteUserResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResetPasswordRequest {
+    pub username: String,
+    pub new_password: String,
+}
+
+pub type ResetPasswordResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Role {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetRolesResponse {
+    pub response_status: ResponseStatus,
+    pub roles: Vec<Role>,
+}
+
+/// Creates the role, or replaces the description and permissions of an existing one.
+pub type SetRoleRequest = Role;
+
+pub type SetRoleResponse = ResponseStatus;
+
+/// A short-lived token to act as the user. Every request made with it is recorded with the game master's user id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImpersonateResponse {
+    pub response_status: ResponseStatus,
+    pub token: String,
+    pub token_expires_at: DateTime<Utc>,
+    pub session_id: String,
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetUserFundsResponse {
+    pub response_status: ResponseStatus,
+    pub funds: i32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct TransactionEntry {
+    pub sender_username: String,
+    pub receiver_username: String,
+    pub message: String,
+    pub amount: i32,
+    pub time_stamp: chrono::NaiveDateTime
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetUserTransactionHistoryResponse {
+    pub response_status: ResponseStatus,
+    pub transactions: Vec<TransactionEntry>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TransferFundsRequest {
+    pub receiver_username: String,
+    pub message: String,
+    pub amount: i32
+}
+
+pub type TransferFundsResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MintFundsRequest {
+    pub receiver_username: String,
+    pub amount: i32
+}
+
+/// `funds` is the balance of the receiver after the minting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MintFundsResponse {
+    pub response_status: ResponseStatus,
+    pub funds: i32
+}
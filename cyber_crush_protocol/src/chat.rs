@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct DirectChat {
+    pub chat_id: i32,
+    pub chat_partner: String,
+    pub last_message: Option<String>,
+    pub last_message_time_stamp: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct GroupChat {
+    pub chat_id: i32,
+    pub title: String,
+    pub last_message: Option<String>,
+    pub last_message_time_stamp: Option<chrono::NaiveDateTime>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetUserChatsResponse {
+    pub response_status: ResponseStatus,
+    pub direct_chats: Option<Vec<DirectChat>>,
+    pub group_chats: Option<Vec<GroupChat>>
+}
+
+/// Leave `history_last_index` empty for the newest messages, or pass the lowest `in_chat_index` received for older ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetChatHistoryRequest {
+    pub chat_id: i32,
+    pub history_last_index: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct ChatMessage {
+    pub sender: String,
+    pub in_chat_index: i32,
+    pub message: String,
+    pub time_stamp: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetChatHistoryResponse {
+    pub response_status: ResponseStatus,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl GetChatHistoryResponse {
+    pub fn success(messages: Vec<ChatMessage>) -> GetChatHistoryResponse {
+        GetChatHistoryResponse{
+            response_status: ResponseStatus::success(),
+            messages }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetChatMetaDataRequest {
+    pub chat_id: i32,
+}
+
+//TODO rename to Metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DirectChatMetaData {
+    pub username_a: String,
+    pub username_b: String,
+}
+
+//TODO rename to Metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GroupChatMetaData {
+    pub admin_username: String,
+    pub title: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ChatMetaData {
+    Direct(DirectChatMetaData),
+    Group(GroupChatMetaData),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetChatMetaDataResponse {
+    pub response_status: ResponseStatus,
+    pub metadata: Option<ChatMetaData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "action", content = "username")]
+pub enum GroupMemberUpdate {
+    AddMember(String),
+    DeleteMember(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateGroupChatMemberRequest {
+    pub chat_id: i32,
+    pub update: GroupMemberUpdate,
+}
+
+pub type UpdateGroupChatMemberResponse = ResponseStatus;
+
+/// Muted members can not send messages to the chat for `minutes`. Only members of group chats can be removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "action")]
+pub enum ChatModeration {
+    DeleteMessage { in_chat_index: i32 },
+    MuteMember { username: String, minutes: u32 },
+    UnmuteMember { username: String },
+    RemoveMember { username: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModerateChatRequest {
+    pub chat_id: i32,
+    pub moderation: ChatModeration,
+}
+
+pub type ModerateChatResponse = ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNewDirectChatRequest {
+    pub partner_username: String,
+    pub creation_message: String,
+}
+
+/// When a direct chat with the partner exists already, `success` is false and `chat_id` is the existing chat.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNewDirectChatResponse {
+    pub response_status: ResponseStatus,
+    pub chat_id: Option<i32>,
+}
+
+impl CreateNewDirectChatResponse {
+    pub fn success(chat_id: i32) -> CreateNewDirectChatResponse {
+        CreateNewDirectChatResponse{
+            response_status: ResponseStatus::success(),
+            chat_id: Some(chat_id) }
+    }
+
+    pub fn chat_exists(chat_id: i32) -> CreateNewDirectChatResponse {
+        CreateNewDirectChatResponse{
+            response_status: ResponseStatus::fail("Direct chat already exsits!".into()),
+            chat_id: Some(chat_id) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNewGroupChatRequest {
+    pub title: String,
+    pub creation_message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateNewGroupChatResponse {
+    pub response_status: ResponseStatus,
+    pub chat_id: Option<i32>,
+}
+
+/// Text frames sent by the client over `/realtime_chat`, starting with `init`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChatClientMessage {
+    Init { token: String, chat_id: i32 },
+    Msg { token: String, message: String },
+    Exit { token: String },
+}
+
+/// Text frames sent by the server over `/realtime_chat`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ChatResponse {
+    Info { text: String },
+    Error { text: String },
+    ChatMessage { chat_id: i32, in_chat_index: i32, sender: String, message: String, time_stamp: String },
+}
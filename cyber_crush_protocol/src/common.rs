@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResponseStatus {
+    pub success: bool,
+    pub status_message: String,
+}
+
+impl ResponseStatus {
+    pub fn fail(reason: String) -> ResponseStatus {
+        ResponseStatus{ success: false, status_message: reason }
+    }
+
+    pub fn success() -> ResponseStatus {
+        ResponseStatus{ success: true, status_message: "Success".into() }
+    }
+}
+
+/// Body of every failed request. `error_code` is a stable SCREAMING_SNAKE_CASE identifier clients can match on,
+/// the message in `response_status` is for humans and may change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    pub error_code: String,
+    pub response_status: ResponseStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_seconds: Option<u64>,
+}
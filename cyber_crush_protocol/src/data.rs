@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetAllUsernamesResponse {
+    pub response_status: ResponseStatus,
+    pub usernames: Vec<String>
+}
+
+/// `extra_data` is a JSON document encoded as a string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetUserDataResponse {
+    pub response_status: ResponseStatus,
+    pub username: String,
+    pub personal_number: String,
+    pub can_publish_posts: bool,
+    pub cyber_defence_level: i32,
+    pub extra_data: String,
+}
+
+impl GetUserDataResponse {
+    pub fn success(username: String, personal_number: String, can_publish_posts: bool, cyber_defence_level: i32, extra_data: String) -> GetUserDataResponse {
+        GetUserDataResponse{ response_status: ResponseStatus::success(),
+                            username,
+                            can_publish_posts,
+                            cyber_defence_level,
+                            personal_number,
+                            extra_data }
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GameStateResponse {
+    pub is_game_online: bool,
+    pub info_panel_text: String,
+}
+
+/// `info_panel_text` replaces the text shown while the game is offline, it is kept when missing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetGameStateRequest {
+    pub is_game_online: bool,
+    pub info_panel_text: Option<String>,
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetHackerInfoRequest {
+    pub personal_number: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct HackerInfo {
+    pub username: String,
+    pub can_hack: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetHackerInfoResponse {
+    pub response_status: ResponseStatus,
+    pub hacker_info: HackerInfo,
+}
+
+impl GetHackerInfoResponse {
+    pub fn success(hacker_info: HackerInfo) -> GetHackerInfoResponse {
+        GetHackerInfoResponse{
+            response_status: ResponseStatus::success(),
+            hacker_info }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct HackableUser {
+    pub username: String,
+    pub cyber_defence_level: i32,
+    pub personal_number: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetHackableUsersResponse {
+    pub response_status: ResponseStatus,
+    pub users: Vec::<HackableUser>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum HackType {
+    BankTransaction(i32),
+    ChatAccess,
+    ChatData(i32),
+    PersonalData
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AvailableHackTypesRequest {
+    pub hacker_personal_number: String,
+    pub victim_personal_number: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AvailableHackTypesResponse {
+    pub response_status: ResponseStatus,
+    pub available_hacks: Vec::<HackType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HackTokenRequest {
+    pub victim_personal_number: String,
+}
+
+/// `token` is a short-lived token of the victim for the hacker, `None` while the victim is not logged in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HackTokenResponse {
+    pub response_status: ResponseStatus,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HackStateResultRequest {
+    pub hacker_personal_number: String,
+    pub victim_personal_number: String,
+    pub hack_type: HackType,
+    pub hack_successful: bool
+}
+
+pub type HackStateResultResponse = ResponseStatus;
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LivenessResponse {
+    pub status: String,
+    pub server: String,
+    pub version: String,
+    pub uptime_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DatabaseHealth {
+    pub reachable: bool,
+    pub ping_milliseconds: Option<u128>,
+    pub acquire_milliseconds: Option<u128>,
+    pub pool_size: u32,
+    pub idle_connections: usize,
+    pub busy_connections: usize,
+    pub max_connections: u32,
+    pub saturation: f64,
+    pub acquire_timeout_seconds: u64,
+    pub idle_timeout_seconds: Option<u64>,
+}
+
+/// `status` is `ready` or `not_ready`. Servers without a database have no `database`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadinessResponse {
+    pub status: String,
+    pub server: String,
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub database: Option<DatabaseHealth>,
+}
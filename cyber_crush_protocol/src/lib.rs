@@ -0,0 +1,14 @@
+//! Request and response bodies of every CyberCrush server, shared by the servers and `cyber_crush_client`.
+//! One module per server, plus the bodies every server answers with.
+
+pub mod common;
+pub mod health;
+pub mod authentication;
+pub mod data;
+pub mod bank;
+pub mod chat;
+pub mod news;
+pub mod game_state;
+pub mod hacking;
+
+pub use common::{ErrorResponse, ResponseStatus};
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ResponseStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "database", derive(sqlx::FromRow))]
+pub struct NewsArticleEntry {
+    pub id: i32,
+    pub author: String,
+    pub title: String,
+    pub content: String,
+    pub timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetNewsFeedResponse {
+    pub response_status: ResponseStatus,
+    pub articles: Vec<NewsArticleEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PostNewsArticleRequest {
+    pub title: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PostNewsArticleResponse {
+    pub response_status: ResponseStatus,
+    pub post_id: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteNewsArticleRequest {
+    pub post_id: i32,
+}
+
+pub type DeleteNewsArticleResponse = ResponseStatus;
@@ -0,0 +1,87 @@
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    user_token TEXT,
+    personal_number INT NOT NULL UNIQUE,
+    can_publish_posts BOOLEAN NOT NULL,
+    cyber_defence_level INT NOT NULL,
+    can_hack BOOLEAN NOT NULL,
+    extra_data JSONB
+);
+
+CREATE TABLE IF NOT EXISTS bank_accounts (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    funds INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bank_transactions (
+    id SERIAL PRIMARY KEY,
+    sender_id INTEGER NOT NULL REFERENCES bank_accounts(id),
+    receiver_id INTEGER NOT NULL REFERENCES bank_accounts(id),
+    message TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    time_stamp TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS index_bank_accounts_user_id ON bank_accounts(user_id);
+
+CREATE TABLE IF NOT EXISTS chats (
+    id SERIAL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS chat_messages (
+    id SERIAL PRIMARY KEY,
+    chat_id INTEGER NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    in_chat_index INTEGER NOT NULL,
+    sender_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    time_stamp TIMESTAMP DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS user_chats (
+    id SERIAL PRIMARY KEY,
+    chat_id INTEGER NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (chat_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS direct_chats (
+    chat_id INTEGER PRIMARY KEY REFERENCES chats(id) ON DELETE CASCADE,
+    next_message_index INT NOT NULL DEFAULT 0,
+    last_message TEXT,
+    last_time_stamp TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS group_chats (
+    chat_id INTEGER PRIMARY KEY REFERENCES chats(id) ON DELETE CASCADE,
+    admin_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    next_message_index INT NOT NULL DEFAULT 0,
+    title VARCHAR(64) NOT NULL,
+    last_message TEXT,
+    last_time_stamp TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS index_messages_chat_id_timestamp
+    ON chat_messages (chat_id, time_stamp DESC);
+
+CREATE INDEX IF NOT EXISTS index_user_id_chat_id
+    ON user_chats (user_id);
+
+CREATE TABLE IF NOT EXISTS news_articles (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(64) NOT NULL,
+    content TEXT,
+    timestamp TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS hack_log (
+    id SERIAL PRIMARY KEY,
+    hacker_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    victim_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    hack_type JSONB NOT NULL,
+    successful BOOLEAN NOT NULL,
+    timestamp TIMESTAMP NOT NULL
+);
@@ -0,0 +1,13 @@
+-- One row per login. The id is the jti claim of the session token.
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_label TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS index_sessions_user_id ON sessions(user_id);
+CREATE INDEX IF NOT EXISTS index_sessions_revoked_at ON sessions(revoked_at) WHERE revoked_at IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Characters linked to a session besides the one that logged in, so a shared device switches between them without their passwords.
+CREATE TABLE IF NOT EXISTS session_characters (
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    linked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (session_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS index_session_characters_user_id ON session_characters(user_id);
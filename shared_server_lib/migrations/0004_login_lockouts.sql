@@ -0,0 +1,12 @@
+-- Lockouts after too many failed logins, kept for the game masters to review.
+CREATE TABLE IF NOT EXISTS login_lockouts (
+    id SERIAL PRIMARY KEY,
+    lockout_type TEXT NOT NULL CHECK (lockout_type IN ('username', 'client_ip')),
+    username TEXT NOT NULL,
+    client_ip TEXT,
+    failed_logins INTEGER NOT NULL,
+    locked_until TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS index_login_lockouts_created_at ON login_lockouts(created_at);
@@ -0,0 +1,2 @@
+-- Version of the pepper each password was hashed with, so the pepper can be rotated.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_pepper_version INTEGER NOT NULL DEFAULT 1;
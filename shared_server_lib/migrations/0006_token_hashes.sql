@@ -0,0 +1,7 @@
+-- Legacy tokens are no longer stored in plaintext, they are looked up by a keyed hash the servers
+-- fill in from user_token at startup.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS user_token_hash TEXT;
+CREATE INDEX IF NOT EXISTS index_users_user_token_hash ON users(user_token_hash) WHERE user_token_hash IS NOT NULL;
+
+-- Sessions opened by the hacking server for a hacker, hidden from the victim's session list.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS is_hack BOOLEAN NOT NULL DEFAULT FALSE;
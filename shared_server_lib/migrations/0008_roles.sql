@@ -0,0 +1,70 @@
+-- Roles grant named permissions, replacing the can_publish_posts, can_hack and can_manage_users flags.
+CREATE TABLE IF NOT EXISTS roles (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    permission TEXT NOT NULL CHECK (permission IN ('publish_news', 'hack', 'mint_funds', 'moderate_chat', 'manage_users', 'control_game_state')),
+    PRIMARY KEY (role_id, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, role_id)
+);
+
+INSERT INTO roles (name, description) VALUES
+    ('game_master', 'Runs the game, has every permission.'),
+    ('journalist', 'Publishes news articles.'),
+    ('hacker', 'Hacks other players.'),
+    ('npc', 'A non-player character played by the organizers.')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, permissions.permission
+FROM roles
+JOIN (VALUES
+    ('game_master', 'publish_news'),
+    ('game_master', 'hack'),
+    ('game_master', 'mint_funds'),
+    ('game_master', 'moderate_chat'),
+    ('game_master', 'manage_users'),
+    ('game_master', 'control_game_state'),
+    ('journalist', 'publish_news'),
+    ('hacker', 'hack')
+) AS permissions (role_name, permission) ON permissions.role_name = roles.name
+ON CONFLICT DO NOTHING;
+
+-- The flags migrate into the roles with the same permission.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'users' AND column_name = 'can_publish_posts') THEN
+        INSERT INTO user_roles (user_id, role_id)
+        SELECT users.id, roles.id FROM users JOIN roles ON roles.name = 'journalist' WHERE users.can_publish_posts
+        ON CONFLICT DO NOTHING;
+
+        INSERT INTO user_roles (user_id, role_id)
+        SELECT users.id, roles.id FROM users JOIN roles ON roles.name = 'hacker' WHERE users.can_hack
+        ON CONFLICT DO NOTHING;
+
+        INSERT INTO user_roles (user_id, role_id)
+        SELECT users.id, roles.id FROM users JOIN roles ON roles.name = 'game_master' WHERE users.can_manage_users
+        ON CONFLICT DO NOTHING;
+    END IF;
+END $$;
+
+ALTER TABLE users DROP COLUMN IF EXISTS can_publish_posts;
+ALTER TABLE users DROP COLUMN IF EXISTS can_hack;
+ALTER TABLE users DROP COLUMN IF EXISTS can_manage_users;
+
+-- Sorted names of the permissions of every role of the user.
+CREATE OR REPLACE FUNCTION user_permissions(target_user_id INTEGER) RETURNS TEXT[] AS $$
+    SELECT COALESCE(ARRAY_AGG(DISTINCT role_permissions.permission ORDER BY role_permissions.permission), '{}')
+    FROM user_roles
+    JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+    WHERE user_roles.user_id = target_user_id
+$$ LANGUAGE SQL STABLE;
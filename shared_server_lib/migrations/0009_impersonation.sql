@@ -0,0 +1,15 @@
+-- Sessions a game master opened to act as another user, see /admin/impersonate.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonated_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Every action done with an impersonation token, with the game master behind it.
+CREATE TABLE IF NOT EXISTS impersonation_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    game_master_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    action TEXT NOT NULL,
+    request_id TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS impersonation_audit_log_created_at_idx ON impersonation_audit_log (created_at);
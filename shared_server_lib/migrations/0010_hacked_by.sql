@@ -0,0 +1,2 @@
+-- The hacker each hack session was opened for, see /get_hack_token on the hacking server.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS hacked_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
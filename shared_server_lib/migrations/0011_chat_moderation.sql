@@ -0,0 +1,2 @@
+-- Members muted by a moderator with /moderate_chat can not send messages to the chat until then.
+ALTER TABLE user_chats ADD COLUMN IF NOT EXISTS muted_until TIMESTAMPTZ;
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use tracing::error;
+use axum::{
+    body::{self, Body},
+    extract::{FromRequestParts, OriginalUri, Request},
+    http::{header, request::Parts, HeaderValue},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+
+use crate::{authorization::UserPermissions, common::ServerError, server_logging, session_tokens::SessionTokens};
+
+// Body fields that carried the token before the Authorization header was supported.
+const BODY_TOKEN_FIELDS: &[&str] = &["token", "sender_token", "admin_token"];
+
+// Same as the axum default body limit, so buffering never accepts more than Json would.
+const BODY_TOKEN_BUFFER_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Implemented by every server state that authenticates requests.
+pub trait AuthenticationState {
+    fn session_tokens(&self) -> &SessionTokens;
+}
+
+impl<T: AuthenticationState> AuthenticationState for Arc<T> {
+    fn session_tokens(&self) -> &SessionTokens {
+        (**self).session_tokens()
+    }
+}
+
+/// Extracts the user owning the request token. The token is read from an `Authorization: Bearer` header,
+/// or from the JSON body when `apply_body_token_fallback` is layered on the router.
+/// Requests without a valid token are rejected before the handler runs, requests with an impersonation token
+/// are recorded in the impersonation audit log first.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: i32,
+    pub permissions: UserPermissions,
+    /// `None` for legacy tokens, which have no session.
+    pub session_id: Option<String>,
+    /// User id of the game master acting as the user, for impersonation tokens.
+    pub impersonated_by: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+struct BodyToken(String);
+
+impl<S: AuthenticationState + Send + Sync> FromRequestParts<S> for AuthenticatedUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = match bearer_token(parts)? {
+            Some(token) => token,
+            None => match parts.extensions.get::<BodyToken>() {
+                Some(BodyToken(token)) => token.clone(),
+                None => {
+                    return Err(ServerError::unauthorized("MISSING_TOKEN", "No token provided"));
+                }
+            }
+        };
+
+        let user = state.session_tokens().authenticate(&token).await?;
+        server_logging::record_user_id(user.user_id);
+
+        if let Some(game_master_id) = user.impersonated_by {
+            server_logging::record_impersonated_by(game_master_id);
+
+            // The original URI keeps the server prefix of the launcher.
+            let path = match parts.extensions.get::<OriginalUri>() {
+                Some(OriginalUri(uri)) => uri.path(),
+                None => parts.uri.path(),
+            };
+            let request_id = parts.headers.get(server_logging::REQUEST_ID_HEADER).and_then(|request_id| request_id.to_str().ok());
+
+            state.session_tokens().record_impersonated_action(&user, &format!("{} {}", parts.method, path), request_id).await?;
+        }
+
+        Ok(user)
+    }
+}
+
+/// Lets clients that still send the token in the JSON body authenticate without the Authorization header.
+pub fn apply_body_token_fallback<S: Clone + Send + Sync + 'static>(router: Router<S>) -> Router<S> {
+    router.layer(middleware::from_fn(capture_body_token))
+}
+
+async fn capture_body_token(request: Request, next: Next) -> Response {
+    if request.headers().contains_key(header::AUTHORIZATION) || !has_json_body(&request) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, request_body) = request.into_parts();
+    let bytes = match body::to_bytes(request_body, BODY_TOKEN_BUFFER_LIMIT).await {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            error!("Reading request body for the token failed: {}", error);
+            return ServerError::bad_request("INVALID_BODY", "Request body could not be read").into_response();
+        }
+    };
+
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+        let token = BODY_TOKEN_FIELDS.iter()
+            .find_map(|field| fields.get(*field).and_then(|value| value.as_str()));
+
+        if let Some(token) = token {
+            parts.extensions.insert(BodyToken(token.to_string()));
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+fn has_json_body(request: &Request) -> bool {
+    request.headers().get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+}
+
+fn bearer_token(parts: &Parts) -> Result<Option<String>, ServerError> {
+    let authorization = match parts.headers.get(header::AUTHORIZATION) {
+        Some(authorization) => authorization,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    match parse_bearer_token(authorization) {
+        Some(token) => Ok(Some(token.to_string())),
+        None => Err(ServerError::unauthorized("INVALID_AUTHORIZATION_HEADER", "Authorization header must be: Bearer <token>")),
+    }
+}
+
+fn parse_bearer_token(authorization: &HeaderValue) -> Option<&str> {
+    authorization.to_str().ok()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// The token a request carries, without checking it against the database.
+pub(crate) fn request_token(request: &Request) -> Option<&str> {
+    match request.headers().get(header::AUTHORIZATION) {
+        Some(authorization) => parse_bearer_token(authorization),
+        None => request.extensions().get::<BodyToken>().map(|BodyToken(token)| token.as_str()),
+    }
+}
@@ -0,0 +1,117 @@
+use sqlx::{FromRow, Row, postgres::PgRow};
+use tracing::error;
+
+use cyber_crush_protocol::authentication::Permission;
+
+use crate::{authentication::AuthenticatedUser, common::ServerError};
+
+/// Permissions of the roles of a user, carried by its session token. Read from a `permissions` text array
+/// column, e.g. `user_permissions(users.id) AS permissions` with the database function of the same name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserPermissions(u8);
+
+impl UserPermissions {
+    pub fn contains(&self, permission: Permission) -> bool {
+        self.0 & permission_bit(permission) != 0
+    }
+
+    pub fn with(self, permission: Permission) -> UserPermissions {
+        UserPermissions(self.0 | permission_bit(permission))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Permission> + '_ {
+        Permission::ALL.into_iter().filter(|permission| self.contains(*permission))
+    }
+
+    /// Only the permissions of a player's character, without the ones of game masters. Used where a token
+    /// is handed to another player, like a hack token.
+    pub fn player_permissions(self) -> UserPermissions {
+        self.iter()
+            .filter(|permission| matches!(permission, Permission::PublishNews | Permission::Hack))
+            .collect()
+    }
+}
+
+impl FromIterator<Permission> for UserPermissions {
+    fn from_iter<I: IntoIterator<Item = Permission>>(permissions: I) -> UserPermissions {
+        permissions.into_iter().fold(UserPermissions::default(), UserPermissions::with)
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for UserPermissions {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let permission_names = row.try_get::<Vec<String>, _>("permissions")?;
+
+        Ok(permission_names.iter()
+            .filter_map(|permission_name| {
+                let permission = Permission::from_name(permission_name);
+                if permission.is_none() {
+                    error!("Ignored the unknown permission: {}", permission_name);
+                }
+                permission
+            })
+            .collect())
+    }
+}
+
+impl AuthenticatedUser {
+    /// The authorization check of every server. Rejects the request with 403 unless a role of the user
+    /// grants the permission.
+    pub fn require(&self, permission: Permission) -> Result<(), ServerError> {
+        match self.permissions.contains(permission) {
+            true => Ok(()),
+            false => Err(missing_permission(permission)),
+        }
+    }
+}
+
+fn permission_bit(permission: Permission) -> u8 {
+    1 << permission as u8
+}
+
+// The codes of the checks from before roles are kept for the clients.
+fn missing_permission(permission: Permission) -> ServerError {
+    let (code, message) = match permission {
+        Permission::PublishNews => ("NO_PUBLISHING_PRIVILEGE", "No publishing privilege"),
+        Permission::Hack => ("NO_HACKING_PRIVILEGE", "No hacking privilege"),
+        Permission::MintFunds => ("NO_MINTING_PRIVILEGE", "No minting privilege"),
+        Permission::ModerateChat => ("NO_MODERATION_PRIVILEGE", "No chat moderation privilege"),
+        Permission::ManageUsers => ("NO_USER_MANAGEMENT_PRIVILEGE", "No user management privilege"),
+        Permission::ControlGameState => ("NO_GAME_STATE_PRIVILEGE", "No game state control privilege"),
+    };
+
+    ServerError::forbidden(code, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(permissions: UserPermissions) -> AuthenticatedUser {
+        AuthenticatedUser{ user_id: 5, permissions, session_id: None, impersonated_by: None }
+    }
+
+    #[test]
+    fn player_permissions_drop_the_game_master_ones() {
+        let game_master = Permission::ALL.into_iter().collect::<UserPermissions>();
+
+        let player = game_master.player_permissions();
+
+        assert_eq!(player.iter().collect::<Vec<_>>(), vec![Permission::PublishNews, Permission::Hack]);
+        assert_eq!(UserPermissions::default().with(Permission::MintFunds).with(Permission::ControlGameState).player_permissions(), UserPermissions::default());
+        assert_eq!(player.player_permissions(), player);
+    }
+
+    #[test]
+    fn missing_permissions_keep_their_error_codes() {
+        let journalist = user(UserPermissions::default().with(Permission::PublishNews));
+
+        assert!(journalist.require(Permission::PublishNews).is_ok());
+        assert_eq!(journalist.require(Permission::Hack).unwrap_err().code(), "NO_HACKING_PRIVILEGE");
+        assert_eq!(journalist.require(Permission::MintFunds).unwrap_err().code(), "NO_MINTING_PRIVILEGE");
+        assert_eq!(journalist.require(Permission::ModerateChat).unwrap_err().code(), "NO_MODERATION_PRIVILEGE");
+        assert_eq!(journalist.require(Permission::ManageUsers).unwrap_err().code(), "NO_USER_MANAGEMENT_PRIVILEGE");
+        assert_eq!(journalist.require(Permission::ControlGameState).unwrap_err().code(), "NO_GAME_STATE_PRIVILEGE");
+        assert_eq!(user(UserPermissions::default()).require(Permission::PublishNews).unwrap_err().code(), "NO_PUBLISHING_PRIVILEGE");
+    }
+}
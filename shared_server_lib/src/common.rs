@@ -1,64 +1,288 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use serde_json::{json, Value};
 use sqlx::PgPool;
+use tracing::{error, info};
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Json,
+    Router,
+};
 
-#[derive(Debug, Serialize)]
-pub struct ResponseStatus {
-    pub success: bool,
-    pub status_message: String,
+use crate::{input_validation::InputValidationError, server_metrics::TimedQuery};
+
+pub use cyber_crush_protocol::{ErrorResponse, ResponseStatus};
+
+/// Every handler failure. The `code` is a stable SCREAMING_SNAKE_CASE identifier clients can match on,
+/// the message is for humans and may change.
+#[derive(Debug)]
+pub enum ServerError {
+    BadRequest { code: &'static str, message: String },
+    Unauthorized { code: &'static str, message: String },
+    Forbidden { code: &'static str, message: String },
+    NotFound { code: &'static str, message: String },
+    Conflict { code: &'static str, message: String },
+    TooManyRequests { code: &'static str, message: String, retry_after: Duration },
+    Internal { code: &'static str, message: String },
 }
 
-impl ResponseStatus {
-    pub fn fail(reason: String) -> ResponseStatus {
-        ResponseStatus{ success: false, status_message: reason }
+// Marks responses built from a ServerError so the compatibility layer can rebuild their body.
+#[derive(Debug, Clone)]
+struct ServerErrorResponse(ErrorResponse);
+
+/// Failure body an endpoint answered with before error codes existed, restored in compatibility mode.
+#[derive(Debug, Clone)]
+pub enum LegacyFailureBody {
+    /// A bare `ResponseStatus`: `{ "success": false, "status_message": ... }`.
+    Status,
+    /// `response_status` next to the endpoint's placeholder fields, e.g. `{ "token": "" }`.
+    Fields(Value),
+}
+
+impl ServerError {
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> ServerError {
+        ServerError::BadRequest{ code, message: message.into() }
     }
 
-    pub fn success() -> ResponseStatus {
-        ResponseStatus{ success: true, status_message: "Success".into() }
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> ServerError {
+        ServerError::Unauthorized{ code, message: message.into() }
+    }
+
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> ServerError {
+        ServerError::Forbidden{ code, message: message.into() }
+    }
+
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> ServerError {
+        ServerError::NotFound{ code, message: message.into() }
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> ServerError {
+        ServerError::Conflict{ code, message: message.into() }
+    }
+
+    pub fn too_many_requests(code: &'static str, message: impl Into<String>, retry_after: Duration) -> ServerError {
+        ServerError::TooManyRequests{ code, message: message.into(), retry_after }
+    }
+
+    pub fn internal(message: impl Into<String>) -> ServerError {
+        ServerError::Internal{ code: "INTERNAL_ERROR", message: message.into() }
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct ValidateTokenRequest {
-    pub token: String,
+    pub fn invalid_token() -> ServerError {
+        ServerError::unauthorized("INVALID_TOKEN", "Token not validated")
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::BadRequest{ .. } => StatusCode::BAD_REQUEST,
+            ServerError::Unauthorized{ .. } => StatusCode::UNAUTHORIZED,
+            ServerError::Forbidden{ .. } => StatusCode::FORBIDDEN,
+            ServerError::NotFound{ .. } => StatusCode::NOT_FOUND,
+            ServerError::Conflict{ .. } => StatusCode::CONFLICT,
+            ServerError::TooManyRequests{ .. } => StatusCode::TOO_MANY_REQUESTS,
+            ServerError::Internal{ .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServerError::BadRequest{ code, .. }
+            | ServerError::Unauthorized{ code, .. }
+            | ServerError::Forbidden{ code, .. }
+            | ServerError::NotFound{ code, .. }
+            | ServerError::Conflict{ code, .. }
+            | ServerError::TooManyRequests{ code, .. }
+            | ServerError::Internal{ code, .. } => code,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ServerError::BadRequest{ message, .. }
+            | ServerError::Unauthorized{ message, .. }
+            | ServerError::Forbidden{ message, .. }
+            | ServerError::NotFound{ message, .. }
+            | ServerError::Conflict{ message, .. }
+            | ServerError::TooManyRequests{ message, .. }
+            | ServerError::Internal{ message, .. } => message,
+        }
+    }
 }
 
-//TODO rename to a general id response name like UserIdResponse
-#[derive(Debug)]
-pub struct ValidateTokenResponse {
-    pub response_status: ResponseStatus,
-    pub id : Option<i32>
+impl From<InputValidationError> for ServerError {
+    fn from(error: InputValidationError) -> ServerError {
+        ServerError::bad_request("INVALID_INPUT", error.to_string())
+    }
 }
 
-use ValidateTokenResponse as UserIdByUsernameResponse;
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        match &self {
+            ServerError::Internal{ .. } => error!(error_code = self.code(), "Request failed: {}", self.message()),
+            _ => info!(error_code = self.code(), "Request rejected: {}", self.message()),
+        }
+
+        // Whole seconds, rounded up so a client retrying on time is not rejected again.
+        let retry_after_seconds = match &self {
+            ServerError::TooManyRequests{ retry_after, .. } => Some(retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0)),
+            _ => None,
+        };
 
-pub async fn validate_token(db_pool: &PgPool, token: &String) -> ValidateTokenResponse {
-    let token_validation_query = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE user_token = $1")
-        .bind(token)
-        .fetch_optional(db_pool)
-        .await;
+        let body = ErrorResponse{
+            error_code: self.code().to_string(),
+            response_status: ResponseStatus::fail(self.message().to_string()),
+            retry_after_seconds,
+        };
 
-    match token_validation_query {
-        Ok(Some(id)) => ValidateTokenResponse{ response_status: ResponseStatus::success(), id: Some(id) },
-        Ok(None) => ValidateTokenResponse{ response_status: ResponseStatus::fail("Token not validated".into()), id: None },
-        Err(error) => {
-            eprintln!("Error: Failed to validate token {}: {}", token, error);
-            ValidateTokenResponse{ response_status: ResponseStatus::fail("Token validation server internal error.".into()), id: None }
+        let mut response = (self.status_code(), Json(body.clone())).into_response();
+        if let Some(retry_after_seconds) = retry_after_seconds {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after_seconds.into());
         }
+        response.extensions_mut().insert(ServerErrorResponse(body));
+        response
     }
 }
 
-pub async fn get_user_id_by_username(db_pool: &PgPool, username: &String) -> UserIdByUsernameResponse {
+/// Tags failures of the listed paths with the body they had before error codes existed.
+/// Paths are relative to `router`, so it works the same when the launcher nests it under a prefix.
+/// Every server applies it in its `create_router`, with the list from its `legacy_failure_bodies`.
+pub fn apply_legacy_failure_bodies<S: Clone + Send + Sync + 'static>(router: Router<S>, legacy_failure_bodies: Vec<(&'static str, LegacyFailureBody)>) -> Router<S> {
+    let legacy_failure_bodies = Arc::new(legacy_failure_bodies.into_iter().collect::<HashMap<_, _>>());
+
+    router.layer(middleware::from_fn(move |request: Request, next: Next| {
+        let legacy_failure_body = legacy_failure_bodies.get(request.uri().path()).cloned();
+        async move {
+            let mut response = next.run(request).await;
+            if let Some(legacy_failure_body) = legacy_failure_body {
+                response.extensions_mut().insert(legacy_failure_body);
+            }
+            response
+        }
+    }))
+}
+
+/// In compatibility mode every ServerError is answered with HTTP 200 and, where the endpoint is tagged by
+/// `apply_legacy_failure_bodies`, the body it had before error codes existed. `error_code` is kept in every body.
+/// Applied once per listener on top of the routers of `create_router`, after `server_metrics::apply_metrics`
+/// so the metrics see the real status codes.
+pub fn apply_response_compatibility_mode<S: Clone + Send + Sync + 'static>(router: Router<S>, compatibility_mode: bool) -> Router<S> {
+    if compatibility_mode {
+        router.layer(middleware::map_response(legacy_failure_response))
+    }
+    else {
+        router
+    }
+}
+
+async fn legacy_failure_response(response: Response) -> Response {
+    let error = match response.extensions().get::<ServerErrorResponse>() {
+        Some(ServerErrorResponse(error)) => error.clone(),
+        None => return response,
+    };
+
+    let body = legacy_failure_body(&error, response.extensions().get::<LegacyFailureBody>());
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::OK;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+fn legacy_failure_body(error: &ErrorResponse, legacy_failure_body: Option<&LegacyFailureBody>) -> Value {
+    let mut body = match legacy_failure_body {
+        Some(LegacyFailureBody::Status) => json!({ "success": false, "status_message": error.response_status.status_message }),
+        Some(LegacyFailureBody::Fields(fields)) => {
+            let mut body = fields.clone();
+            body["response_status"] = json!(error.response_status);
+            body
+        },
+        None => return json!(error),
+    };
+
+    body["error_code"] = json!(error.error_code);
+    if let Some(retry_after_seconds) = error.retry_after_seconds {
+        body["retry_after_seconds"] = json!(retry_after_seconds);
+    }
+    body
+}
+
+pub async fn get_user_id_by_username(db_pool: &PgPool, username: &str) -> Result<i32, ServerError> {
     let user_id_query = sqlx::query_scalar::<_, i32>("SELECT id FROM users WHERE username = $1")
         .bind(username)
         .fetch_optional(db_pool)
+        .timed("get_user_id_by_username")
         .await;
 
     match user_id_query {
-        Ok(Some(id)) => UserIdByUsernameResponse{ response_status: ResponseStatus::success(), id: Some(id) },
-        Ok(None) => UserIdByUsernameResponse{ response_status: ResponseStatus::fail("User not found".into()), id: None },
+        Ok(Some(id)) => Ok(id),
+        Ok(None) => Err(ServerError::not_found("USER_NOT_FOUND", "User not found")),
         Err(error) => {
-            eprintln!("Error Failed to get user id by username: {}, Error: {}", username, error);
-            UserIdByUsernameResponse{ response_status: ResponseStatus::fail("User not found. Internal server error".into()), id: None }
+            error!("Failed to get user id by username: {}, Error: {}", username, error);
+            Err(ServerError::internal("User not found. Internal server error"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    fn bank_router() -> Router {
+        let router = Router::new()
+            .route("/get_user_funds", get(|| async { Err::<(), _>(ServerError::not_found("BANK_ACCOUNT_NOT_FOUND", "No account")) }))
+            .route("/transfer_funds", get(|| async { Err::<(), _>(ServerError::too_many_requests("RATE_LIMITED", "Slow down", Duration::from_millis(1500))) }))
+            .route("/get_sessions", get(|| async { Err::<(), _>(ServerError::invalid_token()) }));
+
+        apply_legacy_failure_bodies(router, vec![
+            ("/get_user_funds", LegacyFailureBody::Fields(json!({ "funds": -1 }))),
+            ("/transfer_funds", LegacyFailureBody::Status),
+        ])
+    }
+
+    // Nested like the launcher does it, so the legacy bodies have to match the path inside the server.
+    async fn call(compatibility_mode: bool, path: &str) -> (StatusCode, Value) {
+        let app = apply_response_compatibility_mode(Router::new().nest("/bank", bank_router()), compatibility_mode);
+        let response = app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn errors_have_real_status_codes_and_one_status() {
+        let (status, body) = call(false, "/bank/get_user_funds").await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, json!({ "error_code": "BANK_ACCOUNT_NOT_FOUND", "response_status": { "success": false, "status_message": "No account" } }));
+    }
+
+    #[tokio::test]
+    async fn compatibility_mode_restores_legacy_fields() {
+        let (status, body) = call(true, "/bank/get_user_funds").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "funds": -1, "error_code": "BANK_ACCOUNT_NOT_FOUND", "response_status": { "success": false, "status_message": "No account" } }));
+    }
+
+    #[tokio::test]
+    async fn compatibility_mode_restores_bare_statuses() {
+        let (status, body) = call(true, "/bank/transfer_funds").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "success": false, "status_message": "Slow down", "error_code": "RATE_LIMITED", "retry_after_seconds": 2 }));
+    }
+
+    #[tokio::test]
+    async fn compatibility_mode_keeps_the_error_body_of_new_endpoints() {
+        let (status, body) = call(true, "/bank/get_sessions").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "error_code": "INVALID_TOKEN", "response_status": { "success": false, "status_message": "Token not validated" } }));
+    }
+}
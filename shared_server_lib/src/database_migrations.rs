@@ -0,0 +1,193 @@
+use std::str::FromStr;
+use sqlx::{PgPool, Postgres, pool::PoolConnection};
+use tracing::{error, info};
+
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    sql: &'static str,
+}
+
+/// Ordered list of schema changes. Never edit an entry that was already released, add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration{ version: 1, description: "initial schema", sql: include_str!("../migrations/0001_initial_schema.sql") },
+    Migration{ version: 2, description: "sessions", sql: include_str!("../migrations/0002_sessions.sql") },
+    Migration{ version: 3, description: "session characters", sql: include_str!("../migrations/0003_session_characters.sql") },
+    Migration{ version: 4, description: "login lockouts", sql: include_str!("../migrations/0004_login_lockouts.sql") },
+    Migration{ version: 5, description: "password pepper version", sql: include_str!("../migrations/0005_password_pepper_version.sql") },
+    Migration{ version: 6, description: "token hashes", sql: include_str!("../migrations/0006_token_hashes.sql") },
+    Migration{ version: 7, description: "user management", sql: include_str!("../migrations/0007_user_management.sql") },
+    Migration{ version: 8, description: "roles", sql: include_str!("../migrations/0008_roles.sql") },
+    Migration{ version: 9, description: "impersonation", sql: include_str!("../migrations/0009_impersonation.sql") },
+    Migration{ version: 10, description: "hacked by", sql: include_str!("../migrations/0010_hacked_by.sql") },
+    Migration{ version: 11, description: "chat moderation", sql: include_str!("../migrations/0011_chat_moderation.sql") },
+];
+
+// Shared by every server so only one of them migrates the database at a time.
+const MIGRATION_LOCK_KEY: i64 = 7_243_001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MigrationMode {
+    Apply,
+    Verify,
+}
+
+impl FromStr for MigrationMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "apply" => Ok(MigrationMode::Apply),
+            "verify" => Ok(MigrationMode::Verify),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn latest_schema_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+pub async fn apply_migrations(db_pool: &PgPool) -> Result<i32, String> {
+    let mut connection = match db_pool.acquire().await {
+        Ok(connection) => connection,
+        Err(error) => {
+            return Err(format!("Failed to acquire a migration connection: {}", error));
+        }
+    };
+
+    // Waiting for the lock and running migrations can take longer than the configured statement_timeout.
+    if let Err(error) = sqlx::query("SET statement_timeout = 0").execute(&mut *connection).await {
+        return Err(format!("Failed to disable the statement timeout for migrations: {}", error));
+    }
+
+    if let Err(error) = sqlx::query("SELECT pg_advisory_lock($1)").bind(MIGRATION_LOCK_KEY).execute(&mut *connection).await {
+        return Err(format!("Failed to take the migration lock: {}", error));
+    }
+
+    let migration_result = apply_pending_migrations(&mut connection).await;
+
+    if let Err(error) = sqlx::query("SELECT pg_advisory_unlock($1)").bind(MIGRATION_LOCK_KEY).execute(&mut *connection).await {
+        error!("Releasing the migration lock failed: {}", error);
+    }
+
+    if let Err(error) = sqlx::query("RESET statement_timeout").execute(&mut *connection).await {
+        error!("Restoring the statement timeout after migrations failed: {}", error);
+    }
+
+    migration_result?;
+    verify_schema_version(db_pool).await
+}
+
+/// Fails when the database is behind or ahead of the migrations compiled into this server.
+pub async fn verify_schema_version(db_pool: &PgPool) -> Result<i32, String> {
+    let mut connection = match db_pool.acquire().await {
+        Ok(connection) => connection,
+        Err(error) => {
+            return Err(format!("Failed to acquire a connection for the schema check: {}", error));
+        }
+    };
+
+    let current_version = current_schema_version(&mut connection).await?;
+    let latest_version = latest_schema_version();
+
+    if current_version < latest_version {
+        return Err(format!("Database schema version {} is outdated, this server requires version {}. Run a server with database_migration_mode set to apply.", current_version, latest_version));
+    }
+
+    if current_version > latest_version {
+        return Err(format!("Database schema version {} is newer than version {} supported by this server. Update the server.", current_version, latest_version));
+    }
+
+    Ok(current_version)
+}
+
+async fn apply_pending_migrations(connection: &mut PoolConnection<Postgres>) -> Result<(), String> {
+    let create_migrations_table_query = sqlx::query(
+    r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT NOW()
+        )
+    "#)
+    .execute(&mut **connection)
+    .await;
+
+    if let Err(error) = create_migrations_table_query {
+        return Err(format!("Failed to create the schema_migrations table: {}", error));
+    }
+
+    let current_version = current_schema_version(connection).await?;
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current_version) {
+        let mut transaction = match sqlx::Connection::begin(&mut **connection).await {
+            Ok(transaction) => transaction,
+            Err(error) => {
+                return Err(format!("Failed to start migration {} transaction: {}", migration.version, error));
+            }
+        };
+
+        if let Err(error) = sqlx::raw_sql(migration.sql).execute(&mut *transaction).await {
+            let _ = transaction.rollback().await;
+            return Err(format!("Migration {} ({}) failed: {}", migration.version, migration.description, error));
+        }
+
+        let record_migration_query = sqlx::query("INSERT INTO schema_migrations (version, description) VALUES ($1, $2)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .execute(&mut *transaction)
+            .await;
+
+        if let Err(error) = record_migration_query {
+            let _ = transaction.rollback().await;
+            return Err(format!("Failed to record migration {}: {}", migration.version, error));
+        }
+
+        if let Err(error) = transaction.commit().await {
+            return Err(format!("Failed to commit migration {}: {}", migration.version, error));
+        }
+
+        info!("Applied database migration {}: {}", migration.version, migration.description);
+    }
+
+    Ok(())
+}
+
+async fn current_schema_version(connection: &mut PoolConnection<Postgres>) -> Result<i32, String> {
+    let migrations_table_query = sqlx::query_scalar::<_, bool>("SELECT to_regclass('schema_migrations') IS NOT NULL")
+        .fetch_one(&mut **connection)
+        .await;
+
+    match migrations_table_query {
+        Ok(true) => {},
+        Ok(false) => {
+            return Ok(0);
+        },
+        Err(error) => {
+            return Err(format!("Failed to look up the schema_migrations table: {}", error));
+        }
+    }
+
+    let version_query = sqlx::query_scalar::<_, Option<i32>>("SELECT MAX(version) FROM schema_migrations")
+        .fetch_one(&mut **connection)
+        .await;
+
+    match version_query {
+        Ok(version) => Ok(version.unwrap_or(0)),
+        Err(error) => Err(format!("Failed to read the schema version: {}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_count_up_from_one() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i32 + 1, "{}", migration.description);
+        }
+        assert_eq!(latest_schema_version(), MIGRATIONS.len() as i32);
+    }
+}
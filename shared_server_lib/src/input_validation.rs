@@ -0,0 +1,187 @@
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct InputLimits {
+    pub max_username_length: usize,
+    pub max_password_length: usize,
+    pub max_extra_data_length: usize,
+    pub max_group_chat_members: usize,
+    pub max_chat_message_length: usize,
+    pub max_transfer_message_length: usize,
+    pub max_title_length: usize,
+    pub max_device_label_length: usize,
+    pub max_cyber_defence_level: usize,
+}
+
+#[derive(Debug)]
+pub struct InputValidationError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for InputValidationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Invalid {}: {}", self.field, self.message)
+    }
+}
+
+impl InputLimits {
+    pub fn validate_login(&self, username: &str, password: &str) -> Result<(), InputValidationError> {
+        check_length("username", username, self.max_username_length)?;
+        check_length("password", password, self.max_password_length)
+    }
+
+    /// Like `validate_login`, but neither can be empty.
+    pub fn validate_new_user(&self, username: &str, password: &str) -> Result<(), InputValidationError> {
+        check_not_empty("username", username)?;
+        check_not_empty("password", password)?;
+        self.validate_login(username, password)
+    }
+
+    /// Role names share the username limit, descriptions the title limit.
+    pub fn validate_role(&self, name: &str, description: &str) -> Result<(), InputValidationError> {
+        check_not_empty("name", name)?;
+        check_length("name", name, self.max_username_length)?;
+        check_length("description", description, self.max_title_length)
+    }
+
+    pub fn validate_password(&self, password: &str) -> Result<(), InputValidationError> {
+        check_length("password", password, self.max_password_length)
+    }
+
+    /// The limit applies to the serialized JSON in bytes, the same way the user loader measures it.
+    pub fn validate_extra_data(&self, extra_data: &serde_json::Value) -> Result<(), InputValidationError> {
+        let serialized_length = extra_data.to_string().len();
+
+        if serialized_length > self.max_extra_data_length {
+            return Err(InputValidationError{
+                field: "extra_data",
+                message: format!("{} bytes exceeds the {} byte limit", serialized_length, self.max_extra_data_length) });
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_cyber_defence_level(&self, cyber_defence_level: i32) -> Result<(), InputValidationError> {
+        if usize::try_from(cyber_defence_level).map_or(true, |level| level > self.max_cyber_defence_level) {
+            return Err(InputValidationError{
+                field: "cyber_defence_level",
+                message: format!("{} is not between 0 and {}", cyber_defence_level, self.max_cyber_defence_level) });
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_chat_message(&self, message: &str) -> Result<(), InputValidationError> {
+        check_length("message", message, self.max_chat_message_length)
+    }
+
+    pub fn validate_transfer_message(&self, message: &str) -> Result<(), InputValidationError> {
+        check_length("message", message, self.max_transfer_message_length)
+    }
+
+    pub fn validate_device_label(&self, device_label: &str) -> Result<(), InputValidationError> {
+        check_length("device_label", device_label, self.max_device_label_length)
+    }
+
+    pub fn validate_news_title(&self, title: &str) -> Result<(), InputValidationError> {
+        check_length("title", title, self.max_title_length)
+    }
+
+    pub fn validate_group_chat_title(&self, title: &str) -> Result<(), InputValidationError> {
+        check_length("title", title, self.max_title_length)
+    }
+
+    pub fn validate_group_chat_member_count(&self, member_count: usize) -> Result<(), InputValidationError> {
+        if member_count > self.max_group_chat_members {
+            return Err(InputValidationError{
+                field: "group chat members",
+                message: format!("a group chat can have at most {} members", self.max_group_chat_members) });
+        }
+
+        Ok(())
+    }
+}
+
+fn check_not_empty(field: &'static str, value: &str) -> Result<(), InputValidationError> {
+    if value.trim().is_empty() {
+        return Err(InputValidationError{ field, message: "can not be empty".to_string() });
+    }
+
+    Ok(())
+}
+
+fn check_length(field: &'static str, value: &str, max_length: usize) -> Result<(), InputValidationError> {
+    let length = value.chars().count();
+
+    if length > max_length {
+        return Err(InputValidationError{
+            field,
+            message: format!("{} characters exceeds the {} character limit", length, max_length) });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> InputLimits {
+        InputLimits {
+            max_username_length: 4,
+            max_password_length: 8,
+            max_extra_data_length: 16,
+            max_group_chat_members: 3,
+            max_chat_message_length: 5,
+            max_transfer_message_length: 5,
+            max_title_length: 5,
+            max_device_label_length: 5,
+            max_cyber_defence_level: 3,
+        }
+    }
+
+    #[test]
+    fn lengths_are_counted_in_characters() {
+        // Five characters, but twice as many bytes.
+        assert!(limits().validate_chat_message("żółść").is_ok());
+        assert!(limits().validate_chat_message("żółśćx").is_err());
+        assert!(limits().validate_login("ąęóż", "password").is_ok());
+    }
+
+    #[test]
+    fn too_long_values_name_the_field_and_limit() {
+        let error = limits().validate_news_title("abcdef").unwrap_err();
+
+        assert_eq!(error.field, "title");
+        assert_eq!(error.to_string(), "Invalid title: 6 characters exceeds the 5 character limit");
+    }
+
+    #[test]
+    fn new_users_need_a_name_and_password() {
+        assert_eq!(limits().validate_new_user("  ", "password").unwrap_err().field, "username");
+        assert_eq!(limits().validate_new_user("ann", "").unwrap_err().field, "password");
+        assert!(limits().validate_login("", "").is_ok());
+    }
+
+    #[test]
+    fn extra_data_is_measured_as_serialized_bytes() {
+        assert!(limits().validate_extra_data(&serde_json::json!({ "a": "01234567" })).is_ok());
+        // 14 characters, but 20 bytes once serialized.
+        assert!(limits().validate_extra_data(&serde_json::json!({ "a": "ąęóżźć" })).is_err());
+    }
+
+    #[test]
+    fn cyber_defence_levels_stay_in_range() {
+        assert!(limits().validate_cyber_defence_level(0).is_ok());
+        assert!(limits().validate_cyber_defence_level(3).is_ok());
+        assert!(limits().validate_cyber_defence_level(4).is_err());
+        assert_eq!(limits().validate_cyber_defence_level(-1).unwrap_err().to_string(), "Invalid cyber_defence_level: -1 is not between 0 and 3");
+    }
+
+    #[test]
+    fn group_chats_are_limited_to_the_member_count() {
+        assert!(limits().validate_group_chat_member_count(3).is_ok());
+        assert!(limits().validate_group_chat_member_count(4).is_err());
+    }
+}
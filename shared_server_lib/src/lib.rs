@@ -1,3 +1,18 @@
 pub mod server_configurator;
 pub mod server_database;
+pub mod server_logging;
+pub mod server_health;
+pub mod server_metrics;
+pub mod server_rate_limit;
+pub mod server_listener;
+pub mod server_cors;
+pub mod server_openapi;
+pub mod server_shutdown;
+pub mod database_migrations;
 pub mod common;
+pub mod authentication;
+pub mod authorization;
+pub mod session_tokens;
+pub mod login_lockout;
+pub mod password_hashing;
+pub mod input_validation;
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use metrics::counter;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{common::ServerError, server_metrics::TimedQuery};
+
+// Most usernames and client IPs tracked at once. Once reached, the forgotten ones are dropped first and
+// then the ones with the oldest failure, so a flood of made up usernames can not grow the map forever.
+const MAX_TRACKED_ATTEMPTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLockoutConfiguration {
+    /// Failed logins of one username before it is locked, 0 disables the username lockout.
+    pub username_failures: u32,
+    /// Failed logins from one client IP before it is locked, 0 disables the IP lockout.
+    pub ip_failures: u32,
+    /// Length of the first lockout, every further lockout of the same username or IP doubles it.
+    pub lockout: Duration,
+    /// Longest lockout. Failures are forgotten after this long without a new one.
+    pub max_lockout: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum LockoutKey {
+    Username(String),
+    ClientIp(IpAddr),
+}
+
+#[derive(Debug)]
+struct FailedLogins {
+    failures: u32,
+    lockouts: u32,
+    last_failure_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+struct Lockout {
+    key: LockoutKey,
+    failures: u32,
+    duration: Duration,
+}
+
+/// Counts failed logins per username and per client IP and locks them out for an escalating time once
+/// too many fail. Every lockout is stored in the `login_lockouts` table for the game masters.
+/// The counts are kept in memory, so a restart of the authentication server clears them.
+pub struct LoginLockout {
+    configuration: LoginLockoutConfiguration,
+    db_pool: PgPool,
+    attempts: Mutex<HashMap<LockoutKey, FailedLogins>>,
+    max_tracked_attempts: usize,
+}
+
+impl fmt::Debug for LoginLockout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginLockout")
+            .field("configuration", &self.configuration)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoginLockout {
+    pub fn new(configuration: LoginLockoutConfiguration, db_pool: PgPool) -> LoginLockout {
+        LoginLockout{ configuration, db_pool, attempts: Mutex::new(HashMap::new()), max_tracked_attempts: MAX_TRACKED_ATTEMPTS }
+    }
+
+    /// Rejects the login with a 429 `LOGIN_LOCKED` error while the username or the client IP is locked.
+    /// Unknown usernames are locked the same way, so a lockout tells nothing about which usernames exist.
+    pub fn check(&self, username: &str, client_ip: Option<IpAddr>) -> Result<(), ServerError> {
+        let now = Instant::now();
+        let attempts = match self.attempts.lock() {
+            Ok(attempts) => attempts,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let locked_until = lockout_keys(username, client_ip)
+            .filter_map(|key| attempts.get(&key)?.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .max();
+
+        match locked_until {
+            Some(locked_until) => Err(ServerError::too_many_requests("LOGIN_LOCKED", "Too many failed logins, try again later", locked_until - now)),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, username: &str, client_ip: Option<IpAddr>) {
+        let lockouts = {
+            let mut attempts = match self.attempts.lock() {
+                Ok(attempts) => attempts,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+
+            let now = Instant::now();
+            self.evict_attempts(&mut attempts, now);
+
+            lockout_keys(username, client_ip)
+                .filter_map(|key| self.count_failure(&mut attempts, key, now))
+                .collect::<Vec<_>>()
+        };
+
+        for lockout in lockouts {
+            self.store_lockout(&lockout, username, client_ip).await;
+        }
+    }
+
+    /// Forgets the failed logins of the username, the ones of the client IP are kept.
+    pub fn record_success(&self, username: &str) {
+        let mut attempts = match self.attempts.lock() {
+            Ok(attempts) => attempts,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        attempts.remove(&LockoutKey::Username(username.to_string()));
+    }
+
+    // Leaves room for the two keys of a failed login.
+    fn evict_attempts(&self, attempts: &mut HashMap<LockoutKey, FailedLogins>, now: Instant) {
+        let max_attempts = self.max_tracked_attempts.saturating_sub(2);
+        if attempts.len() <= max_attempts {
+            return;
+        }
+
+        attempts.retain(|_, failed_logins| !failed_logins.is_forgotten(now, self.configuration.max_lockout));
+        if attempts.len() <= max_attempts {
+            return;
+        }
+
+        // Evicts a tenth more than needed, so the sort is not repeated on every failed login.
+        let mut last_failures = attempts.iter()
+            .map(|(key, failed_logins)| (failed_logins.last_failure_at, key.clone()))
+            .collect::<Vec<_>>();
+        last_failures.sort_unstable_by_key(|(last_failure_at, _)| *last_failure_at);
+
+        let eviction_count = attempts.len() - max_attempts + max_attempts / 10;
+        warn!("Tracking failed logins of {} usernames and client IPs, forgetting the {} oldest", attempts.len(), eviction_count);
+        for (_, key) in last_failures.into_iter().take(eviction_count) {
+            attempts.remove(&key);
+        }
+    }
+
+    fn count_failure(&self, attempts: &mut HashMap<LockoutKey, FailedLogins>, key: LockoutKey, now: Instant) -> Option<Lockout> {
+        let threshold = match key {
+            LockoutKey::Username(_) => self.configuration.username_failures,
+            LockoutKey::ClientIp(_) => self.configuration.ip_failures,
+        };
+
+        if threshold == 0 {
+            return None;
+        }
+
+        let failed_logins = attempts.entry(key.clone()).or_insert(FailedLogins{ failures: 0, lockouts: 0, last_failure_at: now, locked_until: None });
+        if failed_logins.is_forgotten(now, self.configuration.max_lockout) {
+            *failed_logins = FailedLogins{ failures: 0, lockouts: 0, last_failure_at: now, locked_until: None };
+        }
+
+        failed_logins.failures += 1;
+        failed_logins.last_failure_at = now;
+
+        if failed_logins.failures < threshold {
+            return None;
+        }
+
+        let duration = self.configuration.lockout
+            .saturating_mul(2u32.saturating_pow(failed_logins.lockouts))
+            .min(self.configuration.max_lockout);
+        let failures = failed_logins.failures;
+
+        failed_logins.failures = 0;
+        failed_logins.lockouts += 1;
+        failed_logins.locked_until = Some(now + duration);
+
+        Some(Lockout{ key, failures, duration })
+    }
+
+    async fn store_lockout(&self, lockout: &Lockout, username: &str, client_ip: Option<IpAddr>) {
+        let lockout_type = match lockout.key {
+            LockoutKey::Username(_) => "username",
+            LockoutKey::ClientIp(_) => "client_ip",
+        };
+
+        warn!("Login lockout by {} for {} seconds after {} failed logins, last one for user: {} from {:?}", lockout_type, lockout.duration.as_secs(), lockout.failures, username, client_ip);
+        counter!("login_lockouts_total", "type" => lockout_type).increment(1);
+
+        let lockout_insertion = sqlx::query(
+            "INSERT INTO login_lockouts (lockout_type, username, client_ip, failed_logins, locked_until) VALUES ($1, $2, $3, $4, NOW() + $5 * INTERVAL '1 second')"
+            )
+            .bind(lockout_type)
+            .bind(username)
+            .bind(client_ip.map(|client_ip| client_ip.to_string()))
+            .bind(lockout.failures as i32)
+            .bind(lockout.duration.as_secs_f64())
+            .execute(&self.db_pool)
+            .timed("store_login_lockout")
+            .await;
+
+        if let Err(error) = lockout_insertion {
+            error!("Failed to store a login lockout of user: {}. Error: {}", username, error);
+        }
+    }
+}
+
+impl FailedLogins {
+    fn is_forgotten(&self, now: Instant, max_lockout: Duration) -> bool {
+        let is_locked = self.locked_until.is_some_and(|locked_until| locked_until > now);
+        !is_locked && now.duration_since(self.last_failure_at) >= max_lockout
+    }
+}
+
+fn lockout_keys(username: &str, client_ip: Option<IpAddr>) -> impl Iterator<Item = LockoutKey> {
+    std::iter::once(LockoutKey::Username(username.to_string()))
+        .chain(client_ip.map(LockoutKey::ClientIp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_database;
+
+    const CLIENT_IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+    const OTHER_CLIENT_IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2)));
+
+    // Storing a lockout in the unreachable database only logs an error.
+    fn login_lockout(username_failures: u32, ip_failures: u32) -> LoginLockout {
+        let configuration = LoginLockoutConfiguration{
+            username_failures,
+            ip_failures,
+            lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(150),
+        };
+        LoginLockout::new(configuration, server_database::unreachable_pool())
+    }
+
+    fn locked_for(result: Result<(), ServerError>) -> Option<Duration> {
+        match result {
+            Ok(()) => None,
+            Err(ServerError::TooManyRequests{ code: "LOGIN_LOCKED", retry_after, .. }) => Some(retry_after),
+            Err(error) => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn usernames_are_locked_after_too_many_failures() {
+        let login_lockout = login_lockout(3, 0);
+
+        for _ in 0..2 {
+            login_lockout.record_failure("alice", CLIENT_IP).await;
+            assert!(login_lockout.check("alice", CLIENT_IP).is_ok());
+        }
+
+        login_lockout.record_failure("alice", CLIENT_IP).await;
+        let locked_for = locked_for(login_lockout.check("alice", OTHER_CLIENT_IP)).unwrap();
+        assert!(locked_for > Duration::from_secs(55) && locked_for <= Duration::from_secs(60));
+        assert!(login_lockout.check("bob", CLIENT_IP).is_ok());
+    }
+
+    #[tokio::test]
+    async fn client_ips_are_locked_for_every_username() {
+        let login_lockout = login_lockout(0, 2);
+
+        login_lockout.record_failure("alice", CLIENT_IP).await;
+        login_lockout.record_failure("unknown user", CLIENT_IP).await;
+
+        assert!(locked_for(login_lockout.check("bob", CLIENT_IP)).is_some());
+        assert!(login_lockout.check("bob", OTHER_CLIENT_IP).is_ok());
+        assert!(login_lockout.check("bob", None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn successes_forget_the_username_failures_only() {
+        let login_lockout = login_lockout(2, 2);
+
+        login_lockout.record_failure("alice", CLIENT_IP).await;
+        login_lockout.record_success("alice");
+        login_lockout.record_failure("alice", OTHER_CLIENT_IP).await;
+        assert!(login_lockout.check("alice", None).is_ok());
+
+        login_lockout.record_failure("bob", CLIENT_IP).await;
+        assert!(locked_for(login_lockout.check("carol", CLIENT_IP)).is_some());
+    }
+
+    #[tokio::test]
+    async fn lockouts_double_up_to_the_longest_lockout() {
+        let login_lockout = login_lockout(1, 0);
+        let mut lockouts = Vec::new();
+
+        for _ in 0..3 {
+            login_lockout.record_failure("alice", None).await;
+            lockouts.push(locked_for(login_lockout.check("alice", None)).unwrap().as_secs_f64().round() as u64);
+        }
+
+        assert_eq!(lockouts, vec![60, 120, 150]);
+    }
+
+    #[tokio::test]
+    async fn zero_thresholds_disable_the_lockout() {
+        let login_lockout = login_lockout(0, 0);
+
+        for _ in 0..5 {
+            login_lockout.record_failure("alice", CLIENT_IP).await;
+        }
+
+        assert!(login_lockout.check("alice", CLIENT_IP).is_ok());
+        assert!(login_lockout.attempts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_oldest_failures_are_evicted_once_too_many_are_tracked() {
+        let mut login_lockout = login_lockout(2, 0);
+        login_lockout.max_tracked_attempts = 22;
+
+        login_lockout.record_failure("oldest", None).await;
+        for user_index in 0..30 {
+            login_lockout.record_failure(&format!("user {}", user_index), None).await;
+        }
+        login_lockout.record_failure("newest", None).await;
+
+        let attempts = login_lockout.attempts.lock().unwrap();
+        assert!(attempts.len() <= 22);
+        assert!(!attempts.contains_key(&LockoutKey::Username("oldest".to_string())));
+        assert!(attempts.contains_key(&LockoutKey::Username("newest".to_string())));
+        assert!(attempts.contains_key(&LockoutKey::Username("user 29".to_string())));
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version, password_hash::{SaltString, rand_core::OsRng}};
+use tracing::error;
+
+#[derive(Clone)]
+pub struct PasswordHashingConfiguration {
+    /// Argon2id cost of new hashes. Hashes with other parameters still verify and are replaced at the next login.
+    pub params: Params,
+    /// Appended to every password before hashing, kept out of the database.
+    pub pepper: String,
+    pub pepper_version: i32,
+    /// Previous peppers by version, accepted until every user logged in with the current one.
+    pub old_peppers: HashMap<i32, String>,
+}
+
+impl fmt::Debug for PasswordHashingConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordHashingConfiguration")
+            .field("params", &self.params)
+            .field("pepper_version", &self.pepper_version)
+            .field("old_pepper_versions", &self.old_peppers.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct HashedPassword {
+    pub hash: String,
+    pub pepper_version: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordCheck {
+    Wrong,
+    Correct,
+    /// Correct, but hashed with other Argon2 parameters or an old pepper. Store a new hash.
+    CorrectOutdated,
+}
+
+/// Hashes and verifies the peppered Argon2id password hashes of the `users` table.
+pub struct PasswordHashing {
+    argon2: Argon2<'static>,
+    pepper: String,
+    pepper_version: i32,
+    old_peppers: HashMap<i32, String>,
+    // Checked for unknown usernames, so they take as long as a wrong password.
+    dummy_hash: String,
+}
+
+impl fmt::Debug for PasswordHashing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordHashing")
+            .field("params", self.argon2.params())
+            .field("pepper_version", &self.pepper_version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PasswordHashing {
+    pub fn new(configuration: &PasswordHashingConfiguration) -> PasswordHashing {
+        let mut password_hashing = PasswordHashing{
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, configuration.params.clone()),
+            pepper: configuration.pepper.clone(),
+            pepper_version: configuration.pepper_version,
+            old_peppers: configuration.old_peppers.clone(),
+            dummy_hash: String::new(),
+        };
+
+        match password_hashing.hash("dummy password") {
+            Ok(dummy_password) => password_hashing.dummy_hash = dummy_password.hash,
+            Err(error) => error!("Hashing the dummy password failed, logins of unknown users will answer faster: {}", error),
+        }
+
+        password_hashing
+    }
+
+    /// Hashes with the current parameters and pepper.
+    pub fn hash(&self, password: &str) -> Result<HashedPassword, String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let pepper_pass = format!("{}{}", password, self.pepper);
+
+        match self.argon2.hash_password(pepper_pass.as_bytes(), &salt) {
+            Ok(hash) => Ok(HashedPassword{ hash: hash.to_string(), pepper_version: self.pepper_version }),
+            Err(error) => Err(format!("Hashing the password failed: {}", error)),
+        }
+    }
+
+    pub fn verify(&self, stored_hash: &str, pepper_version: i32, password: &str) -> Result<PasswordCheck, String> {
+        let pepper = match pepper_version == self.pepper_version {
+            true => &self.pepper,
+            false => match self.old_peppers.get(&pepper_version) {
+                Some(pepper) => pepper,
+                None => {
+                    return Err(format!("Pepper version {} is not configured", pepper_version));
+                }
+            }
+        };
+
+        let parsed_hash = match PasswordHash::new(stored_hash) {
+            Ok(parsed_hash) => parsed_hash,
+            Err(error) => {
+                return Err(format!("Decoding the password hash failed: {}", error));
+            }
+        };
+
+        // The parameters of the stored hash are used, not the configured ones.
+        let pepper_pass = format!("{}{}", password, pepper);
+        if self.argon2.verify_password(pepper_pass.as_bytes(), &parsed_hash).is_err() {
+            return Ok(PasswordCheck::Wrong);
+        }
+
+        match pepper_version == self.pepper_version && self.has_current_parameters(&parsed_hash) {
+            true => Ok(PasswordCheck::Correct),
+            false => Ok(PasswordCheck::CorrectOutdated),
+        }
+    }
+
+    /// Takes as long as verifying a real password, for logins of unknown usernames.
+    pub fn verify_dummy(&self, password: &str) {
+        let _ = self.verify(&self.dummy_hash, self.pepper_version, password);
+    }
+
+    /// `hash` on the blocking threads, an Argon2 hash takes too long for the async executor.
+    pub async fn hash_in_background(self: &Arc<Self>, password: String) -> Result<HashedPassword, String> {
+        let password_hashing = self.clone();
+        match tokio::task::spawn_blocking(move || password_hashing.hash(&password)).await {
+            Ok(hashed_password) => hashed_password,
+            Err(error) => Err(format!("Hashing the password failed: {}", error)),
+        }
+    }
+
+    /// `verify` on the blocking threads.
+    pub async fn verify_in_background(self: &Arc<Self>, stored_hash: String, pepper_version: i32, password: String) -> Result<PasswordCheck, String> {
+        let password_hashing = self.clone();
+        match tokio::task::spawn_blocking(move || password_hashing.verify(&stored_hash, pepper_version, &password)).await {
+            Ok(password_check) => password_check,
+            Err(error) => Err(format!("Verifying the password failed: {}", error)),
+        }
+    }
+
+    /// `verify_dummy` on the blocking threads.
+    pub async fn verify_dummy_in_background(self: &Arc<Self>, password: String) {
+        let password_hashing = self.clone();
+        if let Err(error) = tokio::task::spawn_blocking(move || password_hashing.verify_dummy(&password)).await {
+            error!("Verifying the dummy password failed: {}", error);
+        }
+    }
+
+    fn has_current_parameters(&self, parsed_hash: &PasswordHash) -> bool {
+        let current_params = self.argon2.params();
+        let is_current_algorithm = parsed_hash.algorithm == Algorithm::Argon2id.ident() && parsed_hash.version == Some(Version::V0x13.into());
+
+        match Params::try_from(parsed_hash) {
+            Ok(params) => is_current_algorithm
+                && params.m_cost() == current_params.m_cost()
+                && params.t_cost() == current_params.t_cost()
+                && params.p_cost() == current_params.p_cost(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configuration(memory_cost: u32, pepper_version: i32) -> PasswordHashingConfiguration {
+        PasswordHashingConfiguration{
+            params: Params::new(memory_cost, 1, 1, None).unwrap(),
+            pepper: format!("pepper {}", pepper_version),
+            pepper_version,
+            old_peppers: (1..pepper_version).map(|old_version| (old_version, format!("pepper {}", old_version))).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn hashes_verify_in_the_background() {
+        let password_hashing = Arc::new(PasswordHashing::new(&configuration(8, 1)));
+
+        let hashed_password = password_hashing.hash_in_background("pass1".to_string()).await.unwrap();
+        assert_eq!(hashed_password.pepper_version, 1);
+
+        let correct = password_hashing.verify_in_background(hashed_password.hash.clone(), 1, "pass1".to_string()).await;
+        let wrong = password_hashing.verify_in_background(hashed_password.hash, 1, "pass2".to_string()).await;
+        assert_eq!(correct, Ok(PasswordCheck::Correct));
+        assert_eq!(wrong, Ok(PasswordCheck::Wrong));
+
+        password_hashing.verify_dummy_in_background("pass1".to_string()).await;
+    }
+
+    #[test]
+    fn old_peppers_and_parameters_are_outdated() {
+        let old_hash = PasswordHashing::new(&configuration(8, 1)).hash("pass1").unwrap();
+
+        let new_pepper = PasswordHashing::new(&configuration(8, 2));
+        assert_eq!(new_pepper.verify(&old_hash.hash, 1, "pass1"), Ok(PasswordCheck::CorrectOutdated));
+        assert_eq!(new_pepper.verify(&old_hash.hash, 1, "pass2"), Ok(PasswordCheck::Wrong));
+
+        let new_parameters = PasswordHashing::new(&configuration(16, 1));
+        assert_eq!(new_parameters.verify(&old_hash.hash, 1, "pass1"), Ok(PasswordCheck::CorrectOutdated));
+    }
+
+    #[test]
+    fn unknown_peppers_are_errors() {
+        let password_hashing = PasswordHashing::new(&configuration(8, 1));
+        let hashed_password = password_hashing.hash("pass1").unwrap();
+
+        assert!(password_hashing.verify(&hashed_password.hash, 3, "pass1").is_err());
+        assert!(password_hashing.verify("not a hash", 1, "pass1").is_err());
+    }
+}
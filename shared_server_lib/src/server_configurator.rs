@@ -1,15 +1,46 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
 use std::fs;
-use std::net::SocketAddr;
-use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use tracing::level_filters::LevelFilter;
 
-#[derive(Debug, Deserialize)]
+use crate::database_migrations::MigrationMode;
+use crate::input_validation::InputLimits;
+use crate::login_lockout::LoginLockoutConfiguration;
+use crate::password_hashing::PasswordHashingConfiguration;
+use crate::server_database::DatabasePoolConfiguration;
+use crate::server_listener::TlsConfiguration;
+use crate::server_logging::LogFormat;
+use crate::server_rate_limit::{RateLimit, RateLimitGroup};
+use crate::session_tokens::SessionTokenConfiguration;
+
+const DEFAULT_CONFIGURATION_FILE_PATH: &str = "../server.conf";
+const CONFIGURATION_PATH_ENVIRONMENT_VARIABLE: &str = "CYBER_CRUSH_CONFIG";
+const ENVIRONMENT_VARIABLE_PREFIX: &str = "CYBER_CRUSH_";
+const DATABASE_TITLE_COLUMN_LENGTH: usize = 64;
+const MIN_SESSION_TOKEN_SIGNING_KEY_LENGTH: usize = 32;
+// Shipped in server.conf before the key had to be set for each deployment, anyone can sign tokens with it.
+const PLACEHOLDER_SESSION_TOKEN_SIGNING_KEYS: [&str; 1] = ["CHANGE_THIS_SESSION_TOKEN_SIGNING_KEY"];
+
+#[derive(Debug)]
 pub struct ServerConfiguration {
     pub database_name: String,
     database_admin_username: String,
     database_admin_password: String,
     database_url: String,
-    pub database_password_pepper: String,
-    server_address: String,
+    database_port: u16,
+    pub database_migration_mode: MigrationMode,
+    pub password_hashing: PasswordHashingConfiguration,
+    pub database_connect_attempts: u32,
+    pub session_tokens: SessionTokenConfiguration,
+    pub login_lockout: LoginLockoutConfiguration,
+    database_pools: HashMap<&'static str, DatabasePoolConfiguration>,
+    server_address: IpAddr,
     authentication_server_port: u16,
     data_server_port: u16,
     bank_server_port: u16,
@@ -17,8 +48,21 @@ pub struct ServerConfiguration {
     news_server_port: u16,
     game_state_server_port: u16,
     hacking_server_port: u16,
+    launcher_server_port: u16,
+    pub launcher_mode: LauncherMode,
+    pub launcher_game_state_tui: bool,
+    pub tls: Option<TlsConfiguration>,
+    pub cors_allowed_origins: Vec<String>,
+    pub input_limits: InputLimits,
+    pub response_compatibility_mode: bool,
+    rate_limits: HashMap<&'static str, RateLimit>,
+    pub rate_limit_trust_forwarded_for: bool,
+    pub log_level: LevelFilter,
+    pub log_format: LogFormat,
+    pub log_file: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum ServerType {
     Authentication,
     Data,
@@ -27,36 +71,649 @@ pub enum ServerType {
     News,
     GameState,
     Hacking,
+    Launcher,
+}
+
+/// How the all-in-one launcher exposes the servers it hosts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LauncherMode {
+    /// Every server on `launcher_server_port`, under a path prefix named after it, e.g. `/bank/transfer_funds`.
+    SinglePort,
+    /// Every server on its own `<server>_server_port`, like the separate binaries.
+    ServerPorts,
+}
+
+impl FromStr for LauncherMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "single_port" => Ok(LauncherMode::SinglePort),
+            "server_ports" => Ok(LauncherMode::ServerPorts),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigurationErrors {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigurationErrors {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(formatter, "{} configuration problem(s) found:", self.errors.len())?;
+        for error in &self.errors {
+            writeln!(formatter, " - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ConfigurationSource {
+    File,
+    Environment,
+    CommandLine,
+}
+
+struct ConfigurationLayers {
+    file_values: Map<String, Value>,
+    command_line_values: HashMap<String, String>,
+    used_command_line_keys: HashSet<String>,
+    errors: Vec<String>,
 }
 
 impl ServerConfiguration {
     pub fn get_postgres_connection_url(&self) -> String {
-        format!("postgres://{}:{}@{}/{}", self.database_admin_username, self.database_admin_password, self.database_url, self.database_name)
+        format!("postgres://{}:{}@{}:{}/{}", self.database_admin_username, self.database_admin_password, self.database_url, self.database_port, self.database_name)
+    }
+
+    /// Reads the configuration from the process arguments. See [`ServerConfiguration::load_from_arguments`].
+    pub fn load() -> Result<ServerConfiguration, ConfigurationErrors> {
+        Self::load_from_arguments(env::args().skip(1))
     }
 
-    pub fn load(server_configuration_file_path: &str) -> ServerConfiguration {
-        let configuration_data = fs::read_to_string(server_configuration_file_path).expect("Failed to load configuration data");
-        let server_config: ServerConfiguration = match serde_json::from_str(&configuration_data) {
-            Ok(config) => config,
+    /// Merges the configuration file, `CYBER_CRUSH_*` environment variables and `--field_name value` flags,
+    /// in that order of precedence. The file path comes from `--config`, `CYBER_CRUSH_CONFIG` or defaults to `../server.conf`.
+    pub fn load_from_arguments<I: IntoIterator<Item = String>>(arguments: I) -> Result<ServerConfiguration, ConfigurationErrors> {
+        let mut errors = vec![];
+        let mut command_line_values = parse_command_line(arguments, &mut errors);
+
+        let explicit_path = command_line_values.remove("config").or_else(|| env::var(CONFIGURATION_PATH_ENVIRONMENT_VARIABLE).ok());
+        let file_values = match &explicit_path {
+            Some(path) => read_configuration_file(path, true, &mut errors),
+            None => read_configuration_file(DEFAULT_CONFIGURATION_FILE_PATH, false, &mut errors),
+        };
+
+        let mut layers = ConfigurationLayers{ file_values, command_line_values, used_command_line_keys: HashSet::new(), errors };
+
+        let server_configuration = ServerConfiguration {
+            database_name: layers.required_string("database_name"),
+            database_admin_username: layers.required_string("database_admin_username"),
+            database_admin_password: layers.required_string("database_admin_password"),
+            database_url: layers.required_string("database_url"),
+            database_port: layers.required_parsed("database_port", "port number", 5432),
+            database_migration_mode: layers.optional_parsed("database_migration_mode", "migration mode (apply or verify)", MigrationMode::Apply),
+            password_hashing: layers.password_hashing(),
+            database_connect_attempts: layers.optional_parsed("database_connect_attempts", "positive number", 10),
+            database_pools: layers.database_pools(),
+            session_tokens: layers.session_tokens(),
+            login_lockout: layers.login_lockout(),
+            server_address: layers.required_parsed("server_address", "IP address", IpAddr::from([0, 0, 0, 0])),
+            authentication_server_port: layers.required_parsed("authentication_server_port", "port number", 0),
+            data_server_port: layers.required_parsed("data_server_port", "port number", 0),
+            bank_server_port: layers.required_parsed("bank_server_port", "port number", 0),
+            chat_server_port: layers.required_parsed("chat_server_port", "port number", 0),
+            news_server_port: layers.required_parsed("news_server_port", "port number", 0),
+            game_state_server_port: layers.required_parsed("game_state_server_port", "port number", 0),
+            hacking_server_port: layers.required_parsed("hacking_server_port", "port number", 0),
+            launcher_server_port: layers.optional_parsed("launcher_server_port", "port number", 3100),
+            launcher_mode: layers.optional_parsed("launcher_mode", "launcher mode (single_port or server_ports)", LauncherMode::SinglePort),
+            launcher_game_state_tui: layers.optional_parsed("launcher_game_state_tui", "boolean", true),
+            tls: layers.tls(),
+            cors_allowed_origins: layers.optional_string_list("cors_allowed_origins"),
+            input_limits: InputLimits {
+                max_username_length: layers.limit("max_username_length", None, 1, usize::MAX),
+                max_password_length: layers.limit("max_password_length", None, 1, usize::MAX),
+                max_extra_data_length: layers.limit("max_extra_data_length", None, 1, usize::MAX),
+                max_group_chat_members: layers.limit("max_group_chat_members", None, 2, usize::MAX),
+                max_chat_message_length: layers.limit("max_chat_message_length", Some(2048), 1, usize::MAX),
+                max_transfer_message_length: layers.limit("max_transfer_message_length", Some(256), 1, usize::MAX),
+                max_title_length: layers.limit("max_title_length", Some(DATABASE_TITLE_COLUMN_LENGTH), 1, DATABASE_TITLE_COLUMN_LENGTH),
+                max_device_label_length: layers.limit("max_device_label_length", Some(64), 1, usize::MAX),
+                max_cyber_defence_level: layers.limit("max_cyber_defence_level", Some(10), 1, i32::MAX as usize),
+            },
+            response_compatibility_mode: layers.optional_parsed("response_compatibility_mode", "boolean", false),
+            rate_limits: layers.rate_limits(),
+            rate_limit_trust_forwarded_for: layers.optional_parsed("rate_limit_trust_forwarded_for", "boolean", false),
+            log_level: layers.optional_parsed("log_level", "log level (off, error, warn, info, debug or trace)", LevelFilter::INFO),
+            log_format: layers.optional_parsed("log_format", "log format (text or json)", LogFormat::Text),
+            log_file: layers.optional_string("log_file"),
+        };
+
+        layers.report_unused_command_line_options();
+
+        if layers.errors.is_empty() {
+            Ok(server_configuration)
+        }
+        else {
+            Err(ConfigurationErrors{ errors: layers.errors })
+        }
+    }
+
+    pub fn get_socket_addr(&self, server_type: ServerType) -> SocketAddr {
+        let port = match server_type {
+            ServerType::Authentication => self.authentication_server_port,
+            ServerType::Data => self.data_server_port,
+            ServerType::Bank => self.bank_server_port,
+            ServerType::Chat => self.chat_server_port,
+            ServerType::News => self.news_server_port,
+            ServerType::GameState => self.game_state_server_port,
+            ServerType::Hacking => self.hacking_server_port,
+            ServerType::Launcher => self.launcher_server_port,
+        };
+
+        SocketAddr::new(self.server_address, port)
+    }
+
+    pub fn get_database_pool_configuration(&self, server_type: ServerType) -> DatabasePoolConfiguration {
+        match self.database_pools.get(server_type.name()) {
+            Some(pool_configuration) => *pool_configuration,
+            None => DatabasePoolConfiguration::default(),
+        }
+    }
+
+    pub fn get_rate_limit(&self, group: RateLimitGroup) -> RateLimit {
+        match self.rate_limits.get(group.name()) {
+            Some(rate_limit) => *rate_limit,
+            None => group.default_limit(),
+        }
+    }
+}
+
+impl ServerType {
+    /// Prefix of the per server configuration fields, e.g. `chat` in `chat_database_max_connections`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerType::Authentication => "authentication",
+            ServerType::Data => "data",
+            ServerType::Bank => "bank",
+            ServerType::Chat => "chat",
+            ServerType::News => "news",
+            ServerType::GameState => "game_state",
+            ServerType::Hacking => "hacking",
+            ServerType::Launcher => "launcher",
+        }
+    }
+}
+
+impl ConfigurationLayers {
+    fn lookup(&mut self, key: &str) -> Option<(Value, ConfigurationSource)> {
+        if let Some(value) = self.command_line_values.get(key) {
+            self.used_command_line_keys.insert(key.to_string());
+            return Some((Value::String(value.clone()), ConfigurationSource::CommandLine));
+        }
+
+        if let Ok(value) = env::var(environment_variable_name(key)) {
+            return Some((Value::String(value), ConfigurationSource::Environment));
+        }
+
+        self.file_values.get(key).map(|value| (value.clone(), ConfigurationSource::File))
+    }
+
+    fn required_string(&mut self, key: &str) -> String {
+        match self.lookup(key) {
+            Some((Value::String(value), _)) => value,
+            Some((Value::Number(value), _)) => value.to_string(),
+            Some((_, source)) => {
+                self.errors.push(format!("{} ({}): expected a string", key, source.describe(key)));
+                String::new()
+            },
+            None => {
+                self.errors.push(format!("{}: missing value, set it in the configuration file, {} or --{}", key, environment_variable_name(key), key));
+                String::new()
+            }
+        }
+    }
+
+    fn optional_string(&mut self, key: &str) -> Option<String> {
+        self.lookup(key)?;
+        Some(self.required_string(key))
+    }
+
+    // A JSON array of strings in the file, a comma separated list in environment variables and flags.
+    fn optional_string_list(&mut self, key: &str) -> Vec<String> {
+        match self.lookup(key) {
+            Some((Value::Array(values), source)) => {
+                let strings = values.iter().filter_map(|value| value.as_str()).map(str::to_string).collect::<Vec<_>>();
+                if strings.len() != values.len() {
+                    self.errors.push(format!("{} ({}): expected a list of strings", key, source.describe(key)));
+                }
+                strings
+            },
+            Some((Value::String(value), _)) => {
+                value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+            },
+            Some((_, source)) => {
+                self.errors.push(format!("{} ({}): expected a list of strings", key, source.describe(key)));
+                Vec::new()
+            },
+            None => Vec::new(),
+        }
+    }
+
+    fn required_parsed<T: FromStr>(&mut self, key: &str, expected: &str, placeholder: T) -> T {
+        self.parsed(key, expected).unwrap_or(placeholder)
+    }
+
+    // Records an error and returns None when the value is missing or does not parse.
+    fn parsed<T: FromStr>(&mut self, key: &str, expected: &str) -> Option<T> {
+        let (raw_value, source) = match self.lookup(key) {
+            Some((Value::String(value), source)) => (value, source),
+            Some((Value::Number(value), source)) => (value.to_string(), source),
+            Some((Value::Bool(value), source)) => (value.to_string(), source),
+            Some((_, source)) => {
+                self.errors.push(format!("{} ({}): expected a {}", key, source.describe(key), expected));
+                return None;
+            },
+            None => {
+                self.errors.push(format!("{}: missing value, set it in the configuration file, {} or --{}", key, environment_variable_name(key), key));
+                return None;
+            }
+        };
+
+        match raw_value.trim().parse::<T>() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.errors.push(format!("{} ({}): \"{}\" is not a valid {}", key, source.describe(key), raw_value, expected));
+                None
+            }
+        }
+    }
+
+    fn optional_parsed<T: FromStr>(&mut self, key: &str, expected: &str, default: T) -> T {
+        if self.lookup(key).is_none() {
+            return default;
+        }
+
+        self.required_parsed(key, expected, default)
+    }
+
+    fn limit(&mut self, key: &str, default: Option<usize>, minimum: usize, maximum: usize) -> usize {
+        let value = match default {
+            Some(default) => self.optional_parsed(key, "positive number", default),
+            None => self.required_parsed(key, "positive number", minimum),
+        };
+
+        if value < minimum {
+            self.errors.push(format!("{}: {} is below the minimum of {}", key, value, minimum));
+        }
+        else if value > maximum {
+            self.errors.push(format!("{}: {} is above the maximum of {}", key, value, maximum));
+        }
+
+        value
+    }
+
+    // Every field can be set for all servers (`database_max_connections`) and overridden for one (`chat_database_max_connections`).
+    fn database_pools(&mut self) -> HashMap<&'static str, DatabasePoolConfiguration> {
+        let defaults = DatabasePoolConfiguration::default();
+        let shared = self.database_pool("", defaults);
+
+        let server_types = [ServerType::Authentication, ServerType::Data, ServerType::Bank, ServerType::Chat, ServerType::News, ServerType::Hacking, ServerType::Launcher];
+        server_types.iter()
+            .map(|server_type| (server_type.name(), self.database_pool(&format!("{}_", server_type.name()), shared)))
+            .collect()
+    }
+
+    fn database_pool(&mut self, prefix: &str, defaults: DatabasePoolConfiguration) -> DatabasePoolConfiguration {
+        let max_connections_key = format!("{}database_max_connections", prefix);
+        let max_connections = self.optional_parsed(&max_connections_key, "positive number", defaults.max_connections);
+        if max_connections < 1 {
+            self.errors.push(format!("{}: {} is below the minimum of 1", max_connections_key, max_connections));
+        }
+
+        DatabasePoolConfiguration {
+            max_connections,
+            acquire_timeout: self.optional_duration(&format!("{}database_acquire_timeout_seconds", prefix), Duration::from_secs, defaults.acquire_timeout),
+            idle_timeout: self.optional_duration(&format!("{}database_idle_timeout_seconds", prefix), Duration::from_secs, defaults.idle_timeout),
+            statement_timeout: self.optional_duration(&format!("{}database_statement_timeout_milliseconds", prefix), Duration::from_millis, defaults.statement_timeout),
+        }
+    }
+
+    fn tls(&mut self) -> Option<TlsConfiguration> {
+        match (self.optional_string("tls_certificate_path"), self.optional_string("tls_private_key_path")) {
+            (Some(certificate_path), Some(private_key_path)) => Some(TlsConfiguration{ certificate_path, private_key_path }),
+            (None, None) => None,
+            _ => {
+                self.errors.push("tls_certificate_path and tls_private_key_path: set both to enable TLS, or neither".to_string());
+                None
+            }
+        }
+    }
+
+    fn session_tokens(&mut self) -> SessionTokenConfiguration {
+        let signing_key = self.required_string("session_token_signing_key");
+        if PLACEHOLDER_SESSION_TOKEN_SIGNING_KEYS.contains(&signing_key.as_str()) {
+            self.errors.push("session_token_signing_key: replace the placeholder with a secret random key, e.g. from `openssl rand -hex 32`".to_string());
+        }
+        else if signing_key.len() < MIN_SESSION_TOKEN_SIGNING_KEY_LENGTH && self.lookup("session_token_signing_key").is_some() {
+            self.errors.push(format!("session_token_signing_key: must be at least {} characters long", MIN_SESSION_TOKEN_SIGNING_KEY_LENGTH));
+        }
+
+        let lifetime = self.optional_duration("session_token_lifetime_minutes", |minutes| Duration::from_secs(minutes.saturating_mul(60)), Duration::from_secs(24 * 60 * 60));
+        if lifetime.is_zero() {
+            self.errors.push("session_token_lifetime_minutes: 0 is below the minimum of 1".to_string());
+        }
+
+        let hack_lifetime = self.optional_duration("hack_token_lifetime_minutes", |minutes| Duration::from_secs(minutes.saturating_mul(60)), Duration::from_secs(15 * 60));
+        if hack_lifetime.is_zero() {
+            self.errors.push("hack_token_lifetime_minutes: 0 is below the minimum of 1".to_string());
+        }
+
+        let impersonation_lifetime = self.optional_duration("impersonation_token_lifetime_minutes", |minutes| Duration::from_secs(minutes.saturating_mul(60)), Duration::from_secs(30 * 60));
+        if impersonation_lifetime.is_zero() {
+            self.errors.push("impersonation_token_lifetime_minutes: 0 is below the minimum of 1".to_string());
+        }
+
+        // Realtime chat sockets re-check their session at this interval, which can not be zero.
+        let revocation_refresh_interval = self.optional_duration("session_token_revocation_refresh_seconds", Duration::from_secs, Duration::from_secs(5));
+        if revocation_refresh_interval.is_zero() {
+            self.errors.push("session_token_revocation_refresh_seconds: 0 is below the minimum of 1".to_string());
+        }
+
+        let legacy_tokens_accepted_until = self.lookup("legacy_tokens_accepted_until")
+            .map(|_| self.required_parsed("legacy_tokens_accepted_until", "RFC 3339 date, e.g. 2025-06-01T12:00:00Z", DateTime::<Utc>::UNIX_EPOCH));
+
+        SessionTokenConfiguration {
+            signing_key,
+            lifetime,
+            hack_lifetime,
+            impersonation_lifetime,
+            revocation_refresh_interval,
+            legacy_tokens_accepted_until,
+        }
+    }
+
+    fn password_hashing(&mut self) -> PasswordHashingConfiguration {
+        let defaults = argon2::Params::default();
+        let memory_kib = self.optional_parsed("password_hash_memory_kib", "positive number", defaults.m_cost());
+        let iterations = self.optional_parsed("password_hash_iterations", "positive number", defaults.t_cost());
+        let parallelism = self.optional_parsed("password_hash_parallelism", "positive number", defaults.p_cost());
+
+        let params = match argon2::Params::new(memory_kib, iterations, parallelism, None) {
+            Ok(params) => params,
             Err(error) => {
-                panic!("Error: Reading server configuration failed: {}", error);
+                self.errors.push(format!("password_hash_memory_kib, password_hash_iterations and password_hash_parallelism: {}", error));
+                defaults
             }
         };
 
-        return server_config;
+        let pepper_version = self.optional_parsed("database_password_pepper_version", "number", 1);
+        let mut old_peppers = HashMap::new();
+        for old_pepper in self.optional_string_list("database_password_old_peppers") {
+            match old_pepper.split_once(':').and_then(|(version, pepper)| Some((version.trim().parse::<i32>().ok()?, pepper))) {
+                Some((version, _)) if version == pepper_version => {
+                    self.errors.push(format!("database_password_old_peppers: version {} is the version of database_password_pepper", version));
+                },
+                Some((version, pepper)) => {
+                    old_peppers.insert(version, pepper.to_string());
+                },
+                None => {
+                    self.errors.push("database_password_old_peppers: expected \"version:pepper\" entries, e.g. \"1:OLD_PEPPER\"".to_string());
+                }
+            }
+        }
+
+        PasswordHashingConfiguration {
+            params,
+            pepper: self.required_string("database_password_pepper"),
+            pepper_version,
+            old_peppers,
+        }
     }
 
-    pub fn get_socket_addr(&self, server_type: ServerType) -> SocketAddr {
-        let addr_str = match server_type {
-            ServerType::Authentication => format!("{}:{}", self.server_address, self.authentication_server_port),
-            ServerType::Data => format!("{}:{}", self.server_address, self.data_server_port),
-            ServerType::Bank => format!("{}:{}", self.server_address, self.bank_server_port),
-            ServerType::Chat => format!("{}:{}", self.server_address, self.chat_server_port),
-            ServerType::News => format!("{}:{}", self.server_address, self.news_server_port),
-            ServerType::GameState => format!("{}:{}", self.server_address, self.game_state_server_port),
-            ServerType::Hacking => format!("{}:{}", self.server_address, self.hacking_server_port)
+    fn login_lockout(&mut self) -> LoginLockoutConfiguration {
+        let lockout = self.optional_duration("login_lockout_seconds", Duration::from_secs, Duration::from_secs(30));
+        let max_lockout = self.optional_duration("login_lockout_max_seconds", Duration::from_secs, Duration::from_secs(60 * 60));
+        if max_lockout < lockout {
+            self.errors.push("login_lockout_max_seconds: must not be below login_lockout_seconds".to_string());
+        }
+
+        LoginLockoutConfiguration {
+            username_failures: self.optional_parsed("login_lockout_username_failures", "positive number", 5),
+            ip_failures: self.optional_parsed("login_lockout_ip_failures", "positive number", 30),
+            lockout,
+            max_lockout,
+        }
+    }
+
+    fn rate_limits(&mut self) -> HashMap<&'static str, RateLimit> {
+        RateLimitGroup::ALL.iter()
+            .map(|group| {
+                let defaults = group.default_limit();
+                let rate_limit = RateLimit{
+                    per_minute: self.optional_parsed(&format!("rate_limit_{}_per_minute", group.name()), "positive number", defaults.per_minute),
+                    burst: self.optional_parsed(&format!("rate_limit_{}_burst", group.name()), "positive number", defaults.burst),
+                };
+                (group.name(), rate_limit)
+            })
+            .collect()
+    }
+
+    // An unparsable value is reported and replaced by the default, so it does not also trip the minimum checks.
+    fn optional_duration(&mut self, key: &str, from_number: fn(u64) -> Duration, default: Duration) -> Duration {
+        if self.lookup(key).is_none() {
+            return default;
+        }
+
+        match self.parsed(key, "whole number") {
+            Some(number) => from_number(number),
+            None => default,
+        }
+    }
+
+    fn report_unused_command_line_options(&mut self) {
+        let mut unused_keys: Vec<&String> = self.command_line_values.keys()
+            .filter(|key| !self.used_command_line_keys.contains(*key))
+            .collect();
+        unused_keys.sort();
+
+        for key in unused_keys {
+            self.errors.push(format!("--{}: unknown command line option", key));
+        }
+    }
+}
+
+impl ConfigurationSource {
+    fn describe(&self, key: &str) -> String {
+        match self {
+            ConfigurationSource::File => "configuration file".into(),
+            ConfigurationSource::Environment => format!("environment variable {}", environment_variable_name(key)),
+            ConfigurationSource::CommandLine => format!("command line option --{}", key),
+        }
+    }
+}
+
+fn environment_variable_name(key: &str) -> String {
+    format!("{}{}", ENVIRONMENT_VARIABLE_PREFIX, key.to_uppercase())
+}
+
+fn parse_command_line<I: IntoIterator<Item = String>>(arguments: I, errors: &mut Vec<String>) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut arguments = arguments.into_iter();
+
+    while let Some(argument) = arguments.next() {
+        let option = match argument.strip_prefix("--") {
+            Some(option) => option,
+            None => {
+                errors.push(format!("{}: unexpected command line argument, options are passed as --field_name value", argument));
+                continue;
+            }
         };
 
-        addr_str.parse().expect("Invalid ip address")
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) => (key.to_string(), Some(value.to_string())),
+            None => (option.to_string(), arguments.next()),
+        };
+
+        let key = key.replace('-', "_");
+        match value {
+            Some(value) => { values.insert(key, value); },
+            None => errors.push(format!("--{}: missing value", key)),
+        }
+    }
+
+    values
+}
+
+fn read_configuration_file(path: &str, is_required: bool, errors: &mut Vec<String>) -> Map<String, Value> {
+    let configuration_data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(error) => {
+            if is_required {
+                errors.push(format!("configuration file {}: {}", path, error));
+            }
+            return Map::new();
+        }
+    };
+
+    match serde_json::from_str::<Value>(&configuration_data) {
+        Ok(Value::Object(values)) => values,
+        Ok(_) => {
+            errors.push(format!("configuration file {}: expected a JSON object", path));
+            Map::new()
+        },
+        Err(error) => {
+            errors.push(format!("configuration file {}: {}", path, error));
+            Map::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn valid_configuration() -> Value {
+        json!({
+            "database_name": "file_database",
+            "database_url": "127.0.0.1",
+            "database_port": 5432,
+            "database_admin_username": "admin",
+            "database_admin_password": "password",
+            "database_password_pepper": "PEPPER",
+            "session_token_signing_key": "test_signing_key_that_is_long_enough",
+            "server_address": "127.0.0.1",
+            "authentication_server_port": 3000,
+            "data_server_port": 3001,
+            "bank_server_port": 3002,
+            "chat_server_port": 3003,
+            "news_server_port": 3004,
+            "game_state_server_port": 3005,
+            "hacking_server_port": 3006,
+            "max_username_length": 16,
+            "max_password_length": 32,
+            "max_extra_data_length": 8192,
+            "max_group_chat_members": 16
+        })
+    }
+
+    fn write_configuration(name: &str, values: &Value) -> String {
+        let path = env::temp_dir().join(format!("cyber_crush_{}_{}.conf", name, std::process::id()));
+        fs::write(&path, values.to_string()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn load(path: &str, flags: &[&str]) -> Result<ServerConfiguration, ConfigurationErrors> {
+        let arguments = ["--config", path].into_iter().chain(flags.iter().copied()).map(str::to_string);
+        ServerConfiguration::load_from_arguments(arguments)
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let path = write_configuration("precedence", &valid_configuration());
+        let variable = environment_variable_name("database_name");
+
+        assert_eq!(load(&path, &[]).unwrap().database_name, "file_database");
+
+        // Only this test touches the variable, so setting it cannot race with the other tests.
+        unsafe { env::set_var(&variable, "environment_database") };
+        let from_environment = load(&path, &[]).unwrap().database_name;
+        let from_flag = load(&path, &["--database_name", "flag_database"]).unwrap().database_name;
+        let from_flag_with_equals = load(&path, &["--database-name=equals_database"]).unwrap().database_name;
+        unsafe { env::remove_var(&variable) };
+
+        assert_eq!(from_environment, "environment_database");
+        assert_eq!(from_flag, "flag_database");
+        assert_eq!(from_flag_with_equals, "equals_database");
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut values = valid_configuration();
+        let object = values.as_object_mut().unwrap();
+        object.remove("database_admin_password");
+        object.insert("data_server_port".into(), json!("not a port"));
+        object.insert("session_token_lifetime_minutes".into(), json!("soon"));
+        object.insert("max_username_length".into(), json!(0));
+        let path = write_configuration("aggregation", &values);
+
+        let errors = load(&path, &["--unknown_option", "1"]).unwrap_err().errors;
+
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors[0].starts_with("database_admin_password: missing value"));
+        assert!(errors[1].starts_with("session_token_lifetime_minutes (configuration file): \"soon\""));
+        assert!(errors[2].starts_with("data_server_port (configuration file): \"not a port\""));
+        assert!(errors[3].starts_with("max_username_length: 0 is below the minimum"));
+        assert!(errors[4].starts_with("--unknown_option: unknown command line option"));
+    }
+
+    #[test]
+    fn unparsable_durations_are_errors() {
+        let mut values = valid_configuration();
+        values["database_acquire_timeout_seconds"] = json!("five");
+        values["session_token_lifetime_minutes"] = json!(-1);
+        let path = write_configuration("durations", &values);
+
+        let errors = load(&path, &[]).unwrap_err().errors;
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.iter().all(|error| error.contains("is not a valid whole number")));
+    }
+
+    #[test]
+    fn the_placeholder_signing_key_is_rejected() {
+        let mut values = valid_configuration();
+        values["session_token_signing_key"] = json!("CHANGE_THIS_SESSION_TOKEN_SIGNING_KEY");
+        let path = write_configuration("placeholder_key", &values);
+
+        let errors = load(&path, &[]).unwrap_err().errors;
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("session_token_signing_key: replace the placeholder"));
+    }
+
+    #[test]
+    fn zero_durations_are_rejected_where_they_can_not_work() {
+        let path = write_configuration("zero_durations", &valid_configuration());
+
+        let errors = load(&path, &["--session_token_revocation_refresh_seconds", "0", "--session_token_lifetime_minutes", "0"]).unwrap_err().errors;
+
+        assert_eq!(errors, vec![
+            "session_token_lifetime_minutes: 0 is below the minimum of 1".to_string(),
+            "session_token_revocation_refresh_seconds: 0 is below the minimum of 1".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn durations_from_flags_are_parsed() {
+        let path = write_configuration("duration_flags", &valid_configuration());
+
+        let configuration = load(&path, &["--session_token_lifetime_minutes", "90"]).unwrap();
+
+        assert_eq!(configuration.session_tokens.lifetime, Duration::from_secs(90 * 60));
     }
 }
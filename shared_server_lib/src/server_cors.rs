@@ -0,0 +1,39 @@
+use axum::{
+    http::{header, HeaderName, HeaderValue, Method},
+    Router,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::error;
+
+/// Lets browsers on the listed origins call the router. `*` allows every origin, an empty list
+/// leaves the router without CORS headers. Apply it last so preflight requests are answered first.
+pub fn apply_cors<S: Clone + Send + Sync + 'static>(router: Router<S>, allowed_origins: &[String]) -> Router<S> {
+    if allowed_origins.is_empty() {
+        return router;
+    }
+
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    }
+    else {
+        let origins = allowed_origins.iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(origin) => Some(origin),
+                Err(error) => {
+                    error!("Skipping invalid CORS origin {}: {}", origin, error);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        AllowOrigin::list(origins)
+    };
+
+    let request_id = HeaderName::from_static("x-request-id");
+    let cors = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, request_id.clone()])
+        .expose_headers([request_id, header::RETRY_AFTER]);
+
+    router.layer(cors)
+}
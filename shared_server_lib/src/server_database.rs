@@ -1,18 +1,103 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::time::Duration;
+use sqlx::{postgres::{PgConnectOptions, PgPoolOptions}, PgPool};
+use tracing::{error, info, warn};
 
-pub async fn connect_to_database(db_url: String) -> PgPool {
-    let db_pool = PgPoolOptions::new()
-        .max_connections(3)
-        .connect(&db_url)
-        .await;
+use crate::database_migrations::{self, MigrationMode};
+use crate::server_configurator::{ServerConfiguration, ServerType};
+use crate::session_tokens;
 
-    let db_pool = match db_pool {
-        Ok(pool) => pool,
+const INITIAL_CONNECT_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Zero idle or statement timeout disables it.
+#[derive(Debug, Clone, Copy)]
+pub struct DatabasePoolConfiguration {
+    pub max_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Duration,
+    pub statement_timeout: Duration,
+}
+
+impl Default for DatabasePoolConfiguration {
+    fn default() -> Self {
+        DatabasePoolConfiguration{
+            max_connections: 5,
+            acquire_timeout: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(600),
+            statement_timeout: Duration::from_secs(5) }
+    }
+}
+
+pub async fn connect_to_database(server_configuration: &ServerConfiguration, server_type: ServerType) -> PgPool {
+    let pool_configuration = server_configuration.get_database_pool_configuration(server_type);
+
+    let connect_options: PgConnectOptions = match server_configuration.get_postgres_connection_url().parse() {
+        Ok(options) => options,
         Err(error) => {
-            panic!("Error: Server did not connect to the database: {}", error);
+            panic!("Error: Invalid database connection settings: {}", error);
+        }
+    };
+    let connect_options = connect_options.options([("statement_timeout", pool_configuration.statement_timeout.as_millis().to_string())]);
+
+    let pool_options = PgPoolOptions::new()
+        .max_connections(pool_configuration.max_connections)
+        .acquire_timeout(pool_configuration.acquire_timeout)
+        .idle_timeout(Some(pool_configuration.idle_timeout).filter(|timeout| !timeout.is_zero()));
+
+    let max_attempts = server_configuration.database_connect_attempts.max(1);
+    let mut retry_delay = INITIAL_CONNECT_RETRY_DELAY;
+    let mut attempt = 1;
+
+    let db_pool = loop {
+        match pool_options.clone().connect_with(connect_options.clone()).await {
+            Ok(pool) => break pool,
+            Err(error) if attempt < max_attempts => {
+                warn!("Connecting to the database failed (attempt {}/{}), retrying in {} ms: {}", attempt, max_attempts, retry_delay.as_millis(), error);
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_CONNECT_RETRY_DELAY);
+                attempt += 1;
+            },
+            Err(error) => {
+                error!("Connecting to the database failed after {} attempts", max_attempts);
+                panic!("Error: Server did not connect to the database: {}", error);
+            }
         }
     };
 
-    println!("Connected to postgres!");
-    return db_pool;
+    info!(
+        max_connections = pool_configuration.max_connections,
+        acquire_timeout_seconds = pool_configuration.acquire_timeout.as_secs(),
+        idle_timeout_seconds = pool_configuration.idle_timeout.as_secs(),
+        statement_timeout_milliseconds = pool_configuration.statement_timeout.as_millis() as u64,
+        "Connected to postgres!");
+
+    let schema_check = match server_configuration.database_migration_mode {
+        MigrationMode::Apply => database_migrations::apply_migrations(&db_pool).await,
+        MigrationMode::Verify => database_migrations::verify_schema_version(&db_pool).await,
+    };
+
+    match schema_check {
+        Ok(version) => info!("Database schema is at version {}", version),
+        Err(error) => {
+            panic!("Error: Database schema check failed: {}", error);
+        }
+    }
+
+    match session_tokens::hash_legacy_tokens(&server_configuration.session_tokens, &db_pool).await {
+        Ok(0) => {},
+        Ok(hashed_count) => info!("Replaced {} plaintext legacy token(s) with their hash", hashed_count),
+        Err(error) => error!("Hashing the legacy tokens failed, their users have to log in again: {}", error),
+    }
+
+    db_pool
+}
+
+/// Pool for tests that must not reach a database. Nothing listens on its address and it connects lazily,
+/// so only a test that runs a query fails, and it fails fast.
+#[cfg(any(test, feature = "test_support"))]
+pub fn unreachable_pool() -> PgPool {
+    PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_lazy("postgres://test@127.0.0.1:1/unreachable")
+        .expect("the unreachable database URL is valid")
 }
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sqlx::PgPool;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::get,
+    Json,
+    Router,
+};
+use tracing::error;
+use utoipa::OpenApi;
+
+use cyber_crush_protocol::health::{DatabaseHealth, LivenessResponse, ReadinessResponse};
+
+const DATABASE_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub struct HealthCheck {
+    server_name: &'static str,
+    version: &'static str,
+    started_at: Instant,
+    db_pool: Option<PgPool>,
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(live, ready))]
+pub(crate) struct HealthApi;
+
+impl HealthCheck {
+    /// Pass `env!("CARGO_PKG_VERSION")` as the version so it matches the server binary.
+    /// Servers without a database pass `None` and are ready as soon as they run.
+    pub fn new(server_name: &'static str, version: &'static str, db_pool: Option<PgPool>) -> HealthCheck {
+        HealthCheck{ server_name, version, started_at: Instant::now(), db_pool }
+    }
+
+    fn uptime_seconds(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+}
+
+/// Adds `/health/live`, answered while the process runs, and `/health/ready`, answered with 503
+/// when the server can not reach its database.
+pub fn apply_health_routes<S: Clone + Send + Sync + 'static>(router: Router<S>, health_check: HealthCheck) -> Router<S> {
+    let health_router = Router::new()
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .with_state(Arc::new(health_check));
+
+    router.merge(health_router)
+}
+
+/// Answered while the process runs.
+#[utoipa::path(get, path = "/health/live", tag = "health", responses((status = 200, body = LivenessResponse)))]
+async fn live(State(health_check): State<Arc<HealthCheck>>) -> Json<LivenessResponse> {
+    Json(LivenessResponse{
+        status: "alive".to_string(),
+        server: health_check.server_name.to_string(),
+        version: health_check.version.to_string(),
+        uptime_seconds: health_check.uptime_seconds() })
+}
+
+/// Pings the database, servers without one are ready while they run.
+#[utoipa::path(get, path = "/health/ready", tag = "health", responses(
+    (status = 200, description = "Ready", body = ReadinessResponse),
+    (status = 503, description = "The database can not be reached", body = ReadinessResponse),
+))]
+async fn ready(State(health_check): State<Arc<HealthCheck>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let database = match &health_check.db_pool {
+        Some(db_pool) => Some(check_database(db_pool).await),
+        None => None,
+    };
+
+    let is_ready = database.as_ref().is_none_or(|database| database.reachable);
+    let (status_code, status) = if is_ready {
+        (StatusCode::OK, "ready")
+    }
+    else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+
+    (status_code, Json(ReadinessResponse{
+        status: status.to_string(),
+        server: health_check.server_name.to_string(),
+        version: health_check.version.to_string(),
+        uptime_seconds: health_check.uptime_seconds(),
+        database }))
+}
+
+async fn check_database(db_pool: &PgPool) -> DatabaseHealth {
+    // Read before the ping, which borrows a connection itself.
+    // A slow acquire next to a fast ping means the pool is too small for the load.
+    let pool_size = db_pool.size();
+    let idle_connections = db_pool.num_idle();
+    let max_connections = db_pool.options().get_max_connections();
+    let busy_connections = (pool_size as usize).saturating_sub(idle_connections);
+
+    let acquire_start = Instant::now();
+    let ping_result = tokio::time::timeout(DATABASE_PING_TIMEOUT, async {
+        let mut connection = db_pool.acquire().await?;
+        let acquire_milliseconds = acquire_start.elapsed().as_millis();
+
+        let ping_start = Instant::now();
+        sqlx::query("SELECT 1").execute(&mut *connection).await?;
+        Ok::<_, sqlx::Error>((acquire_milliseconds, ping_start.elapsed().as_millis()))
+    }).await;
+
+    let timings = match ping_result {
+        Ok(Ok(timings)) => Some(timings),
+        Ok(Err(error)) => {
+            error!("Readiness check database ping failed: {}", error);
+            None
+        },
+        Err(_) => {
+            error!("Readiness check database ping timed out after {} seconds", DATABASE_PING_TIMEOUT.as_secs());
+            None
+        }
+    };
+
+    DatabaseHealth{
+        reachable: timings.is_some(),
+        ping_milliseconds: timings.map(|(_, ping_milliseconds)| ping_milliseconds),
+        acquire_milliseconds: timings.map(|(acquire_milliseconds, _)| acquire_milliseconds),
+        pool_size,
+        idle_connections,
+        busy_connections,
+        max_connections,
+        saturation: busy_connections as f64 / max_connections.max(1) as f64,
+        acquire_timeout_seconds: db_pool.options().get_acquire_timeout().as_secs(),
+        idle_timeout_seconds: db_pool.options().get_idle_timeout().map(|timeout| timeout.as_secs()) }
+}
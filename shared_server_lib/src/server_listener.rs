@@ -0,0 +1,57 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use axum::Router;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::server_configurator::ServerConfiguration;
+
+// Same as the chat connection drain timeout, open connections are cut after it.
+const TLS_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// PEM files of the certificate chain and its private key.
+#[derive(Debug, Clone)]
+pub struct TlsConfiguration {
+    pub certificate_path: String,
+    pub private_key_path: String,
+}
+
+/// Serves `app` over HTTPS/WSS when `tls_certificate_path` and `tls_private_key_path` are configured,
+/// plain HTTP/WS otherwise. Returns once `shutdown_signal` completed and in-flight requests finished.
+pub async fn serve<F>(socket_addr: SocketAddr, app: Router, server_configuration: &ServerConfiguration, shutdown_signal: F) -> io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let tls = match &server_configuration.tls {
+        Some(tls) => tls,
+        None => {
+            let listener = TcpListener::bind(socket_addr).await?;
+            return axum::serve(listener, make_service).with_graceful_shutdown(shutdown_signal).await;
+        }
+    };
+
+    // Another server of the same process may have installed it already.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let rustls_config = match RustlsConfig::from_pem_file(&tls.certificate_path, &tls.private_key_path).await {
+        Ok(rustls_config) => rustls_config,
+        Err(error) => {
+            return Err(io::Error::new(error.kind(), format!("loading TLS certificate {} or key {} failed: {}", tls.certificate_path, tls.private_key_path, error)));
+        }
+    };
+    info!("TLS enabled for {}", socket_addr);
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown_signal.await;
+        shutdown_handle.graceful_shutdown(Some(TLS_SHUTDOWN_GRACE_PERIOD));
+    });
+
+    axum_server::bind_rustls(socket_addr, rustls_config).handle(handle).serve(make_service).await
+}
@@ -0,0 +1,104 @@
+use std::fs::OpenOptions;
+use std::str::FromStr;
+use std::sync::Mutex;
+use axum::{
+    body::Body,
+    http::{HeaderName, Request},
+    Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{Level, Span};
+use tracing_subscriber::fmt;
+
+use crate::server_configurator::ServerConfiguration;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Installs the global tracing subscriber. Logs go to stdout, or are appended to `log_file` when it is set.
+pub fn init_logging(server_configuration: &ServerConfiguration) {
+    let builder = fmt()
+        .with_max_level(server_configuration.log_level)
+        .with_target(false);
+
+    let log_file = match &server_configuration.log_file {
+        Some(path) => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(file),
+            Err(error) => {
+                eprintln!("Error: Opening log file {} failed, logging to stdout: {}", path, error);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let result = match (server_configuration.log_format, log_file) {
+        (LogFormat::Text, None) => builder.try_init(),
+        (LogFormat::Json, None) => builder.json().try_init(),
+        (LogFormat::Text, Some(file)) => builder.with_ansi(false).with_writer(Mutex::new(file)).try_init(),
+        (LogFormat::Json, Some(file)) => builder.json().with_writer(Mutex::new(file)).try_init(),
+    };
+
+    if let Err(error) = result {
+        eprintln!("Error: Initializing logging failed: {}", error);
+    }
+}
+
+/// Wraps every request in a span carrying its request ID. The ID is taken from the `x-request-id` header
+/// when the client sends one, so an action can be followed across servers, and is returned in the response.
+/// Applied once per listener by the caller of a server's `create_router`, like `common::apply_response_compatibility_mode`,
+/// so the launcher does not trace a request twice when one listener serves several servers.
+pub fn apply_request_tracing<S: Clone + Send + Sync + 'static>(router: Router<S>) -> Router<S> {
+    let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+
+    router.layer(ServiceBuilder::new()
+        .layer(SetRequestIdLayer::new(request_id_header.clone(), MakeRequestUuid))
+        .layer(TraceLayer::new_for_http()
+            .make_span_with(make_request_span)
+            .on_response(DefaultOnResponse::new().level(Level::INFO)))
+        .layer(PropagateRequestIdLayer::new(request_id_header)))
+}
+
+/// Attaches the authenticated user to the current request span.
+pub fn record_user_id(user_id: i32) {
+    Span::current().record("user_id", user_id);
+}
+
+/// Attaches the game master behind an impersonation token to the current request span.
+pub fn record_impersonated_by(game_master_id: i32) {
+    Span::current().record("impersonated_by", game_master_id);
+}
+
+fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request.headers().get(REQUEST_ID_HEADER)
+        .and_then(|request_id| request_id.to_str().ok())
+        .unwrap_or_default();
+
+    tracing::info_span!("request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        user_id = tracing::field::Empty,
+        impersonated_by = tracing::field::Empty)
+}
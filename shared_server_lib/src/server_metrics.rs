@@ -0,0 +1,140 @@
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::OpenApi;
+
+const LATENCY_BUCKETS_SECONDS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// One recorder per process, servers sharing a process tell their metrics apart by the `server` label.
+static PROMETHEUS_HANDLE: OnceLock<Option<PrometheusHandle>> = OnceLock::new();
+
+#[derive(OpenApi)]
+#[openapi(paths(render_metrics))]
+pub(crate) struct MetricsApi;
+
+#[derive(Debug, Clone)]
+struct MetricsState {
+    server_name: &'static str,
+    db_pool: Option<PgPool>,
+}
+
+fn prometheus_handle() -> Option<&'static PrometheusHandle> {
+    PROMETHEUS_HANDLE.get_or_init(|| {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets(LATENCY_BUCKETS_SECONDS)
+            .and_then(|builder| builder.install_recorder());
+
+        match recorder {
+            Ok(handle) => Some(handle),
+            Err(error) => {
+                error!("Installing the metrics recorder failed: {}", error);
+                None
+            }
+        }
+    }).as_ref()
+}
+
+/// Adds `/metrics` in the Prometheus text format and counts every request of the router by route and status.
+/// Apply it before `common::apply_response_compatibility_mode` so the real status codes are recorded.
+pub fn apply_metrics<S: Clone + Send + Sync + 'static>(router: Router<S>, server_name: &'static str, db_pool: Option<PgPool>) -> Router<S> {
+    prometheus_handle();
+
+    let metrics_router = Router::new()
+        .route("/metrics", get(render_metrics))
+        .with_state(MetricsState{ server_name, db_pool });
+
+    router
+        .merge(metrics_router)
+        .layer(middleware::from_fn_with_state(server_name, track_request))
+}
+
+async fn track_request(State(server_name): State<&'static str>, request: Request, next: Next) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let method = request.method().to_string();
+
+    let request_start = Instant::now();
+    let response = next.run(request).await;
+
+    let labels = [("server", server_name.to_string()), ("method", method), ("route", route), ("status", response.status().as_u16().to_string())];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels[..3]).record(request_start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Every metric of the process in the Prometheus text format.
+#[utoipa::path(get, path = "/metrics", tag = "metrics", responses(
+    (status = 200, content_type = "text/plain", body = String),
+    (status = 503, description = "The metrics recorder could not be installed", body = String),
+))]
+async fn render_metrics(State(metrics_state): State<MetricsState>) -> Response {
+    if let Some(db_pool) = &metrics_state.db_pool {
+        let idle_connections = db_pool.num_idle();
+        let busy_connections = (db_pool.size() as usize).saturating_sub(idle_connections);
+
+        gauge!("database_pool_connections", "server" => metrics_state.server_name, "state" => "idle").set(idle_connections as f64);
+        gauge!("database_pool_connections", "server" => metrics_state.server_name, "state" => "busy").set(busy_connections as f64);
+        gauge!("database_pool_max_connections", "server" => metrics_state.server_name).set(db_pool.options().get_max_connections() as f64);
+    }
+
+    match prometheus_handle() {
+        Some(handle) => {
+            handle.run_upkeep();
+            ([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], handle.render()).into_response()
+        },
+        None => (axum::http::StatusCode::SERVICE_UNAVAILABLE, "Metrics are not available").into_response(),
+    }
+}
+
+pub trait TimedQuery: Future + Sized {
+    /// Records how long the query took in `database_query_duration_seconds`, labeled with `query_name`.
+    fn timed(self, query_name: &'static str) -> impl Future<Output = Self::Output> + Send where Self: Send {
+        async move {
+            let query_start = Instant::now();
+            let output = self.await;
+            histogram!("database_query_duration_seconds", "query" => query_name).record(query_start.elapsed().as_secs_f64());
+            output
+        }
+    }
+}
+
+impl<F: Future> TimedQuery for F {}
+
+pub fn record_login(succeeded: bool) {
+    let result = if succeeded { "succeeded" } else { "failed" };
+    counter!("logins_total", "result" => result).increment(1);
+}
+
+pub fn record_funds_transfer(amount: u32) {
+    counter!("fund_transfers_total").increment(1);
+    counter!("funds_transferred_total").increment(u64::from(amount));
+}
+
+pub fn record_chat_message_sent() {
+    counter!("chat_messages_sent_total").increment(1);
+}
+
+pub fn set_active_chat_connections(chat_id: i32, connection_count: usize) {
+    gauge!("chat_active_websocket_connections", "chat_id" => chat_id.to_string()).set(connection_count as f64);
+}
+
+pub fn record_hack_attempt(successful: bool) {
+    let result = if successful { "successful" } else { "failed" };
+    counter!("hack_attempts_total", "result" => result).increment(1);
+}
@@ -0,0 +1,100 @@
+use axum::{
+    body::Bytes,
+    http::header,
+    routing::get,
+    Router,
+};
+use tracing::error;
+use utoipa::{
+    openapi::{self, path::{Operation, PathItem}, security::{HttpAuthScheme, HttpBuilder, SecurityScheme}, OpenApiBuilder},
+    Modify,
+    OpenApi,
+};
+
+use crate::{common::{ErrorResponse, ResponseStatus}, server_health::HealthApi, server_metrics::MetricsApi};
+
+#[derive(OpenApi)]
+#[openapi(components(schemas(ResponseStatus, ErrorResponse)), modifiers(&TokenSecurity))]
+struct SharedApi;
+
+struct TokenSecurity;
+
+impl Modify for TokenSecurity {
+    fn modify(&self, api: &mut openapi::OpenApi) {
+        let token_security = HttpBuilder::new()
+            .scheme(HttpAuthScheme::Bearer)
+            .description(Some("Token returned by /login. Older clients may send it as the `token` field of a JSON body instead."))
+            .build();
+
+        api.components.get_or_insert_with(Default::default).add_security_scheme("token", SecurityScheme::Http(token_security));
+    }
+}
+
+/// Completes a server's generated document with its title and version and the parts every server shares:
+/// the `token` security scheme, the `ErrorResponse` body, the health checks and `/metrics`.
+/// Endpoints that need a login token declare `security(("token" = []))`.
+pub fn server_openapi(server_name: &str, version: &str, mut api: openapi::OpenApi) -> openapi::OpenApi {
+    api.info.title = format!("CyberCrush {} server", server_name);
+    api.info.version = version.to_string();
+
+    api.merge(SharedApi::openapi());
+    api.merge(HealthApi::openapi());
+    api.merge(MetricsApi::openapi());
+
+    // Routes answering both GET and POST get one operation ID from their handler, client generators need two.
+    for path_item in api.paths.paths.values_mut() {
+        let mut operations = path_operations(path_item).into_iter().filter_map(|(method, operation)| Some((method, operation.as_mut()?))).collect::<Vec<_>>();
+        if operations.len() < 2 {
+            continue;
+        }
+
+        for (method, operation) in operations.iter_mut() {
+            if let Some(operation_id) = operation.operation_id.as_mut() {
+                *operation_id = format!("{}_{}", operation_id, method);
+            }
+        }
+    }
+
+    api
+}
+
+/// One document for several servers sharing a listener, with the paths of every server document
+/// under `/<server_name>` like `Router::nest` puts them, and its operation IDs prefixed with
+/// `<server_name>_` so they stay unique. The health checks of the listener itself stay at the root.
+pub fn nested_openapi(title: &str, version: &str, server_apis: Vec<(&str, openapi::OpenApi)>) -> openapi::OpenApi {
+    let mut api = OpenApiBuilder::new().build();
+    api.info.title = title.to_string();
+    api.info.version = version.to_string();
+    api.merge(SharedApi::openapi());
+    api.merge(HealthApi::openapi());
+
+    server_apis.into_iter().fold(api, |api, (server_name, mut server_api)| {
+        for path_item in server_api.paths.paths.values_mut() {
+            for (_, operation) in path_operations(path_item) {
+                if let Some(operation_id) = operation.as_mut().and_then(|operation| operation.operation_id.as_mut()) {
+                    *operation_id = format!("{}_{}", server_name, operation_id);
+                }
+            }
+        }
+
+        api.nest(format!("/{}", server_name), server_api)
+    })
+}
+
+fn path_operations(path_item: &mut PathItem) -> [(&'static str, &mut Option<Operation>); 8] {
+    [("get", &mut path_item.get), ("put", &mut path_item.put), ("post", &mut path_item.post), ("delete", &mut path_item.delete),
+     ("options", &mut path_item.options), ("head", &mut path_item.head), ("patch", &mut path_item.patch), ("trace", &mut path_item.trace)]
+}
+
+/// Adds `/openapi.json` answering with `api`, serialized once.
+pub fn apply_openapi_route<S: Clone + Send + Sync + 'static>(router: Router<S>, api: &openapi::OpenApi) -> Router<S> {
+    let api_json = match api.to_pretty_json() {
+        Ok(api_json) => Bytes::from(api_json),
+        Err(error) => {
+            error!("Serializing the OpenAPI document of {} failed: {}", api.info.title, error);
+            return router;
+        }
+    };
+
+    router.route("/openapi.json", get(move || async move { ([(header::CONTENT_TYPE, "application/json")], api_json) }))
+}
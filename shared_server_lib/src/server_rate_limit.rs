@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{request::Parts, Extensions, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use metrics::counter;
+use tower::{Layer, Service};
+
+use crate::{authentication, common::ServerError, server_configurator::ServerConfiguration, session_tokens::SessionTokenVerifier};
+
+// Buckets that refilled completely are dropped once this many keys are tracked.
+const BUCKET_PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitGroup {
+    Login,
+    Transfer,
+    ChatSocket,
+    ChatMessage,
+    Default,
+}
+
+impl RateLimitGroup {
+    pub const ALL: [RateLimitGroup; 5] = [RateLimitGroup::Login, RateLimitGroup::Transfer, RateLimitGroup::ChatSocket, RateLimitGroup::ChatMessage, RateLimitGroup::Default];
+
+    /// Part of the configuration fields, e.g. `login` in `rate_limit_login_per_minute`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RateLimitGroup::Login => "login",
+            RateLimitGroup::Transfer => "transfer",
+            RateLimitGroup::ChatSocket => "chat_socket",
+            RateLimitGroup::ChatMessage => "chat_message",
+            RateLimitGroup::Default => "default",
+        }
+    }
+
+    pub fn default_limit(&self) -> RateLimit {
+        match self {
+            RateLimitGroup::Login => RateLimit{ per_minute: 10, burst: 5 },
+            RateLimitGroup::Transfer => RateLimit{ per_minute: 20, burst: 5 },
+            RateLimitGroup::ChatSocket => RateLimit{ per_minute: 30, burst: 10 },
+            RateLimitGroup::ChatMessage => RateLimit{ per_minute: 60, burst: 10 },
+            RateLimitGroup::Default => RateLimit{ per_minute: 600, burst: 100 },
+        }
+    }
+}
+
+/// Token bucket refilled with `per_minute` tokens a minute and holding at most `burst` tokens.
+/// A `per_minute` of 0 disables the limit.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateLimitKey {
+    User(i32),
+    ClientIp(IpAddr),
+    Unknown,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Debug)]
+struct RateLimiter {
+    group: RateLimitGroup,
+    limit: RateLimit,
+    trust_forwarded_for: bool,
+    token_verifier: SessionTokenVerifier,
+    buckets: Mutex<HashMap<RateLimitKey, TokenBucket>>,
+}
+
+/// Limits messages sent over already open connections, e.g. realtime chat sockets, per user.
+/// Every connection of a user takes from the same bucket, so reconnecting does not refill it.
+#[derive(Debug)]
+pub struct UserRateLimiter {
+    group: RateLimitGroup,
+    limit: RateLimit,
+    buckets: Mutex<HashMap<i32, TokenBucket>>,
+}
+
+impl UserRateLimiter {
+    pub fn new(group: RateLimitGroup, limit: RateLimit) -> UserRateLimiter {
+        UserRateLimiter{ group, limit, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn from_configuration(server_configuration: &ServerConfiguration, group: RateLimitGroup) -> UserRateLimiter {
+        UserRateLimiter::new(group, server_configuration.get_rate_limit(group))
+    }
+
+    /// Takes a token from the user's bucket, or returns how long until the next one is available.
+    /// Rejections are counted in `rate_limited_requests_total`.
+    pub fn acquire(&self, user_id: i32) -> Result<(), Duration> {
+        let result = take_token(&self.buckets, user_id, self.limit);
+        if result.is_err() {
+            counter!("rate_limited_requests_total", "group" => self.group.name()).increment(1);
+        }
+
+        result
+    }
+}
+
+/// Limits requests per user when they carry a correctly signed session token, otherwise per client IP,
+/// so made up tokens do not get fresh buckets. Login requests are always limited per client IP.
+/// Rejected requests get a 429 `RATE_LIMITED` error with a `Retry-After` header.
+/// Client IPs are only known when the server is started with `into_make_service_with_connect_info::<SocketAddr>()`.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    /// Every layer has its own buckets, so create one per route group and server.
+    pub fn new(group: RateLimitGroup, limit: RateLimit, trust_forwarded_for: bool, token_verifier: SessionTokenVerifier) -> RateLimitLayer {
+        RateLimitLayer{ limiter: Arc::new(RateLimiter{ group, limit, trust_forwarded_for, token_verifier, buckets: Mutex::new(HashMap::new()) }) }
+    }
+
+    pub fn from_configuration(server_configuration: &ServerConfiguration, group: RateLimitGroup) -> RateLimitLayer {
+        let token_verifier = SessionTokenVerifier::new(&server_configuration.session_tokens);
+        RateLimitLayer::new(group, server_configuration.get_rate_limit(group), server_configuration.rate_limit_trust_forwarded_for, token_verifier)
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService{ inner, limiter: self.limiter.clone() }
+    }
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, context: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(context)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match self.limiter.acquire(&request) {
+            Ok(()) => Box::pin(self.inner.call(request)),
+            Err(retry_after) => {
+                counter!("rate_limited_requests_total", "group" => self.limiter.group.name()).increment(1);
+                let error = ServerError::too_many_requests("RATE_LIMITED", "Too many requests, slow down", retry_after);
+                Box::pin(async move { Ok(error.into_response()) })
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    // Takes a token from the request's bucket, or returns how long until the next one is available.
+    fn acquire(&self, request: &Request) -> Result<(), Duration> {
+        if self.limit.per_minute == 0 {
+            return Ok(());
+        }
+
+        take_token(&self.buckets, self.key(request), self.limit)
+    }
+
+    // The signature is checked without the revocation list, a revoked token still counts for its user.
+    fn key(&self, request: &Request) -> RateLimitKey {
+        if self.group != RateLimitGroup::Login
+            && let Some(user_id) = authentication::request_token(request).and_then(|token| self.token_verifier.signed_user_id(token)) {
+            return RateLimitKey::User(user_id);
+        }
+
+        match client_ip(request.headers(), request.extensions(), self.trust_forwarded_for) {
+            Some(client_ip) => RateLimitKey::ClientIp(client_ip),
+            None => RateLimitKey::Unknown,
+        }
+    }
+}
+
+// Refills the key's bucket for the time since it was last used and takes a token from it.
+fn take_token<K: Eq + Hash>(buckets: &Mutex<HashMap<K, TokenBucket>>, key: K, limit: RateLimit) -> Result<(), Duration> {
+    if limit.per_minute == 0 {
+        return Ok(());
+    }
+
+    let capacity = limit.burst.max(1) as f64;
+    let tokens_per_second = limit.per_minute as f64 / 60.0;
+    let now = Instant::now();
+
+    let mut buckets = match buckets.lock() {
+        Ok(buckets) => buckets,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    if buckets.len() > BUCKET_PRUNE_THRESHOLD {
+        buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * tokens_per_second < capacity);
+    }
+
+    let bucket = buckets.entry(key).or_insert(TokenBucket{ tokens: capacity, updated_at: now });
+    bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * tokens_per_second).min(capacity);
+    bucket.updated_at = now;
+
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+    else {
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / tokens_per_second))
+    }
+}
+
+/// Implemented by server states whose handlers extract the [`ClientIp`].
+pub trait ClientIpState {
+    fn trust_forwarded_for(&self) -> bool;
+}
+
+impl<T: ClientIpState> ClientIpState for Arc<T> {
+    fn trust_forwarded_for(&self) -> bool {
+        (**self).trust_forwarded_for()
+    }
+}
+
+/// IP of the client that sent the request, found the same way as for rate limiting. `None` when it is not known.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S: ClientIpState + Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(client_ip(&parts.headers, &parts.extensions, state.trust_forwarded_for())))
+    }
+}
+
+// The first `x-forwarded-for` address when the proxy in front of the server is trusted, otherwise the connection's address.
+fn client_ip(headers: &HeaderMap, extensions: &Extensions, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded_ip = headers.get("x-forwarded-for")
+            .and_then(|forwarded_for| forwarded_for.to_str().ok())
+            .and_then(|forwarded_for| forwarded_for.split(',').next())
+            .and_then(|client_ip| client_ip.trim().parse::<IpAddr>().ok());
+
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+
+    extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(client_address)| client_address.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::header};
+    use crate::session_tokens::SessionTokenConfiguration;
+
+    const SIGNING_KEY: &str = "rate_limit_test_signing_key_long_enough";
+
+    fn limiter(group: RateLimitGroup) -> RateLimiter {
+        let configuration = SessionTokenConfiguration{
+            signing_key: SIGNING_KEY.into(),
+            lifetime: Duration::from_secs(60),
+            hack_lifetime: Duration::from_secs(60),
+            impersonation_lifetime: Duration::from_secs(60),
+            revocation_refresh_interval: Duration::from_secs(5),
+            legacy_tokens_accepted_until: None,
+        };
+
+        RateLimiter{
+            group,
+            limit: RateLimit{ per_minute: 1, burst: 2 },
+            trust_forwarded_for: false,
+            token_verifier: SessionTokenVerifier::new(&configuration),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn signed_token(user_id: i32, jti: &str, signing_key: &str) -> String {
+        let now = chrono::Utc::now().timestamp();
+        let claims = serde_json::json!({ "jti": jti, "user_id": user_id, "iat": now, "exp": now + 60 });
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &jsonwebtoken::EncodingKey::from_secret(signing_key.as_bytes())).unwrap()
+    }
+
+    fn request(client_ip: [u8; 4], token: Option<&str>) -> Request {
+        let mut request = Request::builder().uri("/transfer_funds");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+
+        let mut request = request.body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from((client_ip, 1234))));
+        request
+    }
+
+    #[test]
+    fn made_up_tokens_share_the_bucket_of_their_ip() {
+        let limiter = limiter(RateLimitGroup::Transfer);
+
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some("random_1"))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some(&signed_token(7, "a", "some_other_signing_key_long_enough")))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some("random_2"))).is_err());
+        assert!(limiter.acquire(&request([10, 0, 0, 2], Some("random_3"))).is_ok());
+    }
+
+    #[test]
+    fn signed_tokens_are_limited_per_user() {
+        let limiter = limiter(RateLimitGroup::Transfer);
+
+        // Two sessions of the same user from two addresses still share one bucket.
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some(&signed_token(7, "a", SIGNING_KEY)))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 2], Some(&signed_token(7, "b", SIGNING_KEY)))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 3], Some(&signed_token(7, "c", SIGNING_KEY)))).is_err());
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some(&signed_token(8, "d", SIGNING_KEY)))).is_ok());
+    }
+
+    #[test]
+    fn login_requests_are_limited_per_ip_only() {
+        let limiter = limiter(RateLimitGroup::Login);
+
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some(&signed_token(7, "a", SIGNING_KEY)))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 1], Some(&signed_token(8, "b", SIGNING_KEY)))).is_ok());
+        assert!(limiter.acquire(&request([10, 0, 0, 1], None)).is_err());
+        assert!(limiter.acquire(&request([10, 0, 0, 2], Some(&signed_token(7, "c", SIGNING_KEY)))).is_ok());
+    }
+
+    #[test]
+    fn rejected_requests_learn_when_to_retry() {
+        let limiter = limiter(RateLimitGroup::Transfer);
+
+        let _ = limiter.acquire(&request([10, 0, 0, 1], None));
+        let _ = limiter.acquire(&request([10, 0, 0, 1], None));
+        let retry_after = limiter.acquire(&request([10, 0, 0, 1], None)).unwrap_err();
+
+        assert!(retry_after > Duration::from_secs(59) && retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn user_limits_are_kept_per_user() {
+        let limiter = UserRateLimiter::new(RateLimitGroup::ChatMessage, RateLimit{ per_minute: 1, burst: 2 });
+
+        assert!(limiter.acquire(7).is_ok());
+        assert!(limiter.acquire(7).is_ok());
+        assert!(limiter.acquire(7).is_err());
+        assert!(limiter.acquire(8).is_ok());
+    }
+
+    #[test]
+    fn disabled_user_limits_never_reject() {
+        let limiter = UserRateLimiter::new(RateLimitGroup::ChatMessage, RateLimit{ per_minute: 0, burst: 1 });
+
+        assert!((0..10).all(|_| limiter.acquire(7).is_ok()));
+    }
+}
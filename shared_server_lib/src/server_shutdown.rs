@@ -0,0 +1,36 @@
+use tokio::signal;
+use tracing::{error, info};
+
+/// Resolves on SIGINT (Ctrl+C) or SIGTERM. Pass it to `axum::serve(...).with_graceful_shutdown`
+/// so in-flight requests finish before the server stops.
+pub async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(error) = signal::ctrl_c().await {
+            error!("Listening for the interrupt signal failed: {}", error);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate_signal) => {
+                terminate_signal.recv().await;
+            },
+            Err(error) => {
+                error!("Listening for the terminate signal failed: {}", error);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received, finishing in-flight requests");
+}
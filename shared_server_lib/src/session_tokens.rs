@@ -0,0 +1,737 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{TryRngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{PgConnection, PgPool};
+use tokio::{sync::RwLock, time::Instant};
+use tracing::{error, info};
+
+use cyber_crush_protocol::authentication::Permission;
+
+use crate::{authentication::AuthenticatedUser, authorization::UserPermissions, common::ServerError, server_metrics::TimedQuery};
+
+#[derive(Debug, Clone)]
+pub struct SessionTokenConfiguration {
+    pub signing_key: String,
+    pub lifetime: Duration,
+    /// Lifetime of the tokens the hacking server hands out to hackers.
+    pub hack_lifetime: Duration,
+    /// Lifetime of the tokens game masters get to act as another user.
+    pub impersonation_lifetime: Duration,
+    pub revocation_refresh_interval: Duration,
+    /// Random hex tokens from before JWT sessions are accepted until this time, or forever when not set.
+    pub legacy_tokens_accepted_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionClaims {
+    jti: String,
+    user_id: i32,
+    // Missing in tokens signed before roles. Names, so a token naming an unknown permission still decodes.
+    #[serde(default)]
+    permissions: Vec<String>,
+    // Flags of the tokens signed before roles, never written anymore.
+    #[serde(default, skip_serializing)]
+    can_publish_posts: bool,
+    #[serde(default, skip_serializing)]
+    can_hack: bool,
+    #[serde(default, skip_serializing)]
+    can_manage_users: bool,
+    /// User id of the game master, only in impersonation tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    impersonated_by: Option<i32>,
+    iat: i64,
+    exp: i64,
+}
+
+impl SessionClaims {
+    fn permissions(&self) -> UserPermissions {
+        let legacy_permissions = [
+            (self.can_publish_posts, Permission::PublishNews),
+            (self.can_hack, Permission::Hack),
+            (self.can_manage_users, Permission::ManageUsers),
+        ];
+
+        legacy_permissions.into_iter()
+            .filter_map(|(granted, permission)| granted.then_some(permission))
+            .chain(self.permissions.iter().filter_map(|permission_name| Permission::from_name(permission_name)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SessionKind {
+    Login,
+    Hack{ hacker_id: i32 },
+    Impersonation{ game_master_id: i32 },
+}
+
+pub struct IssuedToken {
+    pub token: String,
+    pub token_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Issues and verifies the signed session tokens (HS256 JWTs). Verification needs no database query,
+/// except for legacy tokens and a refresh of the revoked sessions every `revocation_refresh_interval`,
+/// which also stores the `last_seen_at` time of the sessions used since the last refresh.
+/// Tokens are never stored, legacy tokens only as a keyed hash.
+pub struct SessionTokens {
+    encoding_key: EncodingKey,
+    verifier: SessionTokenVerifier,
+    token_hash_key: Hmac<Sha256>,
+    lifetime: Duration,
+    hack_lifetime: Duration,
+    impersonation_lifetime: Duration,
+    revocation_refresh_interval: Duration,
+    legacy_tokens_accepted_until: Option<DateTime<Utc>>,
+    db_pool: PgPool,
+    revocations: RwLock<RevocationList>,
+    seen_session_ids: Mutex<HashSet<String>>,
+}
+
+/// Checks the signature and expiry of session tokens, without the revocation list or the database.
+#[derive(Clone)]
+pub struct SessionTokenVerifier {
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+#[derive(Default)]
+struct RevocationList {
+    refreshed_at: Option<Instant>,
+    session_ids: HashSet<String>,
+}
+
+impl fmt::Debug for SessionTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTokens")
+            .field("lifetime", &self.lifetime)
+            .field("hack_lifetime", &self.hack_lifetime)
+            .field("impersonation_lifetime", &self.impersonation_lifetime)
+            .field("revocation_refresh_interval", &self.revocation_refresh_interval)
+            .field("legacy_tokens_accepted_until", &self.legacy_tokens_accepted_until)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionTokens {
+    pub fn new(configuration: &SessionTokenConfiguration, db_pool: PgPool) -> SessionTokens {
+        SessionTokens {
+            encoding_key: EncodingKey::from_secret(configuration.signing_key.as_bytes()),
+            verifier: SessionTokenVerifier::new(configuration),
+            token_hash_key: token_hash_key(&configuration.signing_key),
+            lifetime: configuration.lifetime,
+            hack_lifetime: configuration.hack_lifetime,
+            impersonation_lifetime: configuration.impersonation_lifetime,
+            revocation_refresh_interval: configuration.revocation_refresh_interval,
+            legacy_tokens_accepted_until: configuration.legacy_tokens_accepted_until,
+            db_pool,
+            revocations: RwLock::new(RevocationList::default()),
+            seen_session_ids: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Signs a token of a new session and stores the session, `token_id` is its id.
+    pub async fn start_session(&self, user_id: i32, permissions: UserPermissions, device_label: Option<&str>) -> Result<IssuedToken, ServerError> {
+        self.store_new_session(user_id, permissions, device_label, SessionKind::Login, self.lifetime).await
+    }
+
+    /// Signs a short-lived token of a new session of the victim for a hacker, stored with the hacker's user id.
+    /// Hack sessions are not listed to the victim, logging out of every session or changing the password revokes them.
+    /// A hacker never gets the victim's game master permissions.
+    pub async fn start_hack_session(&self, hacker_id: i32, user_id: i32, permissions: UserPermissions) -> Result<IssuedToken, ServerError> {
+        let permissions = permissions.player_permissions();
+        self.store_new_session(user_id, permissions, None, SessionKind::Hack{ hacker_id }, self.hack_lifetime).await
+    }
+
+    /// Signs a short-lived token of a new session of the user for a game master, flagged with the game master's
+    /// user id. Every request made with it is recorded in the impersonation audit log. Like hack tokens, it never
+    /// carries the user's game master permissions.
+    pub async fn start_impersonation_session(&self, game_master_id: i32, user_id: i32, permissions: UserPermissions) -> Result<IssuedToken, ServerError> {
+        let permissions = permissions.player_permissions();
+        self.store_new_session(user_id, permissions, None, SessionKind::Impersonation{ game_master_id }, self.impersonation_lifetime).await
+    }
+
+    /// Signs a token of an existing session for another character of it. The token expires with the session
+    /// and is revoked with it, a token of an impersonation session stays flagged with its game master.
+    pub fn issue_for_session(&self, session_id: &str, expires_at: DateTime<Utc>, user_id: i32, permissions: UserPermissions, impersonated_by: Option<i32>) -> Result<IssuedToken, String> {
+        self.sign(session_id.to_string(), user_id, permissions, impersonated_by, expires_at)
+    }
+
+    async fn store_new_session(&self, user_id: i32, permissions: UserPermissions, device_label: Option<&str>, kind: SessionKind, lifetime: Duration) -> Result<IssuedToken, ServerError> {
+        let (impersonated_by, hacked_by) = match kind {
+            SessionKind::Impersonation{ game_master_id } => (Some(game_master_id), None),
+            SessionKind::Hack{ hacker_id } => (None, Some(hacker_id)),
+            SessionKind::Login => (None, None),
+        };
+
+        let mut token_id = [0u8; 16];
+        if let Err(error) = OsRng.try_fill_bytes(&mut token_id) {
+            error!("Generating a token id failed for user_id {}: {}", user_id, error);
+            return Err(ServerError::internal("Internal server error"));
+        }
+
+        let issued_token = match self.sign(hex::encode(token_id), user_id, permissions, impersonated_by, Utc::now() + lifetime) {
+            Ok(issued_token) => issued_token,
+            Err(error) => {
+                error!("Issuing a token failed for user_id {}: {}", user_id, error);
+                return Err(ServerError::internal("Internal server error"));
+            }
+        };
+
+        let expired_sessions_deletion = sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND expires_at < NOW()")
+            .bind(user_id)
+            .execute(&self.db_pool)
+            .timed("delete_expired_sessions")
+            .await;
+
+        if let Err(error) = expired_sessions_deletion {
+            error!("Failed to delete the expired sessions of user_id {}: {}", user_id, error);
+        }
+
+        let session_insertion = sqlx::query("INSERT INTO sessions (id, user_id, device_label, expires_at, is_hack, impersonated_by, hacked_by) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+            .bind(&issued_token.token_id)
+            .bind(user_id)
+            .bind(device_label)
+            .bind(issued_token.expires_at)
+            .bind(hacked_by.is_some())
+            .bind(impersonated_by)
+            .bind(hacked_by)
+            .execute(&self.db_pool)
+            .timed("store_session")
+            .await;
+
+        match session_insertion {
+            Ok(_) => Ok(issued_token),
+            Err(error) => {
+                error!("Failed to store a session of user_id {}: {}", user_id, error);
+                Err(ServerError::internal("Internal server error"))
+            }
+        }
+    }
+
+    fn sign(&self, token_id: String, user_id: i32, permissions: UserPermissions, impersonated_by: Option<i32>, expires_at: DateTime<Utc>) -> Result<IssuedToken, String> {
+        let claims = SessionClaims{
+            jti: token_id,
+            user_id,
+            permissions: permissions.iter().map(|permission| permission.name().to_string()).collect(),
+            can_publish_posts: false,
+            can_hack: false,
+            can_manage_users: false,
+            impersonated_by,
+            iat: Utc::now().timestamp(),
+            exp: expires_at.timestamp(),
+        };
+
+        match jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key) {
+            Ok(token) => Ok(IssuedToken{ token, token_id: claims.jti, expires_at }),
+            Err(error) => Err(format!("Signing the token failed: {}", error)),
+        }
+    }
+
+    pub async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser, ServerError> {
+        if !token.contains('.') {
+            return self.authenticate_legacy_token(token).await;
+        }
+
+        let claims = match self.verifier.decode(token) {
+            Ok(claims) => claims,
+            Err(error) if *error.kind() == ErrorKind::ExpiredSignature => {
+                return Err(ServerError::unauthorized("INVALID_TOKEN", "Token expired"));
+            },
+            Err(_) => {
+                return Err(ServerError::invalid_token());
+            }
+        };
+
+        if self.is_revoked(&claims.jti).await? {
+            return Err(ServerError::unauthorized("INVALID_TOKEN", "Token revoked"));
+        }
+
+        if let Ok(mut seen_session_ids) = self.seen_session_ids.lock() {
+            seen_session_ids.insert(claims.jti.clone());
+        }
+
+        let permissions = claims.permissions();
+        Ok(AuthenticatedUser{ user_id: claims.user_id, permissions, session_id: Some(claims.jti), impersonated_by: claims.impersonated_by })
+    }
+
+    /// Revokes one session of the user, or a session the user is a linked character of. Returns false when
+    /// the user has no such active session.
+    pub async fn revoke_session(&self, user_id: i32, session_id: &str) -> Result<bool, ServerError> {
+        let revoke_query = sqlx::query(
+            r#"
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+                AND (user_id = $2 OR EXISTS (SELECT 1 FROM session_characters WHERE session_id = $1 AND user_id = $2))
+            "#
+            )
+            .bind(session_id)
+            .bind(user_id)
+            .execute(&self.db_pool)
+            .timed("revoke_session")
+            .await;
+
+        match revoke_query {
+            Ok(result) if result.rows_affected() == 1 => {
+                // Other servers see the revocation at their next refresh, this one right away.
+                self.revocations.write().await.session_ids.insert(session_id.to_string());
+                Ok(true)
+            },
+            Ok(_) => Ok(false),
+            Err(error) => {
+                error!("Failed to revoke a session of user_id {}: {}", user_id, error);
+                Err(ServerError::internal("Session revocation server internal error."))
+            }
+        }
+    }
+
+    /// Revokes every active session of the user except `except_session_id`, along with the impersonation sessions
+    /// the user opened as a game master. Returns how many were revoked.
+    pub async fn revoke_user_sessions(&self, user_id: i32, except_session_id: Option<&str>) -> Result<usize, ServerError> {
+        let mut connection = match self.db_pool.acquire().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                error!("Failed to revoke the sessions of user_id {}: {}", user_id, error);
+                return Err(ServerError::internal("Session revocation server internal error."));
+            }
+        };
+
+        let session_ids = self.revoke_user_sessions_in(&mut connection, user_id, except_session_id).await?;
+        let revoked_count = session_ids.len();
+        self.record_revoked_sessions(session_ids).await;
+        Ok(revoked_count)
+    }
+
+    /// Same as `revoke_user_sessions`, as part of the transaction of `connection`. This server keeps accepting the
+    /// sessions until the returned ids are passed to `record_revoked_sessions` once the transaction is committed.
+    pub async fn revoke_user_sessions_in(&self, connection: &mut PgConnection, user_id: i32, except_session_id: Option<&str>) -> Result<Vec<String>, ServerError> {
+        let revoke_query = sqlx::query_scalar::<_, String>(
+            r#"
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE (user_id = $1 OR impersonated_by = $1) AND id IS DISTINCT FROM $2 AND revoked_at IS NULL AND expires_at > NOW()
+            RETURNING id
+            "#
+        )
+        .bind(user_id)
+        .bind(except_session_id)
+        .fetch_all(connection)
+        .timed("revoke_user_sessions")
+        .await;
+
+        match revoke_query {
+            Ok(session_ids) => Ok(session_ids),
+            Err(error) => {
+                error!("Failed to revoke the sessions of user_id {}: {}", user_id, error);
+                Err(ServerError::internal("Session revocation server internal error."))
+            }
+        }
+    }
+
+    pub async fn record_revoked_sessions(&self, session_ids: Vec<String>) {
+        self.revocations.write().await.session_ids.extend(session_ids);
+    }
+
+    /// Records an action done with an impersonation token in the audit log. Does nothing for other tokens.
+    /// Fails when the action can not be recorded, so no impersonated action goes unrecorded.
+    pub async fn record_impersonated_action(&self, user: &AuthenticatedUser, action: &str, request_id: Option<&str>) -> Result<(), ServerError> {
+        let game_master_id = match user.impersonated_by {
+            Some(game_master_id) => game_master_id,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let audit_insertion = sqlx::query(
+            "INSERT INTO impersonation_audit_log (session_id, game_master_id, user_id, action, request_id) VALUES ($1, $2, $3, $4, $5)"
+            )
+            .bind(&user.session_id)
+            .bind(game_master_id)
+            .bind(user.user_id)
+            .bind(action)
+            .bind(request_id)
+            .execute(&self.db_pool)
+            .timed("record_impersonated_action")
+            .await;
+
+        match audit_insertion {
+            Ok(_) => {
+                info!("Game master user_id {} acted as user_id {}: {}", game_master_id, user.user_id, action);
+                Ok(())
+            },
+            Err(error) => {
+                error!("Failed to record an action of game master user_id {} as user_id {}: {}", game_master_id, user.user_id, error);
+                Err(ServerError::internal("Impersonation audit log database error."))
+            }
+        }
+    }
+
+    /// Keyed hash of a legacy token, the only form of it kept in the database.
+    pub fn hash_token(&self, token: &str) -> String {
+        keyed_token_hash(&self.token_hash_key, token)
+    }
+
+    /// Longest time a revocation made on another server takes to be seen by this one.
+    pub fn revocation_refresh_interval(&self) -> Duration {
+        self.revocation_refresh_interval
+    }
+
+    async fn is_revoked(&self, session_id: &str) -> Result<bool, ServerError> {
+        let revocations = self.revocations.read().await;
+        if revocations.is_fresh(self.revocation_refresh_interval) {
+            return Ok(revocations.session_ids.contains(session_id));
+        }
+        drop(revocations);
+
+        let mut revocations = self.revocations.write().await;
+        // Another request may have refreshed the list while this one waited for the lock.
+        if !revocations.is_fresh(self.revocation_refresh_interval) {
+            self.store_seen_sessions().await;
+            *revocations = self.load_revocations().await?;
+        }
+
+        Ok(revocations.session_ids.contains(session_id))
+    }
+
+    async fn load_revocations(&self) -> Result<RevocationList, ServerError> {
+        let revocation_query = sqlx::query_scalar::<_, String>("SELECT id FROM sessions WHERE revoked_at IS NOT NULL AND expires_at > NOW()")
+            .fetch_all(&self.db_pool)
+            .timed("load_revoked_sessions")
+            .await;
+
+        match revocation_query {
+            Ok(session_ids) => Ok(RevocationList{ refreshed_at: Some(Instant::now()), session_ids: session_ids.into_iter().collect() }),
+            Err(error) => {
+                error!("Failed to load the revoked sessions: {}", error);
+                Err(ServerError::internal("Token validation server internal error."))
+            }
+        }
+    }
+
+    async fn store_seen_sessions(&self) {
+        let seen_session_ids = match self.seen_session_ids.lock() {
+            Ok(mut seen_session_ids) => seen_session_ids.drain().collect::<Vec<_>>(),
+            Err(_) => {
+                return;
+            }
+        };
+
+        if seen_session_ids.is_empty() {
+            return;
+        }
+
+        let seen_update = sqlx::query("UPDATE sessions SET last_seen_at = NOW() WHERE id = ANY($1)")
+            .bind(&seen_session_ids)
+            .execute(&self.db_pool)
+            .timed("store_session_last_seen")
+            .await;
+
+        if let Err(error) = seen_update {
+            error!("Failed to store the last seen time of {} session(s): {}", seen_session_ids.len(), error);
+        }
+    }
+
+    async fn authenticate_legacy_token(&self, token: &str) -> Result<AuthenticatedUser, ServerError> {
+        if self.legacy_tokens_accepted_until.is_some_and(|accepted_until| Utc::now() >= accepted_until) {
+            info!("Rejected a legacy token after the migration window closed");
+            return Err(ServerError::invalid_token());
+        }
+
+        #[derive(sqlx::FromRow)]
+        struct AuthenticationQuery {
+            id: i32,
+            #[sqlx(flatten)]
+            permissions: UserPermissions,
+        }
+
+        let authentication_query = sqlx::query_as::<_, AuthenticationQuery>("SELECT id, user_permissions(id) AS permissions FROM users WHERE user_token_hash = $1")
+            .bind(self.hash_token(token))
+            .fetch_optional(&self.db_pool)
+            .timed("authenticate_legacy_token")
+            .await;
+
+        match authentication_query {
+            Ok(Some(user)) => Ok(AuthenticatedUser{ user_id: user.id, permissions: user.permissions, session_id: None, impersonated_by: None }),
+            Ok(None) => Err(ServerError::invalid_token()),
+            Err(error) => {
+                error!("Failed to validate a legacy token: {}", error);
+                Err(ServerError::internal("Token validation server internal error."))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SessionTokenVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTokenVerifier").finish_non_exhaustive()
+    }
+}
+
+impl SessionTokenVerifier {
+    pub fn new(configuration: &SessionTokenConfiguration) -> SessionTokenVerifier {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "iat", "jti"]);
+
+        SessionTokenVerifier{ decoding_key: DecodingKey::from_secret(configuration.signing_key.as_bytes()), validation }
+    }
+
+    /// User id of a token signed by this server and not expired yet. Revoked tokens and legacy tokens are not recognized,
+    /// use [`SessionTokens::authenticate`] to authenticate a request.
+    pub fn signed_user_id(&self, token: &str) -> Option<i32> {
+        self.decode(token).ok().map(|claims| claims.user_id)
+    }
+
+    fn decode(&self, token: &str) -> Result<SessionClaims, jsonwebtoken::errors::Error> {
+        jsonwebtoken::decode::<SessionClaims>(token, &self.decoding_key, &self.validation).map(|token_data| token_data.claims)
+    }
+}
+
+/// Replaces the plaintext legacy tokens left in `users.user_token` with their keyed hash. Runs at every
+/// server start, so tokens written by older servers or scripts are hashed by the next start.
+pub async fn hash_legacy_tokens(configuration: &SessionTokenConfiguration, db_pool: &PgPool) -> Result<usize, String> {
+    #[derive(sqlx::FromRow)]
+    struct LegacyTokenQuery {
+        id: i32,
+        user_token: String,
+    }
+
+    let legacy_tokens_query = sqlx::query_as::<_, LegacyTokenQuery>("SELECT id, user_token FROM users WHERE user_token IS NOT NULL")
+        .fetch_all(db_pool)
+        .timed("get_legacy_tokens")
+        .await;
+
+    let legacy_tokens = match legacy_tokens_query {
+        Ok(legacy_tokens) => legacy_tokens,
+        Err(error) => {
+            return Err(format!("Failed to read the legacy tokens: {}", error));
+        }
+    };
+
+    let token_hash_key = token_hash_key(&configuration.signing_key);
+    for legacy_token in &legacy_tokens {
+        // Only replaced when unchanged, the user may have logged out meanwhile.
+        let token_update = sqlx::query("UPDATE users SET user_token_hash = $1, user_token = NULL WHERE id = $2 AND user_token = $3")
+            .bind(keyed_token_hash(&token_hash_key, &legacy_token.user_token))
+            .bind(legacy_token.id)
+            .bind(&legacy_token.user_token)
+            .execute(db_pool)
+            .timed("hash_legacy_token")
+            .await;
+
+        if let Err(error) = token_update {
+            return Err(format!("Failed to hash the legacy token of user_id {}: {}", legacy_token.id, error));
+        }
+    }
+
+    Ok(legacy_tokens.len())
+}
+
+// Derived from the signing key, so the hashes change with it like the signatures do.
+fn token_hash_key(signing_key: &str) -> Hmac<Sha256> {
+    let mut key_derivation = <Hmac<Sha256> as Mac>::new_from_slice(signing_key.as_bytes()).expect("HMAC accepts keys of any length");
+    key_derivation.update(b"cyber crush token hash key");
+    let derived_key = key_derivation.finalize().into_bytes();
+
+    <Hmac<Sha256> as Mac>::new_from_slice(&derived_key).expect("HMAC accepts keys of any length")
+}
+
+fn keyed_token_hash(token_hash_key: &Hmac<Sha256>, token: &str) -> String {
+    let mut token_hash = token_hash_key.clone();
+    token_hash.update(token.as_bytes());
+    hex::encode(token_hash.finalize().into_bytes())
+}
+
+impl RevocationList {
+    fn is_fresh(&self, refresh_interval: Duration) -> bool {
+        self.refreshed_at.is_some_and(|refreshed_at| refreshed_at.elapsed() < refresh_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_database;
+
+    fn configuration(signing_key: &str) -> SessionTokenConfiguration {
+        SessionTokenConfiguration{
+            signing_key: signing_key.into(),
+            lifetime: Duration::from_secs(60),
+            hack_lifetime: Duration::from_secs(60),
+            impersonation_lifetime: Duration::from_secs(60),
+            revocation_refresh_interval: Duration::from_secs(60),
+            legacy_tokens_accepted_until: None,
+        }
+    }
+
+    fn new_session_tokens(configuration: &SessionTokenConfiguration, revoked_session_ids: &[&str]) -> SessionTokens {
+        let session_tokens = SessionTokens::new(configuration, server_database::unreachable_pool());
+        *session_tokens.revocations.try_write().unwrap() = RevocationList{
+            refreshed_at: Some(Instant::now()),
+            session_ids: revoked_session_ids.iter().map(|session_id| session_id.to_string()).collect(),
+        };
+        session_tokens
+    }
+
+    fn sign(session_tokens: &SessionTokens, session_id: &str, expires_at: DateTime<Utc>) -> String {
+        let permissions = UserPermissions::default().with(Permission::PublishNews);
+        session_tokens.issue_for_session(session_id, expires_at, 5, permissions, None).unwrap().token
+    }
+
+    fn hour_from_now() -> DateTime<Utc> {
+        Utc::now() + Duration::from_secs(60 * 60)
+    }
+
+    #[tokio::test]
+    async fn signed_tokens_authenticate_without_the_database() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let token = sign(&session_tokens, "session_1", hour_from_now());
+
+        let user = session_tokens.authenticate(&token).await.unwrap();
+
+        assert_eq!(user.user_id, 5);
+        assert_eq!(user.permissions, UserPermissions::default().with(Permission::PublishNews));
+        assert_eq!(user.session_id.as_deref(), Some("session_1"));
+        assert_eq!(user.impersonated_by, None);
+        assert_eq!(session_tokens.verifier.signed_user_id(&token), Some(5));
+    }
+
+    #[tokio::test]
+    async fn impersonation_tokens_keep_the_game_master() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let token = session_tokens.issue_for_session("session_1", hour_from_now(), 5, UserPermissions::default(), Some(6)).unwrap().token;
+
+        let user = session_tokens.authenticate(&token).await.unwrap();
+
+        assert_eq!(user.user_id, 5);
+        assert_eq!(user.impersonated_by, Some(6));
+        assert_eq!(session_tokens.verifier.signed_user_id(&token), Some(5));
+    }
+
+    #[tokio::test]
+    async fn only_impersonated_actions_are_recorded() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let mut user = AuthenticatedUser{ user_id: 5, permissions: UserPermissions::default(), session_id: Some("session_1".into()), impersonated_by: None };
+
+        assert!(session_tokens.record_impersonated_action(&user, "POST /transfer_funds", None).await.is_ok());
+
+        // An impersonated action that can not be recorded is rejected.
+        user.impersonated_by = Some(6);
+        let error = session_tokens.record_impersonated_action(&user, "POST /transfer_funds", None).await.unwrap_err();
+        assert_eq!(error.code(), "INTERNAL_ERROR");
+    }
+
+    #[tokio::test]
+    async fn tokens_of_revoked_sessions_are_rejected() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &["session_1"]);
+        let token = sign(&session_tokens, "session_1", hour_from_now());
+
+        let error = session_tokens.authenticate(&token).await.unwrap_err();
+
+        assert_eq!(error.code(), "INVALID_TOKEN");
+        assert_eq!(error.message(), "Token revoked");
+    }
+
+    #[tokio::test]
+    async fn a_stale_revocation_list_is_refreshed_before_trusting_a_token() {
+        let mut configuration = configuration("test_signing_key_that_is_long_enough");
+        configuration.revocation_refresh_interval = Duration::from_millis(1);
+        let session_tokens = new_session_tokens(&configuration, &[]);
+        let token = sign(&session_tokens, "session_1", hour_from_now());
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // The refresh has to reach the database, which is not there.
+        let error = session_tokens.authenticate(&token).await.unwrap_err();
+
+        assert_eq!(error.code(), "INTERNAL_ERROR");
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_rejected() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let token = sign(&session_tokens, "session_1", Utc::now() - Duration::from_secs(1));
+
+        let error = session_tokens.authenticate(&token).await.unwrap_err();
+
+        assert_eq!(error.message(), "Token expired");
+        assert_eq!(session_tokens.verifier.signed_user_id(&token), None);
+    }
+
+    #[tokio::test]
+    async fn tokens_signed_with_another_key_or_changed_are_rejected() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let other_session_tokens = new_session_tokens(&configuration("another_signing_key_that_is_long_enough"), &[]);
+        let forged_token = other_session_tokens.issue_for_session("session_1", hour_from_now(), 1, Permission::ALL.into_iter().collect(), None).unwrap().token;
+
+        // The claims of the forged token under the signature of a real one.
+        let token = sign(&session_tokens, "session_1", hour_from_now());
+        let mut token_parts = token.split('.').collect::<Vec<_>>();
+        token_parts[1] = forged_token.split('.').nth(1).unwrap();
+        let changed_token = token_parts.join(".");
+
+        assert_eq!(session_tokens.authenticate(&forged_token).await.unwrap_err().code(), "INVALID_TOKEN");
+        assert_eq!(session_tokens.authenticate(&changed_token).await.unwrap_err().code(), "INVALID_TOKEN");
+        assert_eq!(session_tokens.verifier.signed_user_id(&changed_token), None);
+    }
+
+    #[tokio::test]
+    async fn claims_signed_before_roles_keep_their_permissions() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let claims = serde_json::json!({ "jti": "session_1", "user_id": 5, "can_publish_posts": true, "can_hack": true, "iat": 0, "exp": hour_from_now().timestamp() });
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &session_tokens.encoding_key).unwrap();
+
+        let user = session_tokens.authenticate(&token).await.unwrap();
+
+        assert_eq!(user.permissions, UserPermissions::default().with(Permission::PublishNews).with(Permission::Hack));
+    }
+
+    #[tokio::test]
+    async fn unknown_permissions_are_ignored() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+        let claims = serde_json::json!({ "jti": "session_1", "user_id": 5, "permissions": ["manage_users", "launch_missiles"], "iat": 0, "exp": hour_from_now().timestamp() });
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &session_tokens.encoding_key).unwrap();
+
+        let user = session_tokens.authenticate(&token).await.unwrap();
+
+        assert_eq!(user.permissions, UserPermissions::default().with(Permission::ManageUsers));
+    }
+
+    #[tokio::test]
+    async fn legacy_tokens_are_looked_up_in_the_database() {
+        let session_tokens = new_session_tokens(&configuration("test_signing_key_that_is_long_enough"), &[]);
+
+        let error = session_tokens.authenticate("0123456789abcdef").await.unwrap_err();
+
+        assert_eq!(error.code(), "INTERNAL_ERROR");
+    }
+
+    #[tokio::test]
+    async fn legacy_tokens_are_rejected_once_the_migration_window_closed() {
+        let mut configuration = configuration("test_signing_key_that_is_long_enough");
+        configuration.legacy_tokens_accepted_until = Some(Utc::now() - Duration::from_secs(1));
+        let session_tokens = new_session_tokens(&configuration, &[]);
+
+        let error = session_tokens.authenticate("0123456789abcdef").await.unwrap_err();
+
+        assert_eq!(error.code(), "INVALID_TOKEN");
+    }
+
+    #[test]
+    fn legacy_token_hashes_are_keyed_with_the_signing_key() {
+        let key = token_hash_key("test_signing_key_that_is_long_enough");
+        let other_key = token_hash_key("another_signing_key_that_is_long_enough");
+
+        let token_hash = keyed_token_hash(&key, "0123456789abcdef");
+
+        assert_eq!(token_hash, keyed_token_hash(&key, "0123456789abcdef"));
+        assert_ne!(token_hash, keyed_token_hash(&other_key, "0123456789abcdef"));
+        assert_eq!(token_hash.len(), 64);
+    }
+}
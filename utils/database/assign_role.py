@@ -0,0 +1,66 @@
+import argparse
+import psycopg2
+import json
+
+DATABASE_NAME = ""
+DATABASE_USERNAME = ""
+DATABASE_PASSWORD = ""
+DATABASE_URL = ""
+DATABASE_PORT = 0
+
+def setup_configuration():
+    global DATABASE_NAME, DATABASE_USERNAME, DATABASE_PASSWORD, DATABASE_URL, DATABASE_PORT
+
+    with open("../../server.conf", "r", encoding="utf-8") as file:
+        config = json.load(file)
+
+    DATABASE_NAME = config["database_name"]
+    DATABASE_USERNAME = config["database_admin_username"]
+    DATABASE_PASSWORD = config["database_admin_password"]
+    DATABASE_URL = config["database_url"]
+    DATABASE_PORT = config["database_port"]
+
+def assign_role(db_connection, username, role, remove):
+    try:
+        db_cursor = db_connection.cursor()
+        db_cursor.execute("UPDATE users SET user_token = NULL, user_token_hash = NULL WHERE username = %s RETURNING id;", (username,))
+        user = db_cursor.fetchone()
+        if user is None:
+            raise ValueError(f"User {username} not found")
+
+        db_cursor.execute("SELECT id FROM roles WHERE name = %s;", (role,))
+        role_row = db_cursor.fetchone()
+        if role_row is None:
+            raise ValueError(f"Role {role} not found")
+
+        if remove:
+            db_cursor.execute("DELETE FROM user_roles WHERE user_id = %s AND role_id = %s;", (user[0], role_row[0]))
+        else:
+            db_cursor.execute("INSERT INTO user_roles (user_id, role_id) VALUES (%s, %s) ON CONFLICT DO NOTHING;", (user[0], role_row[0]))
+
+        # The permissions are part of the session token, the user logs in again to get the new ones.
+        db_cursor.execute("UPDATE sessions SET revoked_at = NOW() WHERE user_id = %s AND revoked_at IS NULL;", (user[0],))
+        db_connection.commit()
+    except Exception:
+        db_connection.rollback()
+        raise
+    finally: 
+        db_cursor.close()
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description = "Gives one user a role, or takes it away, and logs the user out.")
+    parser.add_argument("-u", "--username", required = True, help = "Username of the user")
+    parser.add_argument("-r", "--role", required = True, help = "Name of the role, e.g. game_master")
+    parser.add_argument("--remove", action = "store_true", help = "Take the role away instead")
+    args = parser.parse_args()
+
+    setup_configuration()
+
+    db_connection = psycopg2.connect(dbname = DATABASE_NAME,
+                                     user = DATABASE_USERNAME,
+                                     password = DATABASE_PASSWORD,
+                                     host = DATABASE_URL,
+                                     port = DATABASE_PORT);
+
+    assign_role(db_connection, args.username, args.role, args.remove)
+    db_connection.close()
@@ -0,0 +1,65 @@
+import argparse
+import psycopg2
+import json
+
+DATABASE_NAME = ""
+DATABASE_USERNAME = ""
+DATABASE_PASSWORD = ""
+DATABASE_URL = ""
+DATABASE_PORT = 0
+
+def setup_configuration():
+    global DATABASE_NAME, DATABASE_USERNAME, DATABASE_PASSWORD, DATABASE_URL, DATABASE_PORT
+
+    with open("../../server.conf", "r", encoding="utf-8") as file:
+        config = json.load(file)
+
+    DATABASE_NAME = config["database_name"]
+    DATABASE_USERNAME = config["database_admin_username"]
+    DATABASE_PASSWORD = config["database_admin_password"]
+    DATABASE_URL = config["database_url"]
+    DATABASE_PORT = config["database_port"]
+
+def list_impersonations(db_connection, hours, game_master, username):
+    db_cursor = db_connection.cursor()
+    try:
+        db_cursor.execute("""
+            SELECT log.created_at, game_master.username, impersonated_user.username, log.action, log.request_id
+            FROM impersonation_audit_log AS log
+            LEFT JOIN users AS game_master ON game_master.id = log.game_master_id
+            LEFT JOIN users AS impersonated_user ON impersonated_user.id = log.user_id
+            WHERE log.created_at > NOW() - %s * INTERVAL '1 hour'
+                AND (%s IS NULL OR game_master.username = %s)
+                AND (%s IS NULL OR impersonated_user.username = %s)
+            ORDER BY log.created_at DESC;
+        """, (hours, game_master, game_master, username, username))
+
+        actions = db_cursor.fetchall()
+    finally:
+        db_cursor.close()
+
+    if len(actions) == 0:
+        print("No impersonated actions found")
+        return
+
+    for created_at, game_master, username, action, request_id in actions:
+        print(f"{created_at:%Y-%m-%d %H:%M:%S} {game_master or 'deleted user'} as {username or 'deleted user'}: {action}"
+              f"{f' (request {request_id})' if request_id else ''}")
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description = "Lists the actions game masters did while impersonating other users.")
+    parser.add_argument("-H", "--hours", type = float, default = 24, help = "Only actions of the last hours")
+    parser.add_argument("-g", "--game-master", help = "Only actions of this game master")
+    parser.add_argument("-u", "--username", help = "Only actions done as this user")
+    args = parser.parse_args()
+
+    setup_configuration()
+
+    db_connection = psycopg2.connect(dbname = DATABASE_NAME,
+                                     user = DATABASE_USERNAME,
+                                     password = DATABASE_PASSWORD,
+                                     host = DATABASE_URL,
+                                     port = DATABASE_PORT);
+
+    list_impersonations(db_connection, args.hours, args.game_master, args.username)
+    db_connection.close()
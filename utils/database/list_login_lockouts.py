@@ -0,0 +1,60 @@
+import argparse
+import psycopg2
+import json
+
+DATABASE_NAME = ""
+DATABASE_USERNAME = ""
+DATABASE_PASSWORD = ""
+DATABASE_URL = ""
+DATABASE_PORT = 0
+
+def setup_configuration():
+    global DATABASE_NAME, DATABASE_USERNAME, DATABASE_PASSWORD, DATABASE_URL, DATABASE_PORT
+
+    with open("../../server.conf", "r", encoding="utf-8") as file:
+        config = json.load(file)
+
+    DATABASE_NAME = config["database_name"]
+    DATABASE_USERNAME = config["database_admin_username"]
+    DATABASE_PASSWORD = config["database_admin_password"]
+    DATABASE_URL = config["database_url"]
+    DATABASE_PORT = config["database_port"]
+
+def list_login_lockouts(db_connection, hours, username):
+    db_cursor = db_connection.cursor()
+    try:
+        db_cursor.execute("""
+            SELECT created_at, lockout_type, username, client_ip, failed_logins, locked_until
+            FROM login_lockouts
+            WHERE created_at > NOW() - %s * INTERVAL '1 hour' AND (%s IS NULL OR username = %s)
+            ORDER BY created_at DESC;
+        """, (hours, username, username))
+
+        lockouts = db_cursor.fetchall()
+    finally:
+        db_cursor.close()
+
+    if len(lockouts) == 0:
+        print("No login lockouts found")
+        return
+
+    for created_at, lockout_type, username, client_ip, failed_logins, locked_until in lockouts:
+        print(f"{created_at:%Y-%m-%d %H:%M:%S} locked by {lockout_type}: user {username} from {client_ip or 'unknown IP'}, "
+              f"{failed_logins} failed logins, locked until {locked_until:%H:%M:%S}")
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description = "Lists the login lockouts after too many failed logins.")
+    parser.add_argument("-H", "--hours", type = float, default = 24, help = "Only lockouts of the last hours")
+    parser.add_argument("-u", "--username", help = "Only lockouts of this username")
+    args = parser.parse_args()
+
+    setup_configuration()
+
+    db_connection = psycopg2.connect(dbname = DATABASE_NAME,
+                                     user = DATABASE_USERNAME,
+                                     password = DATABASE_PASSWORD,
+                                     host = DATABASE_URL,
+                                     port = DATABASE_PORT);
+
+    list_login_lockouts(db_connection, args.hours, args.username)
+    db_connection.close()
@@ -22,7 +22,8 @@ def setup_configuration():
 def logout_all_users(db_connection):
     try:
         db_cursor = db_connection.cursor()
-        db_cursor.execute("UPDATE users SET user_token = NULL;")
+        db_cursor.execute("UPDATE users SET user_token = NULL, user_token_hash = NULL;")
+        db_cursor.execute("UPDATE sessions SET revoked_at = NOW() WHERE revoked_at IS NULL;")
         db_connection.commit()
     except Exception:
         db_connection.rollback()
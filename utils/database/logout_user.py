@@ -0,0 +1,53 @@
+import argparse
+import psycopg2
+import json
+
+DATABASE_NAME = ""
+DATABASE_USERNAME = ""
+DATABASE_PASSWORD = ""
+DATABASE_URL = ""
+DATABASE_PORT = 0
+
+def setup_configuration():
+    global DATABASE_NAME, DATABASE_USERNAME, DATABASE_PASSWORD, DATABASE_URL, DATABASE_PORT
+
+    with open("../../server.conf", "r", encoding="utf-8") as file:
+        config = json.load(file)
+
+    DATABASE_NAME = config["database_name"]
+    DATABASE_USERNAME = config["database_admin_username"]
+    DATABASE_PASSWORD = config["database_admin_password"]
+    DATABASE_URL = config["database_url"]
+    DATABASE_PORT = config["database_port"]
+
+def logout_user(db_connection, username):
+    try:
+        db_cursor = db_connection.cursor()
+        db_cursor.execute("UPDATE users SET user_token = NULL, user_token_hash = NULL WHERE username = %s RETURNING id;", (username,))
+        user = db_cursor.fetchone()
+        if user is None:
+            raise ValueError(f"User {username} not found")
+
+        db_cursor.execute("UPDATE sessions SET revoked_at = NOW() WHERE user_id = %s AND revoked_at IS NULL;", (user[0],))
+        db_connection.commit()
+    except Exception:
+        db_connection.rollback()
+        raise
+    finally: 
+        db_cursor.close()
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description = "Revokes every session token of one user.")
+    parser.add_argument("-u", "--username", required = True, help = "Username of the user to log out")
+    args = parser.parse_args()
+
+    setup_configuration()
+
+    db_connection = psycopg2.connect(dbname = DATABASE_NAME,
+                                     user = DATABASE_USERNAME,
+                                     password = DATABASE_PASSWORD,
+                                     host = DATABASE_URL,
+                                     port = DATABASE_PORT);
+
+    logout_user(db_connection, args.username)
+    db_connection.close()
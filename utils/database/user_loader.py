@@ -7,10 +7,15 @@ from psycopg2 import sql
 from psycopg2.extras import Json
 
 PEPPER = ""
+PEPPER_VERSION = 1
+PASSWORD_HASH_MEMORY_KIB = 19456
+PASSWORD_HASH_ITERATIONS = 2
+PASSWORD_HASH_PARALLELISM = 1
 MAX_USERNAME_LENGTH = 8
 MAX_PASSWORD_LENGTH = 16
 MAX_EXTRA_DATA_LENGTH = 8192
 MAX_GROUP_CHAT_MEMBERS = 16
+MAX_CYBER_DEFENCE_LEVEL = 10
 
 DATABASE_NAME = ""
 DATABASE_USERNAME = ""
@@ -22,16 +27,22 @@ def hash_password(password: str, ph: PasswordHasher) -> str:
     return ph.hash(password + PEPPER)
 
 def setup_configuration():
-    global PEPPER, MAX_UESRNAME_LENGTH, MAX_PASSWORD_LENGTH, MAX_EXTRA_DATA_LENGTH
+    global PEPPER, PEPPER_VERSION, PASSWORD_HASH_MEMORY_KIB, PASSWORD_HASH_ITERATIONS, PASSWORD_HASH_PARALLELISM
+    global MAX_UESRNAME_LENGTH, MAX_PASSWORD_LENGTH, MAX_EXTRA_DATA_LENGTH, MAX_CYBER_DEFENCE_LEVEL
     global DATABASE_NAME, DATABASE_USERNAME, DATABASE_PASSWORD, DATABASE_URL, DATABASE_PORT
     
     with open("../../server.conf", "r", encoding="utf-8") as file:
         config = json.load(file)
 
     PEPPER = config["database_password_pepper"]
+    PEPPER_VERSION = config.get("database_password_pepper_version", PEPPER_VERSION)
+    PASSWORD_HASH_MEMORY_KIB = config.get("password_hash_memory_kib", PASSWORD_HASH_MEMORY_KIB)
+    PASSWORD_HASH_ITERATIONS = config.get("password_hash_iterations", PASSWORD_HASH_ITERATIONS)
+    PASSWORD_HASH_PARALLELISM = config.get("password_hash_parallelism", PASSWORD_HASH_PARALLELISM)
     MAX_USERNAME_LENGTH = config["max_username_length"]
     MAX_PASSWORD_LENGTH = config["max_password_length"]
     MAX_EXTRA_DATA_LENGTH = config["max_extra_data_length"]
+    MAX_CYBER_DEFENCE_LEVEL = config.get("max_cyber_defence_level", MAX_CYBER_DEFENCE_LEVEL)
     MAX_GROUP_CHAT_MEMVERS = config["max_group_chat_members"]
 
     DATABASE_NAME = config["database_name"]
@@ -40,7 +51,7 @@ def setup_configuration():
     DATABASE_URL = config["database_url"]
     DATABASE_PORT = config["database_port"]
 
-def validate_user(username: str, password: str, extra_data: str) -> bool:
+def validate_user(username: str, password: str, cyber_defence_level: int, extra_data: str) -> bool:
     if len(username) > MAX_USERNAME_LENGTH:
         print(f"Username: { username }, excides { MAX_USERNAME_LENGTH } character limit")
         return False
@@ -50,9 +61,22 @@ def validate_user(username: str, password: str, extra_data: str) -> bool:
     if len(extra_data) > MAX_EXTRA_DATA_LENGTH:
         print(f"Extra data for user { username }, excides { MAX_EXTRA_DATA_LENGTH } character limit")
         return False
+    if not 0 <= cyber_defence_level <= MAX_CYBER_DEFENCE_LEVEL:
+        print(f"Cyber defence level of user { username } is not between 0 and { MAX_CYBER_DEFENCE_LEVEL }")
+        return False
 
     return True
 
+# The flags stand for the roles with the same permission, like in the authentication server.
+def user_roles(user_data) -> list[str]:
+    roles = list(user_data.get("roles", []))
+    if user_data.get("can_publish_posts", False):
+        roles.append("journalist")
+    if user_data.get("can_hack", False):
+        roles.append("hacker")
+
+    return roles
+
 def generate_unique_personal_numbers(count: int) -> list[int]:
     if count > 9000:
         raise ValueError("Error: To many users. Cannot generate unique 4-digit codes.")
@@ -63,7 +87,12 @@ def load_user_data(user_data_path, db_connection):
     with open(user_data_path, "r", encoding="utf-8") as file:
         users = json.load(file)
 
-    password_hasher = PasswordHasher()
+    # Same parameters as the authentication server, so the users are not rehashed at their first login.
+    password_hasher = PasswordHasher(time_cost = PASSWORD_HASH_ITERATIONS,
+                                     memory_cost = PASSWORD_HASH_MEMORY_KIB,
+                                     parallelism = PASSWORD_HASH_PARALLELISM,
+                                     hash_len = 32,
+                                     salt_len = 16)
     personal_numbers = generate_unique_personal_numbers(len(users))
 
     username_to_id = {}
@@ -71,25 +100,31 @@ def load_user_data(user_data_path, db_connection):
     try:
         db_cursor = db_connection.cursor()
 
-        insert_user_sql = """INSERT INTO users (username, password, user_token, personal_number, can_publish_posts, cyber_defence_level, can_hack, extra_data)
-                            VALUES (%s, %s, %s, %s, %s, %s, %s, %s) RETURNING id;"""
+        insert_user_sql = """INSERT INTO users (username, password, password_pepper_version, user_token, personal_number, cyber_defence_level, extra_data)
+                            VALUES (%s, %s, %s, %s, %s, %s, %s) RETURNING id;"""
+        insert_user_roles_sql = """INSERT INTO user_roles (user_id, role_id)
+                                SELECT %s, id FROM roles WHERE name = ANY(%s);"""
         
         for (itr, user_data) in enumerate(users):
             username = user_data["username"]
             password = user_data["password"]
-            can_publish_posts = user_data["can_publish_posts"]
             cyber_defence_level = user_data["cyber_defence_level"]
-            can_hack = user_data["can_hack"]
             extra_data = user_data.get("extra_data", {})
 
-            if not validate_user(username, password, json.dumps(extra_data, ensure_ascii=False).encode("utf-8")):
+            if not validate_user(username, password, cyber_defence_level, json.dumps(extra_data, ensure_ascii=False).encode("utf-8")):
                 continue
 
             hashed_password = hash_password(password, password_hasher)
-            insert_user_params = (username, hashed_password, None, personal_numbers[itr], can_publish_posts, cyber_defence_level, can_hack, Json(extra_data))
+            insert_user_params = (username, hashed_password, PEPPER_VERSION, None, personal_numbers[itr], cyber_defence_level, Json(extra_data))
             db_cursor.execute(insert_user_sql, insert_user_params)
 
             user_id = db_cursor.fetchone()[0]
+
+            roles = set(user_roles(user_data))
+            db_cursor.execute(insert_user_roles_sql, (user_id, list(roles)))
+            if db_cursor.rowcount != len(roles):
+                raise ValueError(f"Unknown role in { sorted(roles) } of user { username }")
+
             username_to_id[username] = user_id
 
     except Exception: